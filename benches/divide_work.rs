@@ -1,5 +1,11 @@
-use and_conquer::{divide_equal_work, divide_work};
-use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use and_conquer::{
+    default_parallelism, divide_equal_work, divide_sort, divide_sum_by, divide_weighted_work,
+    divide_work, divide_work_by_cost, divide_work_shared, divide_work_stealing,
+    divide_work_unordered, CoreSelection, Partition, Schedule, WorkConfig,
+};
+use criterion::{black_box, criterion_group, BatchSize, Criterion};
+use std::sync::Arc;
+use std::time::Duration;
 
 fn bench_small_equal_work(c: &mut Criterion) {
     let input: Vec<i32> = (1..1000).collect();
@@ -18,6 +24,183 @@ fn bench_small_equal_work(c: &mut Criterion) {
             BatchSize::SmallInput,
         )
     });
+    group.bench_with_input("work stealing", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work_stealing(black_box(i), black_box(|x| x * 2)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_with_input("unordered", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work_unordered(black_box(i), black_box(|x| x * 2)),
+            BatchSize::SmallInput,
+        )
+    });
+    let shared: Arc<[i32]> = input.into();
+    group.bench_with_input("atomic cursor", &shared, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work_shared(black_box(i), black_box(|x: &i32| x * 2)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Queue backend is chosen at compile time. To compare them, save a baseline with one backend and compare the other one
+/// against it: `cargo bench -- queue_backend --save-baseline std`, then
+/// `cargo bench --features crossbeam -- queue_backend --baseline std`.
+fn bench_queue_backend(c: &mut Criterion) {
+    let input: Vec<i32> = (0..10_000).collect();
+    let mut group = c.benchmark_group("queue_backend");
+    // Single element batches, so that workers contend for the queue on every element
+    let config = WorkConfig::new().batch_size(1);
+    group.bench_with_input("cheap items", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| config.divide_work(black_box(i), black_box(|x| x * 2)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_large_elements(c: &mut Criterion) {
+    let input: Vec<[u8; 1024]> = (0..10_000).map(|x| [x as u8; 1024]).collect();
+    let mut group = c.benchmark_group("large_elements");
+    group.bench_with_input("equal", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_equal_work(black_box(i), black_box(|x: [u8; 1024]| x[0])),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_large_results(c: &mut Criterion) {
+    let input: Vec<u8> = (0..10_000).map(|x| x as u8).collect();
+    let mut group = c.benchmark_group("large_results");
+    group.bench_with_input("vec mtx", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work(black_box(i), black_box(|x: u8| [x; 4096])),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Cost of an element grows linearly with its index
+fn growing_cost(x: u64) -> u64 {
+    (0..x * 100).fold(0, |acc, i| black_box(acc ^ i))
+}
+
+fn bench_positional_skew(c: &mut Criterion) {
+    let input: Vec<u64> = (0..1000).collect();
+    let mut group = c.benchmark_group("positional_skew");
+    for partition in [Partition::Contiguous, Partition::Strided] {
+        let config = WorkConfig::new().partition(partition);
+        group.bench_with_input(format!("{:?}", partition), &input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_equal_work(black_box(i), black_box(growing_cost)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// First 10% of elements are 100 times more expensive than the rest
+fn hot_spot_cost(x: u64) -> u64 {
+    let iterations = if x < 100 { 10_000 } else { 100 };
+    (0..iterations).fold(x, |acc, i| black_box(acc ^ i))
+}
+
+fn bench_hot_spot(c: &mut Criterion) {
+    let input: Vec<u64> = (0..1000).collect();
+    let mut group = c.benchmark_group("hot_spot");
+    for partition in [Partition::Contiguous, Partition::Shuffled { seed: 0 }] {
+        let config = WorkConfig::new().partition(partition);
+        group.bench_with_input(format!("{:?}", partition), &input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_equal_work(black_box(i), black_box(hot_spot_cost)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.bench_with_input("vec mtx", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work(black_box(i), black_box(hot_spot_cost)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Pareto distributed weights with shape 1.16 (80-20 rule), from a fixed sequence of uniform samples
+fn pareto_weights(len: usize) -> Vec<u64> {
+    let mut state: u64 = 0x853c_49e6_748f_ea9b;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let uniform = ((state >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+            (100.0 / uniform.powf(1.0 / 1.16)).min(1e6) as u64
+        })
+        .collect()
+}
+
+fn weighted_cost(weight: u64) -> u64 {
+    (0..weight).fold(0, |acc, i| black_box(acc ^ i))
+}
+
+fn bench_weighted_work(c: &mut Criterion) {
+    let input = pareto_weights(1000);
+    let mut group = c.benchmark_group("weighted_work");
+    group.bench_with_input("equal", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_equal_work(black_box(i), black_box(weighted_cost)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_with_input("weighted", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_weighted_work(black_box(i), |&w| w, black_box(weighted_cost)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_batch_size(c: &mut Criterion) {
+    let input: Vec<i32> = (0..100_000).collect();
+    let mut group = c.benchmark_group("batch_size");
+    group.bench_with_input("default", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work(black_box(i), black_box(|x| x * 2)),
+            BatchSize::LargeInput,
+        )
+    });
+    for chunk in [1, 4, 16, 64] {
+        let config = WorkConfig::new().dynamic_chunk(chunk);
+        group.bench_with_input(format!("chunk {}", chunk), &input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_work(black_box(i), black_box(|x| x * 2)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
     group.finish();
 }
 
@@ -46,8 +229,246 @@ fn bench_unequal_work(c: &mut Criterion) {
             BatchSize::SmallInput,
         )
     });
+    group.bench_with_input("work stealing", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work_stealing(black_box(i), black_box(fibonacci)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_with_input("by cost", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work_by_cost(black_box(i), |&n| n, black_box(fibonacci)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Sum without collecting an output against summing the output of `divide_work`
+fn bench_sum(c: &mut Criterion) {
+    let input: Vec<u64> = (0..10_000_000).collect();
+    let mut group = c.benchmark_group("sum");
+    group.sample_size(10);
+    group.bench_with_input("divide_work", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| {
+                divide_work(black_box(i), black_box(|x| x * 2))
+                    .into_iter()
+                    .sum::<u64>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_with_input("divide_sum_by", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_sum_by::<_, _, _, u64>(black_box(i), black_box(|x| x * 2)),
+            BatchSize::LargeInput,
+        )
+    });
     group.finish();
 }
 
-criterion_group!(benches, bench_small_equal_work, bench_unequal_work,);
-criterion_main!(benches);
+/// Parallel sort against `slice::sort`, on random and on already sorted input
+fn bench_sort(c: &mut Criterion) {
+    // Xorshift is enough to scramble the input without a dependency
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let random: Vec<u64> = (0..10_000_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect();
+    let sorted: Vec<u64> = (0..10_000_000).collect();
+    let mut group = c.benchmark_group("sort");
+    group.sample_size(10);
+    for (name, input) in [("random", &random), ("sorted", &sorted)] {
+        group.bench_with_input(format!("slice::sort {}", name), input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |mut i| {
+                    black_box(&mut i).sort();
+                    i
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(format!("divide_sort {}", name), input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| divide_sort(black_box(i)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Workers started on demand against all of them at once, on small inputs where spawn cost is comparable to the work
+fn bench_adaptive_spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adaptive_spawn");
+    for len in [20, 100] {
+        let input: Vec<u64> = (0..len).map(|_| 15).collect();
+        for (name, adaptive_spawn) in [("eager", false), ("adaptive", true)] {
+            let config = WorkConfig::new()
+                .threshold(1)
+                .adaptive_spawn(adaptive_spawn);
+            group.bench_with_input(format!("{} {}", name, len), &input, |b, i| {
+                b.iter_batched(
+                    || i.clone(),
+                    |i| config.divide_work(black_box(i), black_box(fibonacci)),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Guided schedule against the dynamic and static ones, both on cheap uniform work and on work of unequal cost
+fn bench_schedule(c: &mut Criterion) {
+    let schedules = [
+        ("dynamic", Schedule::Dynamic),
+        ("static", Schedule::Static),
+        ("guided", Schedule::Guided { min_chunk: 1 }),
+    ];
+    let cheap: Vec<u64> = (0..100_000).collect();
+    let unequal: Vec<u64> = (0..30).collect();
+    let mut group = c.benchmark_group("schedule");
+    for (name, schedule) in schedules {
+        let config = WorkConfig::new().schedule(schedule);
+        group.bench_with_input(format!("{} cheap", name), &cheap, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_work(black_box(i), black_box(|x| x * 2)),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(format!("{} fibonacci", name), &unequal, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_work(black_box(i), black_box(fibonacci)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Vectorized arithmetic keeps execution ports of a core busy, so SMT siblings mostly slow each other down. On machines
+/// with SMT, physical cores are expected to be on par with logical ones or faster.
+fn bench_core_selection(c: &mut Criterion) {
+    let input: Vec<Vec<f32>> = (0..256).map(|x| vec![x as f32; 16 * 1024]).collect();
+    let dot = |v: Vec<f32>| {
+        (0..64)
+            .map(|_| v.iter().map(|x| x * x).sum::<f32>())
+            .sum::<f32>()
+    };
+    let mut group = c.benchmark_group("core_selection");
+    for (name, cores) in [
+        ("logical", CoreSelection::Logical),
+        ("physical", CoreSelection::Physical),
+    ] {
+        let config = WorkConfig::new().cores(cores);
+        group.bench_with_input(name, &input, |b, i| {
+            b.iter_batched(
+                || i.clone(),
+                |i| config.divide_equal_work(black_box(i), black_box(dot)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_io_bound_work(c: &mut Criterion) {
+    let input: Vec<u64> = (0..64).collect();
+    let sleep = |x| {
+        std::thread::sleep(Duration::from_millis(1));
+        x
+    };
+    let mut group = c.benchmark_group("io_bound_work");
+    group.sample_size(10);
+    group.bench_with_input("default", &input, |b, i| {
+        b.iter_batched(
+            || i.clone(),
+            |i| divide_work(black_box(i), black_box(sleep)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_with_input("io_bound x4", &input, |b, i| {
+        let config = WorkConfig::io_bound(4.0);
+        b.iter_batched(
+            || i.clone(),
+            |i| config.divide_work(black_box(i), black_box(sleep)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Sums buffers that are too large for caches, so memory bandwidth is the bottleneck. Buffers are allocated by the
+/// workers that sum them, so with NUMA awareness each of them is local to the node that reads it. Only shows a
+/// difference on multi-socket hardware: `cargo bench --features numa -- numa`.
+#[cfg(feature = "numa")]
+fn bench_numa(c: &mut Criterion) {
+    const BUFFERS: usize = 256;
+    const BUFFER_LEN: usize = 1 << 16;
+    let sum = |buf: Vec<u64>| buf.iter().sum::<u64>();
+    let mut group = c.benchmark_group("numa");
+    group.sample_size(10);
+    for (name, numa) in [("default", false), ("numa", true)] {
+        let config = WorkConfig::new().numa(numa);
+        group.bench_function(format!("{} equal", name), |b| {
+            b.iter_batched(
+                || config.divide_equal_work((0..BUFFERS as u64).collect(), |x| vec![x; BUFFER_LEN]),
+                |i| config.divide_equal_work(black_box(i), black_box(sum)),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(format!("{} dynamic", name), |b| {
+            b.iter_batched(
+                || config.divide_work((0..BUFFERS as u64).collect(), |x| vec![x; BUFFER_LEN]),
+                |i| config.divide_work(black_box(i), black_box(sum)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "numa")]
+criterion_group!(numa_benches, bench_numa);
+
+criterion_group!(
+    benches,
+    bench_small_equal_work,
+    bench_batch_size,
+    bench_large_elements,
+    bench_large_results,
+    bench_queue_backend,
+    bench_unequal_work,
+    bench_adaptive_spawn,
+    bench_sum,
+    bench_sort,
+    bench_positional_skew,
+    bench_hot_spot,
+    bench_weighted_work,
+    bench_schedule,
+    bench_core_selection,
+    bench_io_bound_work,
+);
+
+fn main() {
+    println!("Default parallelism: {}", default_parallelism());
+
+    benches();
+    #[cfg(feature = "numa")]
+    numa_benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,272 @@
+//! Pinning of worker threads to CPU cores, enabled with [WorkConfig::pin_threads](crate::WorkConfig::pin_threads).
+//! Only Linux is supported, on other platforms every attempt to pin fails with [io::ErrorKind::Unsupported]. Threads
+//! of the pools outlive the calls, so the original affinity of a thread is restored once its job is done.
+
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+thread_local! {
+    /// Core this thread is pinned to for the job it is running
+    static PINNED: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Core the current thread is pinned to, if it is a worker that was pinned successfully
+pub(crate) fn pinned_core() -> Option<usize> {
+    PINNED.with(Cell::get)
+}
+
+/// Cores the current thread is allowed to run on, if they can be determined
+#[cfg(feature = "numa")]
+pub(crate) fn available_cores() -> Option<Vec<usize>> {
+    sys::get().ok().map(|set| sys::cores(&set))
+}
+
+/// The OS failed to pin a worker thread to a core. The worker runs without pinning instead.
+#[derive(Debug)]
+pub struct PinError {
+    core: Option<usize>,
+    error: io::Error,
+}
+
+impl PinError {
+    /// Core the worker was supposed to be pinned to. It is unknown if the cores available to the thread couldn't be
+    /// determined.
+    pub fn core(&self) -> Option<usize> {
+        self.core
+    }
+
+    /// Error reported by the OS
+    pub fn io_error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.core {
+            Some(core) => write!(
+                f,
+                "failed to pin worker thread to core {}: {}",
+                core, self.error
+            ),
+            None => write!(f, "failed to pin worker thread: {}", self.error),
+        }
+    }
+}
+
+impl Error for PinError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Pinning of worker number `index`, and where failures are reported
+#[derive(Debug, Clone)]
+pub(crate) struct Pin {
+    /// Cores that workers are pinned to in turn. If there is no list, these are the cores available to the thread.
+    pub(crate) cores: Option<Arc<[usize]>>,
+    pub(crate) index: usize,
+    pub(crate) hook: Option<fn(&PinError)>,
+}
+
+impl Pin {
+    /// Runs `job` with the current thread pinned to its core. If pinning fails, the error is reported to the hook and
+    /// `job` runs as is.
+    pub(crate) fn run<R>(self, job: impl FnOnce() -> R) -> R {
+        struct Restore(Option<sys::CpuSet>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                PINNED.with(|pinned| pinned.set(None));
+                if let Some(original) = &self.0 {
+                    // Nothing can be done about failure at this point, and the thread still works either way
+                    let _ = sys::set(original);
+                }
+            }
+        }
+
+        let mut core = self
+            .cores
+            .as_ref()
+            .map(|cores| cores[self.index % cores.len()]);
+        let res = sys::get().and_then(|original| {
+            let target = match core {
+                Some(core) => core,
+                None => {
+                    let available = sys::cores(&original);
+                    let target = available[self.index % available.len()];
+                    *core.insert(target)
+                }
+            };
+            sys::pin(target)?;
+            Ok((original, target))
+        });
+        let _restore = match res {
+            Ok((original, target)) => {
+                PINNED.with(|pinned| pinned.set(Some(target)));
+                Restore(Some(original))
+            }
+            Err(error) => {
+                if let Some(hook) = self.hook {
+                    hook(&PinError { core, error });
+                }
+                Restore(None)
+            }
+        };
+        job()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+    use std::mem;
+
+    pub(super) type CpuSet = libc::cpu_set_t;
+
+    /// Affinity of the current thread
+    pub(super) fn get() -> io::Result<CpuSet> {
+        // SAFETY: `cpu_set_t` is a plain bit mask, for which all zeroes is a valid value
+        let mut set: CpuSet = unsafe { mem::zeroed() };
+        // SAFETY: Size matches the mask that the OS writes to
+        let res = unsafe { libc::sched_getaffinity(0, mem::size_of::<CpuSet>(), &mut set) };
+        if res == 0 {
+            Ok(set)
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Cores in `set`. The current thread always runs on one of them, so there is at least one.
+    pub(super) fn cores(set: &CpuSet) -> Vec<usize> {
+        let cores = (0..libc::CPU_SETSIZE as usize)
+            // SAFETY: `core` is within the mask
+            .filter(|&core| unsafe { libc::CPU_ISSET(core, set) })
+            .collect::<Vec<_>>();
+        if cores.is_empty() {
+            vec![0]
+        } else {
+            cores
+        }
+    }
+
+    /// Sets affinity of the current thread
+    pub(super) fn set(set: &CpuSet) -> io::Result<()> {
+        // SAFETY: Size matches the mask that the OS reads from
+        let res = unsafe { libc::sched_setaffinity(0, mem::size_of::<CpuSet>(), set) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Pins the current thread to `core`
+    pub(super) fn pin(core: usize) -> io::Result<()> {
+        if core >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "core is out of range of the affinity mask",
+            ));
+        }
+        // SAFETY: Same as in `get`
+        let mut mask: CpuSet = unsafe { mem::zeroed() };
+        // SAFETY: `core` is within the mask
+        unsafe { libc::CPU_SET(core, &mut mask) };
+        set(&mask)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+
+    pub(super) type CpuSet = ();
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pinning threads isn't supported on this platform",
+        )
+    }
+
+    pub(super) fn get() -> io::Result<CpuSet> {
+        Err(unsupported())
+    }
+
+    pub(super) fn cores(_: &CpuSet) -> Vec<usize> {
+        vec![0]
+    }
+
+    pub(super) fn set(_: &CpuSet) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(super) fn pin(_: usize) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Schedule, WorkConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_failure(err: &super::PinError) {
+        assert_eq!(err.core(), Some(100_000));
+        FAILURES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn pinned_cores_in_stats() {
+        let caller = thread::current().id();
+        let (output, stats) = WorkConfig::new()
+            .threads(3)
+            .pin_threads(true)
+            .divide_work_with_stats((0..300).collect(), |x| {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                x * 2
+            });
+        assert_eq!(output, (0..300).map(|x| x * 2).collect::<Vec<_>>());
+        for worker in &stats.workers {
+            if worker.thread == caller {
+                // Calling thread is never pinned
+                assert_eq!(worker.core, None);
+            } else if cfg!(target_os = "linux") {
+                assert!(worker.core.is_some());
+            }
+        }
+        assert!(super::pinned_core().is_none());
+    }
+
+    #[test]
+    fn explicit_cores() {
+        let (_, stats) = WorkConfig::new()
+            .threads(2)
+            .schedule(Schedule::Static)
+            .pin_cores(vec![0])
+            .divide_work_with_stats((0..20).collect(), |x| x);
+        for worker in stats.workers {
+            if worker.thread != thread::current().id() && cfg!(target_os = "linux") {
+                assert_eq!(worker.core, Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn pin_failure_is_reported() {
+        let output = WorkConfig::new()
+            .threads(3)
+            .pin_cores(vec![100_000])
+            .pin_failure_hook(count_failure)
+            .divide_equal_work((0..30).collect(), |x| x + 1);
+        assert_eq!(output, (1..31).collect::<Vec<_>>());
+        assert_eq!(FAILURES.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Cancels work of [divide_work_cancellable](crate::divide_work_cancellable) from another thread, for example when
+/// the result is no longer needed. Clones share the same state, so cancelling any of them cancels all calls that use
+/// any of the clones. Once cancelled, the token stays cancelled, and following calls that use it return right away.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Creates a token that isn't cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tells all calls that use this token to stop picking up new elements. Elements that are already being processed
+    /// are finished.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [cancel](CancelToken::cancel) was called on this token or any of its clones
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Error of [divide_work_cancellable](crate::divide_work_cancellable). Results of processed elements are placed at
+/// their indices in `completed`, and elements that weren't processed are returned in `remaining` in input order, with
+/// `None` at their indices in `completed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cancelled<T, R> {
+    /// Result of every element of the input at its index, `None` for elements that weren't processed
+    pub completed: Vec<Option<R>>,
+    /// Elements that weren't processed, in input order
+    pub remaining: Vec<T>,
+}
+
+impl<T, R> fmt::Display for Cancelled<T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "work was cancelled with {} elements remaining",
+            self.remaining.len()
+        )
+    }
+}
+
+impl<T: fmt::Debug, R: fmt::Debug> Error for Cancelled<T, R> {}
+
+/// Conditions under which workers stop picking up new elements, in addition to errors and panics
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Until {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) token: Option<CancelToken>,
+}
+
+impl Until {
+    /// Whether any of the conditions is met
+    pub(crate) fn reached(&self) -> bool {
+        self.token.as_ref().is_some_and(CancelToken::is_cancelled)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WorkConfig, WorkError};
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn cancel_from_other_thread() {
+        let token = CancelToken::new();
+        let processed = Arc::new(AtomicUsize::new(0));
+
+        let canceller = {
+            let token = token.clone();
+            let processed = processed.clone();
+            std::thread::spawn(move || {
+                while processed.load(Ordering::Relaxed) < 10 {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                token.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        let counter = processed.clone();
+        // Takes more than a second if not cancelled
+        let output = WorkConfig::new().threads(4).divide_work_cancellable(
+            (0..1000).collect(),
+            &token,
+            move |x| {
+                std::thread::sleep(Duration::from_millis(5));
+                counter.fetch_add(1, Ordering::Relaxed);
+                x * 2
+            },
+        );
+        canceller.join().unwrap();
+
+        let err = output.unwrap_err();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(err.completed.len(), 1000);
+        let processed = err.completed.iter().filter(|r| r.is_some()).count();
+        assert_eq!(processed + err.remaining.len(), 1000);
+        assert!((10..100).contains(&processed));
+        for (x, res) in err.completed.iter().enumerate() {
+            match res {
+                Some(r) => assert_eq!(*r, x * 2),
+                None => assert!(err.remaining.binary_search(&x).is_ok()),
+            }
+        }
+    }
+
+    #[test]
+    fn token_reused() {
+        let token = CancelToken::new();
+        let config = WorkConfig::new().threads(4);
+
+        let output = config.divide_work_cancellable((0..100).collect(), &token, |x| x + 1);
+        assert_eq!(output, Ok((1..101).collect()));
+
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        let output = config.divide_work_cancellable((0..100).collect(), &token, |x: usize| x + 1);
+        let err = output.unwrap_err();
+        assert_eq!(err.remaining, (0..100).collect::<Vec<_>>());
+        assert!(err.completed.iter().all(Option::is_none));
+        assert_eq!(
+            err.to_string(),
+            "work was cancelled with 100 elements remaining"
+        );
+
+        let output = crate::divide_work_cancellable((0..100).collect(), &token, |x: usize| x + 1);
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn cancelled_work_error() {
+        let token = CancelToken::new();
+        token.cancel();
+        let run = || -> Result<Vec<usize>, WorkError> {
+            let output = WorkConfig::new().threads(4).divide_work_cancellable(
+                (0..100).collect(),
+                &token,
+                |x| x + 1,
+            )?;
+            Ok(output)
+        };
+        assert!(matches!(run(), Err(WorkError::Cancelled)));
+    }
+}
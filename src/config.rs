@@ -0,0 +1,3882 @@
+#[cfg(feature = "affinity")]
+use crate::affinity::{Pin, PinError};
+use crate::cancel::{CancelToken, Cancelled, Until};
+use crate::deadline::{self, ItemCtx};
+#[cfg(feature = "numa")]
+use crate::numa;
+use crate::stats::{Collector, WorkStats};
+use crate::worker::{self, DynFn, OsSpawner, ScopedSpawner, Spawner, StopFlag};
+use crate::{
+    cursor, dynamic, equal, output, scan, sort, steal, threads, ItemPanic, ItemTimedOut,
+    PanicOrError, SpawnError, WorkError,
+};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{Infallible, TryFrom};
+use std::hash::Hash;
+use std::iter::{self, Product, Sum};
+use std::ops::{Add, ControlFlow};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Upper bound for the amount of worker threads produced by oversubscription
+const MAX_OVERSUBSCRIBED_THREADS: usize = 512;
+
+/// Estimated sequential run time above which adaptive mode splits work, unless configured otherwise
+const DEFAULT_ADAPTIVE_TARGET: Duration = Duration::from_millis(1);
+
+/// Explains why work was or wasn't split between threads. Returned by
+/// [WorkConfig::divide_work_with_decision] and [WorkConfig::divide_equal_work_with_decision] for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Work was done sequentially, because it was forced with [set_sequential](crate::set_sequential)
+    ForcedSequential,
+    /// Work was done sequentially, because the call was made from `f` of another call, see [WorkConfig::nested]
+    Nested,
+    /// There are not enough worker threads to split work, work was done sequentially
+    SingleThread,
+    /// Input had less than `threshold` elements, work was done sequentially
+    BelowThreshold { len: usize, threshold: usize },
+    /// Input had at least `threshold` elements, work was split between threads
+    AboveThreshold { len: usize, threshold: usize },
+    /// Adaptive mode processed one element in `sample` time, and estimated that processing all elements sequentially
+    /// takes `estimate`. Work was split between threads if `estimate` is at least `target`.
+    Estimated {
+        sample: Duration,
+        estimate: Duration,
+        target: Duration,
+    },
+}
+
+impl Decision {
+    /// Whether work was split between threads
+    pub fn is_parallel(&self) -> bool {
+        match *self {
+            Decision::ForcedSequential
+            | Decision::Nested
+            | Decision::SingleThread
+            | Decision::BelowThreshold { .. } => false,
+            Decision::AboveThreshold { .. } => true,
+            Decision::Estimated {
+                estimate, target, ..
+            } => estimate >= target,
+        }
+    }
+}
+
+/// Result of [divide_work_timeout](crate::divide_work_timeout)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeoutOutcome<T, R> {
+    /// The whole input was processed before the deadline
+    Completed(Vec<R>),
+    /// Deadline has passed before the whole input was processed. Results of processed elements are placed at their
+    /// indices in `completed`, and elements that weren't processed are returned in `remaining` in input order, with
+    /// `None` at their indices in `completed`.
+    TimedOut {
+        completed: Vec<Option<R>>,
+        remaining: Vec<T>,
+    },
+}
+
+impl<T, R> TimeoutOutcome<T, R> {
+    /// Results of the whole input, or [WorkError::TimedOut] with partial results discarded if deadline has passed
+    pub fn into_result<E>(self) -> Result<Vec<R>, WorkError<E>> {
+        match self {
+            TimeoutOutcome::Completed(output) => Ok(output),
+            TimeoutOutcome::TimedOut { .. } => Err(WorkError::TimedOut),
+        }
+    }
+}
+
+/// Side of the output of [divide_partition_map](crate::divide_partition_map) that a result goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<L, R> {
+    /// Goes to the first vector
+    Left(L),
+    /// Goes to the second vector
+    Right(R),
+}
+
+impl<L, R> Either<L, R> {
+    /// Appends the value to the vector of its side
+    pub(crate) fn push_to(self, left: &mut Vec<L>, right: &mut Vec<R>) {
+        match self {
+            Either::Left(l) => left.push(l),
+            Either::Right(r) => right.push(r),
+        }
+    }
+}
+
+/// Which error is reported for an element when all attempts of [WorkConfig::try_divide_work_retrying] fail. Set with
+/// [WorkConfig::report_error].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportedError {
+    /// Error of the first attempt
+    First,
+    /// Error of the last attempt. This is the default.
+    Last,
+}
+
+/// What happens when `f` panics. Set with [WorkConfig::panic_policy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Other workers keep processing the rest of the input, then the panic is propagated to the caller with its
+    /// original payload. Useful when `f` has side effects that should be applied to as many elements as possible.
+    Propagate,
+    /// Elements for which `f` panicked are left out of the output, and work continues with the rest of the input.
+    /// Panics are caught as in [divide_work_catch](crate::divide_work_catch), with the same unwind safety caveats.
+    /// Use [WorkConfig::divide_work_skipping] to know which elements were skipped. Other variants, like
+    /// [try_divide_work](crate::try_divide_work) and [divide_work_timeout](crate::divide_work_timeout), treat this the
+    /// same as [PanicPolicy::CancelAll].
+    SkipItem,
+    /// Other workers stop picking up new elements as soon as `f` panics, then the panic is propagated to the caller
+    /// with its original payload once elements that were already being processed are finished. This is the default.
+    CancelAll,
+}
+
+/// How [divide_equal_work](crate::divide_equal_work) splits input between workers. Set with [WorkConfig::partition].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    /// Each worker gets a contiguous range of the input. This is the default.
+    Contiguous,
+    /// Worker `k` of `n` gets elements `k`, `k + n`, `k + 2n` and so on. Balances work whose cost depends on position
+    /// of the element, for example when later elements are more expensive, without any scheduling at runtime.
+    Strided,
+    /// Input is shuffled with a permutation determined by `seed`, split into contiguous ranges, and results are put
+    /// back in input order. Smears clusters of expensive elements between workers at the cost of moving every element
+    /// twice, which is cheaper than dynamic scheduling for mildly skewed work.
+    Shuffled {
+        /// Same seed always gives the same permutation
+        seed: u64,
+    },
+}
+
+/// How [divide_work](crate::divide_work) and [try_divide_work](crate::try_divide_work) hand out elements to workers.
+/// Set with [WorkConfig::schedule].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Workers take elements from a shared queue in batches set with [WorkConfig::batch_size]. This is the default.
+    Dynamic,
+    /// Input is split into equal chunks up front, same as with [divide_equal_work](crate::divide_equal_work), including
+    /// its [Partition].
+    Static,
+    /// Workers take `max(min_chunk, remaining / (workers * 2))` elements from a shared queue at once, so the first
+    /// chunks are large and cheap to hand out, while the end of the input is split finely between workers. Overrides
+    /// [WorkConfig::batch_size]. With `crossbeam` feature elements are taken one at a time, same as with
+    /// [Schedule::Dynamic].
+    Guided {
+        /// Smallest amount of elements taken at once, `0` behaves the same as `1`
+        min_chunk: usize,
+    },
+}
+
+/// Which cores are counted for the default amount of worker threads. Set with [WorkConfig::cores].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreSelection {
+    /// Every hardware thread counts as a core, see [default_parallelism](crate::default_parallelism). This is the
+    /// default.
+    Logical,
+    /// Hardware threads that share a physical core count once, see [physical_parallelism](crate::physical_parallelism).
+    /// Compute-dense closures, like SIMD-heavy numeric work, often run faster this way, since SMT siblings compete for
+    /// the same caches and execution ports.
+    Physical,
+    /// Machine is assumed to have this many cores, `0` behaves the same as `1`
+    Exact(usize),
+}
+
+/// What to do if the OS fails to create a worker thread. Set with [WorkConfig::on_spawn_failure].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnFailurePolicy {
+    /// Stop the workers that were already spawned and report the failure. [divide_work](crate::divide_work) and
+    /// [divide_equal_work](crate::divide_equal_work) panic, checked variants return [SpawnError]. This is the default.
+    Fail,
+    /// Continue with the workers that were already spawned. Chunks of work that were meant for the threads that
+    /// failed to spawn are processed on the calling thread. Failure is reported only if no worker could be spawned.
+    Degrade,
+}
+
+/// Tuning knobs shared by [divide_work](crate::divide_work) and [divide_equal_work](crate::divide_equal_work).
+///
+/// Default configuration is the one used by the free functions of this crate, so
+/// `WorkConfig::default().divide_work(input, f)` is the same as `divide_work(input, f)`. Configuration is cheap to
+/// clone and can be stored once and reused for any number of calls.
+///
+/// ```
+/// use and_conquer::WorkConfig;
+///
+/// let config = WorkConfig::new().threads(4).threshold(100);
+/// let output = config.divide_work((0..1000).collect(), |x| x * 2);
+/// assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+/// ```
+#[derive(Debug, Clone)]
+pub struct WorkConfig {
+    threads: Option<usize>,
+    cores: CoreSelection,
+    oversubscription: f64,
+    reserved_cores: usize,
+    threshold: Option<usize>,
+    adaptive_target: Option<Duration>,
+    retries: usize,
+    reported_error: ReportedError,
+    pub(crate) min_chunk_size: usize,
+    pub(crate) batch_size: Option<usize>,
+    pub(crate) partition: Partition,
+    pub(crate) schedule: Schedule,
+    pub(crate) thread_name_prefix: Option<String>,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) spawn_per_call: bool,
+    pub(crate) adaptive_spawn: bool,
+    nested: bool,
+    shrink_output: bool,
+    panic_policy: PanicPolicy,
+    pub(crate) spawn_failure_policy: SpawnFailurePolicy,
+    pub(crate) spawn_failure_hook: Option<fn(&SpawnError)>,
+    #[cfg(feature = "affinity")]
+    pin_threads: bool,
+    #[cfg(feature = "affinity")]
+    pin_cores: Option<Arc<[usize]>>,
+    #[cfg(feature = "affinity")]
+    pin_failure_hook: Option<fn(&PinError)>,
+    #[cfg(feature = "numa")]
+    numa: bool,
+}
+
+impl Default for WorkConfig {
+    fn default() -> Self {
+        WorkConfig {
+            threads: None,
+            cores: CoreSelection::Logical,
+            oversubscription: 1.0,
+            reserved_cores: 0,
+            threshold: None,
+            adaptive_target: None,
+            retries: 0,
+            reported_error: ReportedError::Last,
+            min_chunk_size: 1,
+            batch_size: None,
+            partition: Partition::Contiguous,
+            schedule: Schedule::Dynamic,
+            thread_name_prefix: None,
+            stack_size: None,
+            spawn_per_call: false,
+            adaptive_spawn: false,
+            nested: false,
+            shrink_output: false,
+            panic_policy: PanicPolicy::CancelAll,
+            spawn_failure_policy: SpawnFailurePolicy::Fail,
+            spawn_failure_hook: None,
+            #[cfg(feature = "affinity")]
+            pin_threads: false,
+            #[cfg(feature = "affinity")]
+            pin_cores: None,
+            #[cfg(feature = "affinity")]
+            pin_failure_hook: None,
+            #[cfg(feature = "numa")]
+            numa: false,
+        }
+    }
+}
+
+impl WorkConfig {
+    /// Creates default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets amount of worker threads. By default [default_parallelism](crate::default_parallelism) threads are used.
+    /// `0` or `1` means that work is done sequentially on the calling thread.
+    pub fn threads(mut self, n_threads: usize) -> Self {
+        self.threads = Some(n_threads);
+        self
+    }
+
+    /// Sets which cores are counted for the default amount of worker threads, it is then adjusted by
+    /// [reserve_cores](WorkConfig::reserve_cores) and [oversubscribe](WorkConfig::oversubscribe). Explicit
+    /// [threads](WorkConfig::threads) take precedence. Default is [CoreSelection::Logical].
+    ///
+    /// ```
+    /// use and_conquer::{CoreSelection, WorkConfig};
+    ///
+    /// let config = WorkConfig::new().cores(CoreSelection::Physical);
+    /// let output = config.divide_work((0..1000).collect(), |x: u64| x.pow(2));
+    /// assert_eq!(output[999], 998_001);
+    /// ```
+    pub fn cores(mut self, selection: CoreSelection) -> Self {
+        self.cores = selection;
+        self
+    }
+
+    /// Creates configuration for closures that spend most of their time blocked on IO. Same as
+    /// `WorkConfig::new().oversubscribe(factor)`.
+    pub fn io_bound(factor: f64) -> Self {
+        Self::new().oversubscribe(factor)
+    }
+
+    /// Multiplies default amount of worker threads by `factor`. This is useful when `f` spends most of its time
+    /// blocked on disk or network, since one thread per core leaves the machine underutilized. Resulting amount of
+    /// threads is rounded and clamped between 1 and 512. Explicit [threads](WorkConfig::threads) take precedence.
+    pub fn oversubscribe(mut self, factor: f64) -> Self {
+        self.oversubscription = factor;
+        self
+    }
+
+    /// Leaves `n` cores free for the rest of the application by subtracting them from the default amount of worker
+    /// threads, which never goes below 1. Default is `0`. Applied before [oversubscribe](WorkConfig::oversubscribe),
+    /// explicit [threads](WorkConfig::threads) take precedence.
+    pub fn reserve_cores(mut self, n: usize) -> Self {
+        self.reserved_cores = n;
+        self
+    }
+
+    /// Sets minimal amount of elements required to split work between threads. By default process-wide
+    /// [parallel_threshold](crate::parallel_threshold) is used. Threshold of `0` or `1` means that work is split
+    /// whenever there is more than one element.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Creates adaptive configuration with 1 ms target. Same as `WorkConfig::new().adaptive_target(1 ms)`.
+    pub fn adaptive() -> Self {
+        Self::new().adaptive_target(DEFAULT_ADAPTIVE_TARGET)
+    }
+
+    /// Enables adaptive mode, which decides whether to split work by cost of `f` instead of the threshold. One element
+    /// is processed sequentially to estimate how long it takes to process the whole input, and work is split only if
+    /// the estimate is at least `target`. Result of the sampled element is placed in the output like any other.
+    pub fn adaptive_target(mut self, target: Duration) -> Self {
+        self.adaptive_target = Some(target);
+        self
+    }
+
+    /// Sets how many extra times [try_divide_work_retrying](WorkConfig::try_divide_work_retrying) calls `f` for an
+    /// element after it fails, before treating the error as final. Default is `0`.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets which error is reported when all attempts to process an element fail. Default is [ReportedError::Last].
+    pub fn report_error(mut self, reported_error: ReportedError) -> Self {
+        self.reported_error = reported_error;
+        self
+    }
+
+    /// Sets minimal amount of elements in a chunk for [divide_equal_work](WorkConfig::divide_equal_work). With cheap
+    /// `f` small chunks are not worth the thread spawn overhead, so less threads with larger chunks are used instead:
+    /// `min(threads, len / min_chunk_size)`. Default is `1`, `0` behaves the same as `1`.
+    pub fn min_chunk_size(mut self, min_chunk_size: usize) -> Self {
+        self.min_chunk_size = min_chunk_size.max(1);
+        self
+    }
+
+    /// Sets how [divide_equal_work](WorkConfig::divide_equal_work) splits input between workers. Default is
+    /// [Partition::Contiguous].
+    pub fn partition(mut self, partition: Partition) -> Self {
+        self.partition = partition;
+        self
+    }
+
+    /// Sets amount of elements a worker of [divide_work](WorkConfig::divide_work) takes from the queue at once. With
+    /// cheap `f` workers contend for the queue lock on every element, larger batches make them take it less often at
+    /// the cost of less even distribution of work. By default batch size is `max(1, remaining / (workers * 4))`, so
+    /// batches shrink as the queue drains. `0` behaves the same as `1`.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size.max(1));
+        self
+    }
+
+    /// Sets amount of elements a worker of [divide_work](WorkConfig::divide_work) takes from the queue at once, same as
+    /// [WorkConfig::batch_size]. With `1` every element is taken separately, the last batch may be smaller than
+    /// `chunk`, and results keep the input order either way.
+    pub fn dynamic_chunk(self, chunk: usize) -> Self {
+        self.batch_size(chunk)
+    }
+
+    /// Sets how [divide_work](WorkConfig::divide_work) and [try_divide_work](WorkConfig::try_divide_work) hand out
+    /// elements to workers. Default is [Schedule::Dynamic].
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Sets name prefix of worker threads, so that they are identifiable in profilers and debuggers. Workers are named
+    /// `<prefix>-<index>`, default prefix is `and-conquer`. Calling thread takes part in the work too, its name is
+    /// left unchanged.
+    pub fn thread_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.thread_name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets stack size of spawned worker threads in bytes. By default worker threads use the same stack size as
+    /// [std::thread::spawn]. Calling thread takes part in the work too, its stack size is left unchanged.
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Sets whether worker threads are spawned for every call and joined once it is done, instead of being taken from
+    /// the process-wide pool. Pooled threads are created on first use and parked between calls, see
+    /// [shutdown_pool](crate::shutdown_pool) to stop them. Thread names and stack sizes are the same either way, but
+    /// thread-local values of pooled threads outlive the calls. Default is `false`.
+    pub fn spawn_per_call(mut self, spawn_per_call: bool) -> Self {
+        self.spawn_per_call = spawn_per_call;
+        self
+    }
+
+    /// Sets whether workers of [divide_work](WorkConfig::divide_work) are started as they are needed instead of all at
+    /// once. The calling thread starts processing the queue alone, and after each of its batches starts one more worker
+    /// if the remaining elements, estimated by duration of the batch, are worth the cost of starting it. This saves the
+    /// spawn overhead on small inputs with moderately expensive `f`. Failure to start a worker with this flag is always
+    /// reported to the spawn failure hook and only stops the ramp up, same as [SpawnFailurePolicy::Degrade]. Applies to
+    /// the [Schedule::Dynamic] queue, including [divide_work_init](WorkConfig::divide_work_init) and
+    /// [divide_work_with_state](WorkConfig::divide_work_with_state). Default is `false`.
+    pub fn adaptive_spawn(mut self, adaptive_spawn: bool) -> Self {
+        self.adaptive_spawn = adaptive_spawn;
+        self
+    }
+
+    /// Sets whether calls made from `f` of another call may split work between threads, for example when `f` calls a
+    /// library that uses this crate as well. Outer call already keeps every core busy, so by default nested calls run
+    /// sequentially on the thread that makes them, instead of multiplying amount of threads. Default is `false`.
+    pub fn nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+
+    /// Sets whether returned vectors are shrunk to fit their results. Schedulers allocate exactly what is needed, but
+    /// outputs that drop elements, like the ones of [PanicPolicy::SkipItem], may be left with excess capacity, which
+    /// is worth giving back for results that are kept for long. Default is `false`.
+    pub fn shrink_output(mut self, shrink_output: bool) -> Self {
+        self.shrink_output = shrink_output;
+        self
+    }
+
+    /// Sets what happens when `f` panics. Default is [PanicPolicy::CancelAll].
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Sets what to do if the OS fails to create a worker thread. Default is [SpawnFailurePolicy::Fail].
+    pub fn on_spawn_failure(mut self, policy: SpawnFailurePolicy) -> Self {
+        self.spawn_failure_policy = policy;
+        self
+    }
+
+    /// Sets function that is called with the error whenever a failure to spawn a worker thread is swallowed by
+    /// [SpawnFailurePolicy::Degrade], for example to log it
+    pub fn spawn_failure_hook(mut self, hook: fn(&SpawnError)) -> Self {
+        self.spawn_failure_hook = Some(hook);
+        self
+    }
+
+    /// Sets whether worker number `i` is pinned to core `i` modulo amount of cores available to it, so that the OS
+    /// doesn't migrate workers between cores in the middle of the work. Calling thread, which takes part in the work,
+    /// is never pinned. Workers that fail to be pinned run as usual, see [WorkConfig::pin_failure_hook]. Default is
+    /// `false`.
+    #[cfg(feature = "affinity")]
+    pub fn pin_threads(mut self, pin_threads: bool) -> Self {
+        self.pin_threads = pin_threads;
+        self
+    }
+
+    /// Pins worker number `i` to core `cores[i % cores.len()]` instead of the cores available to it. Enables
+    /// [pin_threads](WorkConfig::pin_threads), unless `cores` is empty.
+    #[cfg(feature = "affinity")]
+    pub fn pin_cores(mut self, cores: impl Into<Vec<usize>>) -> Self {
+        let cores = cores.into();
+        self.pin_threads = !cores.is_empty();
+        self.pin_cores = Some(cores.into());
+        self
+    }
+
+    /// Sets function that is called with the error whenever a worker fails to be pinned to a core, for example to log
+    /// it. Pinning is only supported on Linux.
+    #[cfg(feature = "affinity")]
+    pub fn pin_failure_hook(mut self, hook: fn(&PinError)) -> Self {
+        self.pin_failure_hook = Some(hook);
+        self
+    }
+
+    /// Sets whether work is split by NUMA nodes, so that workers of each node process a contiguous region of the
+    /// input, and memory allocated by `f` is local to the node. Workers are pinned to the cores of their node, and
+    /// with dynamic scheduling each node has a queue of its own, taking elements from the queues of other nodes only
+    /// once it is empty. Applies to [WorkConfig::divide_work], [WorkConfig::divide_equal_work], their variants that
+    /// return decisions or statistics, and calls of a [Pool](crate::Pool). Has no effect on a single node, or if
+    /// topology can't be detected, which is the case outside of Linux. Default is `false`.
+    #[cfg(feature = "numa")]
+    pub fn numa(mut self, numa: bool) -> Self {
+        self.numa = numa;
+        self
+    }
+
+    /// Same as [divide_work](crate::divide_work), but uses this configuration
+    #[inline]
+    pub fn divide_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.divide_work_with_decision(input, f).0
+    }
+
+    /// Same as [divide_work_iter](crate::divide_work_iter), but uses this configuration
+    #[inline]
+    pub fn divide_work_iter<I, F, T, R>(&self, input: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.divide_work(input.into_iter().collect(), f)
+    }
+
+    /// Same as [WorkConfig::divide_work], but also explains whether work was split between threads
+    #[inline]
+    pub fn divide_work_with_decision<F, T, R>(&self, input: Vec<T>, f: F) -> (Vec<R>, Decision)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        expect_spawned(self.dynamic(&OsSpawner, input, f))
+    }
+
+    /// Same as [divide_work_with_stats](crate::divide_work_with_stats), but uses this configuration
+    pub fn divide_work_with_stats<F, T, R>(&self, input: Vec<T>, f: F) -> (Vec<R>, WorkStats)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let start = Instant::now();
+        let collector = Arc::new(Collector::new());
+        let recorder = collector.clone();
+        let f = move |val| {
+            let start = Instant::now();
+            let r = f(val);
+            recorder.record(start.elapsed());
+            r
+        };
+        let (output, decision) = self.divide_work_with_decision(input, f);
+        let stats = collector.finish(start.elapsed(), self.schedule, decision);
+        (output, stats)
+    }
+
+    /// Same as [divide_work_checked](crate::divide_work_checked), but uses this configuration
+    pub fn divide_work_checked<F, T, R>(&self, input: Vec<T>, f: F) -> Result<Vec<R>, WorkError>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let input = input.into_iter().enumerate().collect();
+        let output = self.try_divide_work(input, catch_item_panics(f));
+        output.map_err(WorkError::flatten_panic)
+    }
+
+    /// Same as [WorkConfig::divide_work], but leaves the elements for which `f` panicked as `None` and continues with
+    /// the rest of the input, as if [PanicPolicy::SkipItem] was set
+    pub fn divide_work_skipping<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<Option<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = self.run(input, skip_panics(f), |config, input, f| {
+            dynamic::divide_work(config, &OsSpawner, input, f)
+        });
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_work_by_cost](crate::divide_work_by_cost), but uses this configuration
+    pub fn divide_work_by_cost<C, F, T, R>(&self, input: Vec<T>, cost: C, f: F) -> Vec<R>
+    where
+        C: Fn(&T) -> u64,
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = if self.panic_policy == PanicPolicy::SkipItem {
+            self.run(input, skip_panics(f), |config, input, f| {
+                dynamic::divide_work_by_cost(config, &OsSpawner, input, &cost, f)
+            })
+            .map(|(output, decision)| (self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| {
+                dynamic::divide_work_by_cost(config, &OsSpawner, input, &cost, f)
+            })
+        };
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_work_stealing](crate::divide_work_stealing), but uses this configuration
+    pub fn divide_work_stealing<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = if self.panic_policy == PanicPolicy::SkipItem {
+            self.run(input, skip_panics(f), |config, input, f| {
+                steal::divide_work_stealing(config, &OsSpawner, input, f)
+            })
+            .map(|(output, decision)| (self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| {
+                steal::divide_work_stealing(config, &OsSpawner, input, f)
+            })
+        };
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_work_unordered](crate::divide_work_unordered), but uses this configuration
+    pub fn divide_work_unordered<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = if self.panic_policy == PanicPolicy::SkipItem {
+            self.run(input, skip_panics(f), |config, input, f| {
+                dynamic::divide_work_unordered(config, &OsSpawner, input, f)
+            })
+            .map(|(output, decision)| (self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| {
+                dynamic::divide_work_unordered(config, &OsSpawner, input, f)
+            })
+        };
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_for_each](crate::divide_for_each), but uses this configuration. Equal chunks are used for
+    /// [Schedule::Static] and a shared queue otherwise.
+    pub fn divide_for_each<F, T>(&self, input: Vec<T>, f: F)
+    where
+        T: Send + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        if self.schedule == Schedule::Static {
+            self.divide_equal_for_each(input, f)
+        } else {
+            // Results are `()`, and workers of the unordered queue keep them to themselves, so nothing is allocated
+            // for them and nothing is written to a shared output
+            let _: Vec<()> = self.divide_work_unordered(input, f);
+        }
+    }
+
+    /// Same as [divide_equal_for_each](crate::divide_equal_for_each), but uses this configuration
+    pub fn divide_equal_for_each<F, T>(&self, input: Vec<T>, f: F)
+    where
+        T: Send + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        // Chunks collect their results in vectors of `()`, which don't allocate
+        let _: Vec<()> = self.divide_equal_work(input, f);
+    }
+
+    /// Same as [divide_fold](crate::divide_fold), but uses this configuration
+    pub fn divide_fold<I, F, C, T, A>(
+        &self,
+        mut input: Vec<T>,
+        identity: I,
+        fold: F,
+        combine: C,
+    ) -> A
+    where
+        T: Send + 'static,
+        A: Send + 'static,
+        I: Fn() -> A + Send + Sync + 'static,
+        F: Fn(A, T) -> A + Send + Sync + 'static,
+        C: Fn(A, A) -> A,
+    {
+        let (decision, sample) =
+            self.plan_with(input.len(), || input.pop().map(|val| fold(identity(), val)));
+        let acc = if decision.is_parallel() {
+            let accs = expect_spawned(equal::divide_fold(self, &OsSpawner, input, identity, fold));
+            let acc = accs.into_iter().reduce(&combine);
+            acc.expect("there is at least one chunk")
+        } else {
+            input.into_iter().fold(identity(), fold)
+        };
+        // Sampled element is the last one, so it is combined last
+        match sample {
+            Some(sample) => combine(acc, sample),
+            None => acc,
+        }
+    }
+
+    /// Same as [divide_try_fold](crate::divide_try_fold), but uses this configuration
+    pub fn divide_try_fold<I, F, C, T, A, B>(
+        &self,
+        mut input: Vec<T>,
+        identity: I,
+        fold: F,
+        combine: C,
+    ) -> ControlFlow<B, A>
+    where
+        T: Send + 'static,
+        A: Send + 'static,
+        B: Send + 'static,
+        I: Fn() -> A + Send + Sync + 'static,
+        F: Fn(A, T) -> ControlFlow<B, A> + Send + Sync + 'static,
+        C: Fn(A, A) -> A,
+    {
+        let (decision, sample) =
+            self.plan_with(input.len(), || input.pop().map(|val| fold(identity(), val)));
+        // Sampled element is the last one, but there is no need to process the rest of the input if it breaks
+        let sample = match sample {
+            Some(ControlFlow::Break(b)) => return ControlFlow::Break(b),
+            Some(ControlFlow::Continue(sample)) => Some(sample),
+            None => None,
+        };
+        let acc = if decision.is_parallel() {
+            let folded = equal::divide_try_fold(self, &OsSpawner, input, identity, fold);
+            let accs = match expect_spawned(folded) {
+                ControlFlow::Continue(accs) => accs,
+                ControlFlow::Break(b) => return ControlFlow::Break(b),
+            };
+            let acc = accs.into_iter().reduce(&combine);
+            acc.expect("there is at least one chunk")
+        } else {
+            input.into_iter().try_fold(identity(), fold)?
+        };
+        ControlFlow::Continue(match sample {
+            Some(sample) => combine(acc, sample),
+            None => acc,
+        })
+    }
+
+    /// Same as [divide_reduce](crate::divide_reduce), but uses this configuration
+    pub fn divide_reduce<F, T>(&self, input: Vec<T>, op: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: Fn(T, T) -> T + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        let fold = {
+            let op = op.clone();
+            move |acc: Option<T>, val| match acc {
+                Some(acc) => Some(op(acc, val)),
+                None => Some(val),
+            }
+        };
+        // Chunks are never empty, but the sampled element has nothing to be combined with if it is the only one
+        let combine = move |a: Option<T>, b: Option<T>| match (a, b) {
+            (Some(a), Some(b)) => Some(op(a, b)),
+            (a, b) => a.or(b),
+        };
+        self.divide_fold(input, || None, fold, combine)
+    }
+
+    /// Same as [divide_sum](crate::divide_sum), but uses this configuration
+    pub fn divide_sum<T, S>(&self, input: Vec<T>) -> S
+    where
+        T: Send + 'static,
+        S: Sum<T> + Sum<S> + Send + 'static,
+    {
+        self.divide_sum_by(input, |val| val)
+    }
+
+    /// Same as [divide_sum_by](crate::divide_sum_by), but uses this configuration
+    pub fn divide_sum_by<F, T, R, S>(&self, input: Vec<T>, f: F) -> S
+    where
+        T: Send + 'static,
+        R: 'static,
+        S: Sum<R> + Sum<S> + Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        // Additions go through `Sum`, so overflow behaves exactly like in `Iterator::sum`
+        let add = |a: S, b: S| iter::once(a).chain(iter::once(b)).sum();
+        let fold = move |acc, val| add(acc, iter::once(f(val)).sum());
+        self.divide_fold(input, || iter::empty::<S>().sum(), fold, add)
+    }
+
+    /// Same as [divide_product](crate::divide_product), but uses this configuration
+    pub fn divide_product<T, P>(&self, input: Vec<T>) -> P
+    where
+        T: Send + 'static,
+        P: Product<T> + Product<P> + Send + 'static,
+    {
+        let mul = |a: P, b: P| iter::once(a).chain(iter::once(b)).product();
+        let fold = move |acc, val| mul(acc, iter::once(val).product());
+        self.divide_fold(input, || iter::empty::<P>().product(), fold, mul)
+    }
+
+    /// Same as [divide_max_by_key](crate::divide_max_by_key), but uses this configuration
+    pub fn divide_max_by_key<F, T, K>(&self, input: Vec<T>, key: F) -> Option<T>
+    where
+        T: Send + 'static,
+        K: Ord + Send + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        self.select_by_key(input, key, |ord| ord != Ordering::Greater)
+    }
+
+    /// Same as [divide_min_by_key](crate::divide_min_by_key), but uses this configuration
+    pub fn divide_min_by_key<F, T, K>(&self, input: Vec<T>, key: F) -> Option<T>
+    where
+        T: Send + 'static,
+        K: Ord + Send + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        self.select_by_key(input, key, |ord| ord == Ordering::Greater)
+    }
+
+    /// Same as [divide_max_by](crate::divide_max_by), but uses this configuration
+    pub fn divide_max_by<F, T>(&self, input: Vec<T>, compare: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T, &T) -> Ordering + Send + Sync + 'static,
+    {
+        self.divide_reduce(input, move |a, b| match compare(&a, &b) {
+            Ordering::Greater => a,
+            _ => b,
+        })
+    }
+
+    /// Same as [divide_min_by](crate::divide_min_by), but uses this configuration
+    pub fn divide_min_by<F, T>(&self, input: Vec<T>, compare: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T, &T) -> Ordering + Send + Sync + 'static,
+    {
+        self.divide_reduce(input, move |a, b| match compare(&a, &b) {
+            Ordering::Greater => b,
+            _ => a,
+        })
+    }
+
+    /// Same as [divide_flat_map](crate::divide_flat_map), but uses this configuration. Equal chunks are used for
+    /// [Schedule::Static] and a shared queue otherwise.
+    pub fn divide_flat_map<F, T, I>(&self, input: Vec<T>, f: F) -> Vec<I::Item>
+    where
+        T: Send + 'static,
+        I: IntoIterator + 'static,
+        I::Item: Send + 'static,
+        F: Fn(T) -> I + Send + Sync + 'static,
+    {
+        self.flat_map(input, f)
+    }
+
+    /// Same as [divide_filter](crate::divide_filter), but uses this configuration. Equal chunks are used for
+    /// [Schedule::Static] and a shared queue otherwise.
+    pub fn divide_filter<P, T>(&self, input: Vec<T>, pred: P) -> Vec<T>
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.flat_map(input, move |val| if pred(&val) { Some(val) } else { None })
+    }
+
+    /// Same as [divide_filter_map](crate::divide_filter_map), but uses this configuration. Equal chunks are used for
+    /// [Schedule::Static] and a shared queue otherwise.
+    pub fn divide_filter_map<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        self.flat_map(input, f)
+    }
+
+    /// Same as [divide_work_shared](crate::divide_work_shared), but uses this configuration
+    pub fn divide_work_shared<F, T, R>(&self, input: impl Into<Arc<[T]>>, f: F) -> Vec<R>
+    where
+        T: Send + Sync + 'static,
+        R: Send + 'static,
+        F: Fn(&T) -> R + Send + Sync + 'static,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            self.skipped(self.shared(input.into(), skip_panics_ref(f)))
+        } else {
+            self.shared(input.into(), f)
+        }
+    }
+
+    /// Same as [divide_for_each_mut](crate::divide_for_each_mut), but uses this configuration
+    pub fn divide_for_each_mut<F, T>(&self, data: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Send + Sync,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            self.equal_mut(data, move |val: &mut T| {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| f(val)));
+            })
+        } else {
+            self.equal_mut(data, f)
+        }
+    }
+
+    /// Same as [divide_for_each_mut_dynamic](crate::divide_for_each_mut_dynamic), but uses this configuration
+    pub fn divide_for_each_mut_dynamic<F, T>(&self, data: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Send + Sync,
+    {
+        let f = &f;
+        let input: Vec<&mut T> = data.iter_mut().collect();
+        expect_spawned(thread::scope(|s| {
+            self.dynamic(&ScopedSpawner(s), input, move |val: &mut T| f(val))
+        }));
+    }
+
+    /// Same as [divide_work_init](crate::divide_work_init), but uses this configuration
+    pub fn divide_work_init<I, F, T, R, St>(&self, input: Vec<T>, init: I, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+    {
+        self.init(input, init, f, drop, self.schedule).0
+    }
+
+    /// Same as [divide_equal_work_init](crate::divide_equal_work_init), but uses this configuration
+    pub fn divide_equal_work_init<I, F, T, R, St>(&self, input: Vec<T>, init: I, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+    {
+        self.init(input, init, f, drop, Schedule::Static).0
+    }
+
+    /// Same as [divide_work_enumerate](crate::divide_work_enumerate), but uses this configuration
+    pub fn divide_work_enumerate<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(usize, T) -> R + Send + Sync + 'static,
+    {
+        let f = move |_: &mut (), idx, val| f(idx, val);
+        self.init_indexed(input, || (), f, drop, self.schedule).0
+    }
+
+    /// Same as [divide_equal_work_enumerate](crate::divide_equal_work_enumerate), but uses this configuration
+    pub fn divide_equal_work_enumerate<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(usize, T) -> R + Send + Sync + 'static,
+    {
+        let f = move |_: &mut (), idx, val| f(idx, val);
+        self.init_indexed(input, || (), f, drop, Schedule::Static).0
+    }
+
+    /// Same as [divide_any](crate::divide_any), but uses this configuration
+    pub fn divide_any<P, T>(&self, input: Vec<T>, pred: P) -> bool
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let found = self.find_any(input, move |val| if pred(&val) { Some(()) } else { None });
+        found.is_some()
+    }
+
+    /// Same as [divide_find_any](crate::divide_find_any), but uses this configuration
+    pub fn divide_find_any<P, T>(&self, input: Vec<T>, pred: P) -> Option<T>
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.find_any(input, move |val| if pred(&val) { Some(val) } else { None })
+    }
+
+    /// Same as [divide_positions](crate::divide_positions), but uses this configuration
+    pub fn divide_positions<P, T>(&self, input: &[T], pred: P) -> Vec<usize>
+    where
+        T: Sync,
+        P: Fn(&T) -> bool + Send + Sync,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let pred = skip_panics_ref(pred);
+            self.positions(input, move |val| pred(val).unwrap_or(false))
+        } else {
+            self.positions(input, pred)
+        }
+    }
+
+    /// Same as [divide_retain](crate::divide_retain), but uses this configuration
+    pub fn divide_retain<P, T>(&self, vec: &mut Vec<T>, pred: P)
+    where
+        T: Sync,
+        P: Fn(&T) -> bool + Send + Sync,
+    {
+        let keep = if self.panic_policy == PanicPolicy::SkipItem {
+            let pred = skip_panics_ref(pred);
+            self.borrowed_with(vec, move |val| pred(val).unwrap_or(false), self.schedule)
+        } else {
+            self.borrowed_with(vec, pred, self.schedule)
+        };
+        // `retain` visits elements in order, exactly once each
+        let mut keep = keep.into_iter();
+        vec.retain(|_| keep.next().expect("every element has a verdict"));
+    }
+
+    /// Same as [divide_partition](crate::divide_partition), but uses this configuration
+    pub fn divide_partition<P, T>(&self, input: Vec<T>, pred: P) -> (Vec<T>, Vec<T>)
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.divide_partition_map(input, move |val| {
+            if pred(&val) {
+                Either::Left(val)
+            } else {
+                Either::Right(val)
+            }
+        })
+    }
+
+    /// Same as [divide_partition_map](crate::divide_partition_map), but uses this configuration
+    pub fn divide_partition_map<F, T, A, B>(&self, mut input: Vec<T>, f: F) -> (Vec<A>, Vec<B>)
+    where
+        T: Send + 'static,
+        A: Send + 'static,
+        B: Send + 'static,
+        F: Fn(T) -> Either<A, B> + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let (mut left, mut right) = if decision.is_parallel() {
+            expect_spawned(equal::divide_partition_map(self, &OsSpawner, input, f))
+        } else {
+            let (mut left, mut right) = (Vec::new(), Vec::new());
+            for val in input {
+                f(val).push_to(&mut left, &mut right);
+            }
+            (left, right)
+        };
+        // Sampled element is the last one, so its result goes last on its side
+        if let Some(sample) = sample {
+            sample.push_to(&mut left, &mut right);
+        }
+        (self.shrink(left), self.shrink(right))
+    }
+
+    /// Same as [divide_partition_results](crate::divide_partition_results), but uses this configuration
+    pub fn divide_partition_results<F, T, R, E>(&self, input: Vec<T>, f: F) -> (Vec<R>, Vec<E>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    {
+        self.divide_partition_map(input, move |val| match f(val) {
+            Ok(r) => Either::Left(r),
+            Err(err) => Either::Right(err),
+        })
+    }
+
+    /// Same as [divide_unzip](crate::divide_unzip), but uses this configuration
+    pub fn divide_unzip<F, T, A, B>(&self, mut input: Vec<T>, f: F) -> (Vec<A>, Vec<B>)
+    where
+        T: Send + 'static,
+        A: Send + 'static,
+        B: Send + 'static,
+        F: Fn(T) -> (A, B) + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let (mut left, mut right): (Vec<A>, Vec<B>) = if decision.is_parallel() {
+            expect_spawned(dynamic::divide_unzip(self, &OsSpawner, input, f))
+        } else {
+            input.into_iter().map(f).unzip()
+        };
+        if let Some((a, b)) = sample {
+            left.push(a);
+            right.push(b);
+        }
+        (self.shrink(left), self.shrink(right))
+    }
+
+    /// Same as [divide_collect_map](crate::divide_collect_map), but uses this configuration
+    pub fn divide_collect_map<F, T, K, V>(&self, input: Vec<T>, f: F) -> HashMap<K, V>
+    where
+        T: Send + 'static,
+        K: Eq + Hash + Send + 'static,
+        V: Send + 'static,
+        F: Fn(T) -> (K, V) + Send + Sync + 'static,
+    {
+        self.collect_map(input, f, |_, later| later)
+    }
+
+    /// Same as [divide_collect_map_with](crate::divide_collect_map_with), but uses this configuration
+    pub fn divide_collect_map_with<F, M, T, K, V>(
+        &self,
+        input: Vec<T>,
+        f: F,
+        merge: M,
+    ) -> HashMap<K, V>
+    where
+        T: Send + 'static,
+        K: Eq + Hash + Send + 'static,
+        V: Send + 'static,
+        F: Fn(T) -> (K, V) + Send + Sync + 'static,
+        M: Fn(V, V) -> V + Send + Sync + 'static,
+    {
+        self.collect_map(input, f, merge)
+    }
+
+    /// Same as [divide_collect_btree_map](crate::divide_collect_btree_map), but uses this configuration
+    pub fn divide_collect_btree_map<F, T, K, V>(&self, input: Vec<T>, f: F) -> BTreeMap<K, V>
+    where
+        T: Send + 'static,
+        K: Ord + Send + 'static,
+        V: Send + 'static,
+        F: Fn(T) -> (K, V) + Send + Sync + 'static,
+    {
+        self.collect_map(input, f, |_, later| later)
+    }
+
+    /// Same as [divide_collect_btree_map_with](crate::divide_collect_btree_map_with), but uses this configuration
+    pub fn divide_collect_btree_map_with<F, M, T, K, V>(
+        &self,
+        input: Vec<T>,
+        f: F,
+        merge: M,
+    ) -> BTreeMap<K, V>
+    where
+        T: Send + 'static,
+        K: Ord + Send + 'static,
+        V: Send + 'static,
+        F: Fn(T) -> (K, V) + Send + Sync + 'static,
+        M: Fn(V, V) -> V + Send + Sync + 'static,
+    {
+        self.collect_map(input, f, merge)
+    }
+
+    /// Same as [divide_group_by](crate::divide_group_by), but uses this configuration
+    pub fn divide_group_by<F, G, T, K, V>(
+        &self,
+        input: Vec<T>,
+        key: F,
+        map: G,
+    ) -> HashMap<K, Vec<V>>
+    where
+        T: Send + 'static,
+        K: Eq + Hash + Send + 'static,
+        V: Send + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+        G: Fn(T) -> V + Send + Sync + 'static,
+    {
+        let fold = move |mut groups: HashMap<K, Vec<V>>, val| {
+            groups.entry(key(&val)).or_default().push(map(val));
+            groups
+        };
+        let append = |mut earlier: Vec<V>, mut later| {
+            earlier.append(&mut later);
+            earlier
+        };
+        let combine = move |earlier, later| merge_maps(earlier, later, append);
+        self.divide_fold(input, HashMap::new, fold, combine)
+    }
+
+    /// Same as [divide_sort](crate::divide_sort), but uses this configuration
+    pub fn divide_sort<T>(&self, input: Vec<T>) -> Vec<T>
+    where
+        T: Ord + Send + 'static,
+    {
+        self.divide_sort_by(input, T::cmp)
+    }
+
+    /// Same as [divide_sort_by](crate::divide_sort_by), but uses this configuration
+    pub fn divide_sort_by<F, T>(&self, mut input: Vec<T>, compare: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T, &T) -> Ordering + Send + Sync + 'static,
+    {
+        if self.decide(input.len()).is_parallel() {
+            expect_spawned(sort::divide_sort_by(self, &OsSpawner, input, compare))
+        } else {
+            input.sort_by(compare);
+            input
+        }
+    }
+
+    /// Same as [divide_sort_by_key](crate::divide_sort_by_key), but uses this configuration
+    pub fn divide_sort_by_key<F, T, K>(&self, input: Vec<T>, key: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        K: Ord,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        self.divide_sort_by(input, move |a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Same as [divide_sort_by_cached_key](crate::divide_sort_by_cached_key), but uses this configuration. Panics of
+    /// `key` are propagated even with [PanicPolicy::SkipItem].
+    pub fn divide_sort_by_cached_key<F, T, K>(&self, mut input: Vec<T>, key: F) -> Vec<T>
+    where
+        T: Sync,
+        K: Ord + Send,
+        F: Fn(&T) -> K + Send + Sync,
+    {
+        let keys = self.borrowed_with(&input, key, self.schedule);
+        let mut keys: Vec<(K, usize)> = keys.into_iter().zip(0..).collect();
+        // Indices are unique, so unstable sort keeps equal keys in order, same as in `slice::sort_by_cached_key`
+        keys.sort_unstable();
+        // Element for position `i` is at `keys[i].1`, unless it was already swapped away by an earlier position, in
+        // which case the chain of swaps leads to where it is now
+        for i in 0..input.len() {
+            let mut idx = keys[i].1;
+            while idx < i {
+                idx = keys[idx].1;
+            }
+            keys[i].1 = idx;
+            input.swap(i, idx);
+        }
+        input
+    }
+
+    /// Same as [divide_merge_sorted](crate::divide_merge_sorted), but uses this configuration
+    pub fn divide_merge_sorted<T>(&self, shards: Vec<Vec<T>>) -> Vec<T>
+    where
+        T: Ord + Send + 'static,
+    {
+        self.divide_merge_sorted_by(shards, T::cmp)
+    }
+
+    /// Same as [divide_merge_sorted_by](crate::divide_merge_sorted_by), but uses this configuration
+    pub fn divide_merge_sorted_by<F, T>(&self, shards: Vec<Vec<T>>, compare: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T, &T) -> Ordering + Send + Sync + 'static,
+    {
+        let len = shards.iter().map(Vec::len).sum();
+        let shards: Vec<Vec<T>> = shards
+            .into_iter()
+            .filter(|shard| !shard.is_empty())
+            .collect();
+        if self.decide(len).is_parallel() {
+            expect_spawned(sort::merge_runs(
+                self,
+                &OsSpawner,
+                shards,
+                Arc::new(compare),
+            ))
+        } else {
+            // Stable sort detects the runs of the shards and merges them, which is as good as merging sequentially
+            let mut output = output::concat(shards);
+            output.sort_by(compare);
+            output
+        }
+    }
+
+    /// Same as [divide_dedup](crate::divide_dedup), but uses this configuration
+    pub fn divide_dedup<T>(&self, input: Vec<T>) -> Vec<T>
+    where
+        T: PartialEq + Send + 'static,
+    {
+        self.dedup_by(input, |a, b| a == b)
+    }
+
+    /// Same as [divide_dedup_by_key](crate::divide_dedup_by_key), but uses this configuration
+    pub fn divide_dedup_by_key<F, T, K>(&self, input: Vec<T>, key: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        K: PartialEq,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        self.dedup_by(input, move |a, b| key(a) == key(b))
+    }
+
+    /// Same as [divide_scan](crate::divide_scan), but uses this configuration
+    pub fn divide_scan<I, F, A>(&self, input: Vec<A>, identity: I, op: F) -> Vec<A>
+    where
+        A: Send + 'static,
+        I: Fn() -> A + Send + Sync + 'static,
+        F: Fn(&A, &A) -> A + Send + Sync + 'static,
+    {
+        if self.decide(input.len()).is_parallel() {
+            expect_spawned(scan::divide_scan(self, &OsSpawner, input, identity, op))
+        } else {
+            scan::scan(input, identity(), &op)
+        }
+    }
+
+    /// Same as [divide_prefix_sum](crate::divide_prefix_sum), but uses this configuration
+    pub fn divide_prefix_sum<T>(&self, input: Vec<T>) -> Vec<T>
+    where
+        T: Add<Output = T> + Copy + Default + Send + 'static,
+    {
+        self.divide_scan(input, T::default, |&a, &b| a + b)
+    }
+
+    /// Same as [divide_chunks](crate::divide_chunks), but uses this configuration
+    pub fn divide_chunks<F, T, R>(&self, input: Vec<T>, chunk_size: usize, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(&[T]) -> Vec<R> + Send + Sync + 'static,
+    {
+        self.divide_chunks_owned(input, chunk_size, move |chunk| f(&chunk))
+    }
+
+    /// Same as [divide_chunks_owned](crate::divide_chunks_owned), but uses this configuration
+    pub fn divide_chunks_owned<F, T, R>(&self, input: Vec<T>, chunk_size: usize, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(Vec<T>) -> Vec<R> + Send + Sync + 'static,
+    {
+        assert_ne!(chunk_size, 0, "chunk size must be positive");
+        // Whether work is split depends on amount of elements, so it is decided before they are grouped into chunks
+        let parallel = self.decide(input.len()).is_parallel();
+        let chunks = equal::split_sized(input, chunk_size);
+        if self.panic_policy == PanicPolicy::SkipItem {
+            // `f` processes a whole chunk at once, so the whole chunk is skipped if it panics
+            let outputs = self.sized_chunks(chunks, parallel, skip_panics(f));
+            self.shrink(output::concat(outputs.into_iter().flatten().collect()))
+        } else {
+            output::concat(self.sized_chunks(chunks, parallel, f))
+        }
+    }
+
+    /// Same as [divide_windows](crate::divide_windows), but uses this configuration
+    pub fn divide_windows<F, T, R>(&self, data: &[T], window: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&[T]) -> R + Send + Sync,
+    {
+        assert_ne!(window, 0, "window size must be positive");
+        if self.panic_policy == PanicPolicy::SkipItem {
+            self.skipped(self.windows_with(data, window, skip_panics_ref(f)))
+        } else {
+            self.windows_with(data, window, f)
+        }
+    }
+
+    /// Same as [divide_zip](crate::divide_zip), but uses this configuration
+    pub fn divide_zip<F, A, B, R>(&self, a: Vec<A>, b: Vec<B>, f: F) -> Vec<R>
+    where
+        A: Send + 'static,
+        B: Send + 'static,
+        R: Send + 'static,
+        F: Fn(A, B) -> R + Send + Sync + 'static,
+    {
+        assert_eq!(a.len(), b.len(), "zipped vectors must have the same length");
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let f = skip_panics(move |(a, b)| f(a, b));
+            self.skipped(self.zip_with(a, b, move |a, b| f((a, b))))
+        } else {
+            self.zip_with(a, b, f)
+        }
+    }
+
+    /// Same as [divide_zip_ref](crate::divide_zip_ref), but uses this configuration
+    pub fn divide_zip_ref<F, A, B, R>(&self, a: &[A], b: &[B], f: F) -> Vec<R>
+    where
+        A: Sync,
+        B: Sync,
+        R: Send,
+        F: Fn(&A, &B) -> R + Send + Sync,
+    {
+        assert_eq!(a.len(), b.len(), "zipped slices must have the same length");
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let f = move |a: &A, b: &B| skip_panics_ref(|b| f(a, b))(b);
+            self.skipped(self.zip_ref_with(a, b, f))
+        } else {
+            self.zip_ref_with(a, b, f)
+        }
+    }
+
+    /// Same as [divide_find_first](crate::divide_find_first), but uses this configuration
+    pub fn divide_find_first<P, T>(&self, input: Vec<T>, pred: P) -> Option<(usize, T)>
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let find = move |val| if pred(&val) { Some(val) } else { None };
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let find = skip_panics(find);
+            self.find_first(input, move |val| find(val).flatten())
+        } else {
+            self.find_first(input, find)
+        }
+    }
+
+    /// Same as [divide_map_while](crate::divide_map_while), but uses this configuration
+    pub fn divide_map_while<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            // Elements that panicked are left out, but don't end the prefix
+            let f = skip_panics(f);
+            let output =
+                self.map_while(input, move |val| f(val).map_or(Some(None), |r| r.map(Some)));
+            output.into_iter().flatten().collect()
+        } else {
+            self.map_while(input, f)
+        }
+    }
+
+    /// Same as [divide_find_any_ref](crate::divide_find_any_ref), but uses this configuration
+    pub fn divide_find_any_ref<'a, P, T>(&self, input: &'a [T], pred: P) -> Option<&'a T>
+    where
+        T: Sync,
+        P: Fn(&T) -> bool + Send + Sync,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let pred = skip_panics_ref(pred);
+            self.find_any_ref(input, move |val| pred(val).unwrap_or(false))
+        } else {
+            self.find_any_ref(input, pred)
+        }
+    }
+
+    /// Same as [divide_all](crate::divide_all), but uses this configuration
+    pub fn divide_all<P, T>(&self, input: Vec<T>, pred: P) -> bool
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        !self.divide_any(input, move |val| !pred(val))
+    }
+
+    /// Same as [divide_count](crate::divide_count), but uses this configuration. With [Schedule::Static] the input is
+    /// split into equal chunks, same as with [divide_equal_count](crate::divide_equal_count).
+    pub fn divide_count<P, T>(&self, input: Vec<T>, pred: P) -> usize
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.count(input, pred, self.schedule)
+    }
+
+    /// Same as [divide_equal_count](crate::divide_equal_count), but uses this configuration
+    pub fn divide_equal_count<P, T>(&self, input: Vec<T>, pred: P) -> usize
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.count(input, pred, Schedule::Static)
+    }
+
+    /// Same as [divide_work_with_state](crate::divide_work_with_state), but uses this configuration. With
+    /// [Schedule::Static] the input is split into equal chunks, same as with
+    /// [divide_equal_work_init](crate::divide_equal_work_init).
+    pub fn divide_work_with_state<I, F, T, R, St>(
+        &self,
+        input: Vec<T>,
+        init: I,
+        f: F,
+    ) -> (Vec<R>, Vec<St>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: Send + 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+    {
+        self.init(input, init, f, |state| state, self.schedule)
+    }
+
+    /// Same as [divide_work_scoped](crate::divide_work_scoped), but uses this configuration
+    pub fn divide_work_scoped<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Send + Sync,
+    {
+        expect_spawned(thread::scope(|s| self.dynamic(&ScopedSpawner(s), input, f))).0
+    }
+
+    /// Same as [divide_equal_work_scoped](crate::divide_equal_work_scoped), but uses this configuration
+    pub fn divide_equal_work_scoped<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Send + Sync,
+    {
+        expect_spawned(thread::scope(|s| self.equal(&ScopedSpawner(s), input, f))).0
+    }
+
+    /// Same as [divide_work_ref](crate::divide_work_ref), but uses this configuration
+    pub fn divide_work_ref<F, T, R>(&self, input: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Send + Sync,
+    {
+        self.borrowed(input, f, self.schedule)
+    }
+
+    /// Same as [divide_equal_work_ref](crate::divide_equal_work_ref), but uses this configuration
+    pub fn divide_equal_work_ref<F, T, R>(&self, input: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Send + Sync,
+    {
+        self.borrowed(input, f, Schedule::Static)
+    }
+
+    /// Same as [divide_equal_work](crate::divide_equal_work), but uses this configuration
+    #[inline]
+    pub fn divide_equal_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.divide_equal_work_with_decision(input, f).0
+    }
+
+    /// Same as [divide_equal_work_iter](crate::divide_equal_work_iter), but uses this configuration
+    #[inline]
+    pub fn divide_equal_work_iter<I, F, T, R>(&self, input: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.divide_equal_work(input.into_iter().collect(), f)
+    }
+
+    /// Same as [WorkConfig::divide_equal_work], but also explains whether work was split between threads
+    #[inline]
+    pub fn divide_equal_work_with_decision<F, T, R>(
+        &self,
+        input: Vec<T>,
+        f: F,
+    ) -> (Vec<R>, Decision)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        expect_spawned(self.equal(&OsSpawner, input, f))
+    }
+
+    /// Same as [divide_equal_work_checked](crate::divide_equal_work_checked), but uses this configuration
+    pub fn divide_equal_work_checked<F, T, R>(
+        &self,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, WorkError>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let input = input.into_iter().enumerate().collect();
+        let output = self.try_divide_equal_work(input, catch_item_panics(f));
+        output.map_err(WorkError::flatten_panic)
+    }
+
+    /// Same as [WorkConfig::divide_equal_work], but leaves the elements for which `f` panicked as `None` and continues
+    /// with the rest of the input, as if [PanicPolicy::SkipItem] was set
+    pub fn divide_equal_work_skipping<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<Option<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = self.run(input, skip_panics(f), |config, input, f| {
+            equal::divide_equal_work(config, &OsSpawner, input, f)
+        });
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_weighted_work](crate::divide_weighted_work), but uses this configuration
+    pub fn divide_weighted_work<W, F, T, R>(&self, input: Vec<T>, weight: W, f: F) -> Vec<R>
+    where
+        W: Fn(&T) -> u64,
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let run = if self.panic_policy == PanicPolicy::SkipItem {
+            self.run(input, skip_panics(f), |config, input, f| {
+                equal::divide_weighted_work(config, &OsSpawner, input, &weight, f)
+            })
+            .map(|(output, decision)| (self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| {
+                equal::divide_weighted_work(config, &OsSpawner, input, &weight, f)
+            })
+        };
+        expect_spawned(run).0
+    }
+
+    /// Same as [divide_work_catch](crate::divide_work_catch), but uses this configuration
+    pub fn divide_work_catch<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<thread::Result<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.divide_work(input, move |val| {
+            panic::catch_unwind(AssertUnwindSafe(|| f(val)))
+        })
+    }
+
+    /// Same as [divide_work_catching](crate::divide_work_catching), but uses this configuration. Failures to spawn
+    /// worker threads panic, same as with [WorkConfig::divide_work].
+    pub fn divide_work_catching<F, T, R>(&self, input: Vec<T>, f: F) -> Result<Vec<R>, ItemPanic>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let input = input.into_iter().enumerate().collect();
+        let output = self.try_divide_work(input, catch_item_panics(f));
+        output.map_err(|err| match err {
+            WorkError::ItemError(panic) => panic,
+            WorkError::SpawnFailed(err) => expect_spawned(Err(SpawnError::new(err))),
+            _ => unreachable!("work is neither cancelled nor timed out"),
+        })
+    }
+
+    /// Same as [divide_work_partial](crate::divide_work_partial), but uses this configuration. Failures to spawn worker
+    /// threads are handled as with [SpawnFailurePolicy::Degrade] regardless of the configured policy.
+    pub fn divide_work_partial<F, T, R>(
+        &self,
+        input: Vec<T>,
+        f: F,
+    ) -> (Vec<Option<R>>, Option<WorkError>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let len = input.len();
+        let input = input.into_iter().enumerate().collect();
+        let stop = Arc::new(StopFlag::new());
+        let failure = Arc::new(Mutex::new(None));
+
+        let config = self.clone().on_spawn_failure(SpawnFailurePolicy::Degrade);
+        let output = {
+            let failure = failure.clone();
+            // Elements that are left after cancellation are still taken from the input, but not processed
+            config.dynamic(&OsSpawner, input, move |(index, val)| {
+                if stop.is_stopped() {
+                    return None;
+                }
+                match panic::catch_unwind(AssertUnwindSafe(|| f(val))) {
+                    Ok(r) => Some(r),
+                    Err(payload) => {
+                        stop.stop();
+                        let mut failure = worker::lock(&failure);
+                        if failure.is_none() {
+                            *failure = Some(ItemPanic { index, payload });
+                        }
+                        None
+                    }
+                }
+            })
+        };
+
+        match output {
+            Ok((output, _)) => (output, worker::lock(&failure).take().map(WorkError::from)),
+            Err(err) => ((0..len).map(|_| None).collect(), Some(WorkError::from(err))),
+        }
+    }
+
+    /// Same as [divide_work_timeout](crate::divide_work_timeout), but uses this configuration
+    pub fn divide_work_timeout<F, T, R>(
+        &self,
+        input: Vec<T>,
+        timeout: Duration,
+        f: F,
+    ) -> TimeoutOutcome<T, R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let until = Until {
+            deadline: Some(Instant::now() + timeout),
+            token: None,
+        };
+        let (completed, remaining) = self.run_until(input, until, f);
+        if remaining.is_empty() {
+            let output = completed
+                .into_iter()
+                .map(|r| r.expect("all elements are processed"));
+            TimeoutOutcome::Completed(output.collect())
+        } else {
+            TimeoutOutcome::TimedOut {
+                completed,
+                remaining,
+            }
+        }
+    }
+
+    /// Same as [divide_work_cancellable](crate::divide_work_cancellable), but uses this configuration
+    pub fn divide_work_cancellable<F, T, R>(
+        &self,
+        input: Vec<T>,
+        token: &CancelToken,
+        f: F,
+    ) -> Result<Vec<R>, Cancelled<T, R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let until = Until {
+            deadline: None,
+            token: Some(token.clone()),
+        };
+        let (completed, remaining) = self.run_until(input, until, f);
+        if remaining.is_empty() {
+            let output = completed
+                .into_iter()
+                .map(|r| r.expect("all elements are processed"));
+            Ok(output.collect())
+        } else {
+            Err(Cancelled {
+                completed,
+                remaining,
+            })
+        }
+    }
+
+    /// Same as [divide_work_with_deadline](crate::divide_work_with_deadline), but uses this configuration
+    pub fn divide_work_with_deadline<F, T, R>(
+        &self,
+        input: Vec<T>,
+        limit: Duration,
+        f: F,
+    ) -> Vec<Result<R, ItemTimedOut>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T, &ItemCtx) -> R + Send + Sync + 'static,
+    {
+        self.divide_work(input, deadline::with_deadline(limit, f))
+    }
+
+    /// Same as [divide_equal_work_with_deadline](crate::divide_equal_work_with_deadline), but uses this configuration
+    pub fn divide_equal_work_with_deadline<F, T, R>(
+        &self,
+        input: Vec<T>,
+        limit: Duration,
+        f: F,
+    ) -> Vec<Result<R, ItemTimedOut>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T, &ItemCtx) -> R + Send + Sync + 'static,
+    {
+        self.divide_equal_work(input, deadline::with_deadline(limit, f))
+    }
+
+    /// Same as [divide_work_or](crate::divide_work_or), but uses this configuration
+    pub fn divide_work_or<F, G, T, R, E>(&self, input: Vec<T>, f: F, fallback: G) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        E: 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+        G: Fn(&PanicOrError<E>) -> R + Send + Sync + 'static,
+    {
+        self.divide_work(input, move |val| {
+            match panic::catch_unwind(AssertUnwindSafe(|| f(val))) {
+                Ok(Ok(r)) => r,
+                Ok(Err(err)) => fallback(&PanicOrError::Failed(err)),
+                Err(payload) => fallback(&PanicOrError::Panicked(payload)),
+            }
+        })
+    }
+
+    /// Same as [divide_work_or_default](crate::divide_work_or_default), but uses this configuration
+    pub fn divide_work_or_default<F, T, R, E>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Default + Send + 'static,
+        E: 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    {
+        self.divide_work_or(input, f, |_| R::default())
+    }
+
+    /// Same as [try_divide_work](crate::try_divide_work), but uses this configuration
+    pub fn try_divide_work<F, T, R, E>(
+        &self,
+        mut input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, WorkError<E>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let sample = sample.transpose().map_err(WorkError::ItemError)?;
+        let output = if decision.is_parallel() {
+            let output = if self.schedule == Schedule::Static {
+                equal::try_divide_equal_work(self, &OsSpawner, input, f)?
+            } else {
+                dynamic::try_divide_work(self, &OsSpawner, input, f)?
+            };
+            output.map_err(WorkError::ItemError)?
+        } else {
+            let output = input.into_iter().map(f).collect::<Result<Vec<_>, _>>();
+            output.map_err(WorkError::ItemError)?
+        };
+        Ok(self.finish(output, sample))
+    }
+
+    /// Same as [try_divide_equal_work](crate::try_divide_equal_work), but uses this configuration
+    pub fn try_divide_equal_work<F, T, R, E>(
+        &self,
+        mut input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, WorkError<E>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let sample = sample.transpose().map_err(WorkError::ItemError)?;
+        let output = if decision.is_parallel() {
+            equal::try_divide_equal_work(self, &OsSpawner, input, f)?
+                .map_err(WorkError::ItemError)?
+        } else {
+            let output = input.into_iter().map(f).collect::<Result<Vec<_>, _>>();
+            output.map_err(WorkError::ItemError)?
+        };
+        Ok(self.finish(output, sample))
+    }
+
+    /// Same as [try_divide_work_with_retries](crate::try_divide_work_with_retries), but uses this configuration. Amount
+    /// of retries is set with [retries](WorkConfig::retries).
+    pub fn try_divide_work_retrying<F, T, R, E>(
+        &self,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, WorkError<E>>
+    where
+        T: Clone + Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+        F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    {
+        let retries = self.retries;
+        let reported_error = self.reported_error;
+        self.try_divide_work(input, move |val: T| {
+            let mut first_err = None;
+            for _ in 0..retries {
+                match f(val.clone()) {
+                    Ok(r) => return Ok(r),
+                    Err(err) if reported_error == ReportedError::First && first_err.is_none() => {
+                        first_err = Some(err)
+                    }
+                    Err(_) => {}
+                }
+            }
+            // Last attempt doesn't need a copy of the element
+            f(val).map_err(|err| first_err.unwrap_or(err))
+        })
+    }
+
+    /// Processes `input` with dynamic scheduler until `until` is reached. Returns results at their indices in the
+    /// original input, with `None` for elements that weren't processed, and unprocessed elements themselves.
+    fn run_until<F, T, R>(&self, mut input: Vec<T>, until: Until, f: F) -> (Vec<Option<R>>, Vec<T>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        if until.reached() {
+            return (input.iter().map(|_| None).collect(), input);
+        }
+
+        let (decision, sample) = self.plan(&mut input, &f);
+        let (completed, remaining) = if decision.is_parallel() {
+            expect_spawned(dynamic::divide_work_until(
+                self, &OsSpawner, input, until, f,
+            ))
+        } else {
+            let mut completed = Vec::with_capacity(input.len());
+            let mut input = input.into_iter();
+            while !until.reached() {
+                match input.next() {
+                    Some(val) => completed.push(Some(f(val))),
+                    None => break,
+                }
+            }
+            let remaining: Vec<T> = input.collect();
+            completed.extend(remaining.iter().map(|_| None));
+            (completed, remaining)
+        };
+        (self.finish(completed, sample.map(Some)), remaining)
+    }
+
+    /// Processes `input` with per-worker state according to [PanicPolicy], with equal chunks for [Schedule::Static] and
+    /// a shared queue otherwise. Every state is handed to `finish` once its thread is done, and results of `finish` are
+    /// returned along with the output.
+    fn init<I, F, G, T, R, St, A>(
+        &self,
+        input: Vec<T>,
+        init: I,
+        f: F,
+        finish: G,
+        schedule: Schedule,
+    ) -> (Vec<R>, Vec<A>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: 'static,
+        A: Send + 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+        G: Fn(St) -> A + Send + Sync + 'static,
+    {
+        let f = move |state: &mut St, _, val| f(state, val);
+        self.init_indexed(input, init, f, finish, schedule)
+    }
+
+    /// Same as [WorkConfig::init], but `f` also gets the index of every element in `input`
+    fn init_indexed<I, F, G, T, R, St, A>(
+        &self,
+        input: Vec<T>,
+        init: I,
+        f: F,
+        finish: G,
+        schedule: Schedule,
+    ) -> (Vec<R>, Vec<A>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: 'static,
+        A: Send + 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, usize, T) -> R + Send + Sync + 'static,
+        G: Fn(St) -> A + Send + Sync + 'static,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let f = move |state: &mut St, idx, val| {
+                panic::catch_unwind(AssertUnwindSafe(|| f(state, idx, val))).ok()
+            };
+            let (output, states) = self.init_with(input, init, f, finish, schedule);
+            (self.skipped(output), states)
+        } else {
+            self.init_with(input, init, f, finish, schedule)
+        }
+    }
+
+    fn init_with<I, F, G, T, R, St, A>(
+        &self,
+        mut input: Vec<T>,
+        init: I,
+        f: F,
+        finish: G,
+        schedule: Schedule,
+    ) -> (Vec<R>, Vec<A>)
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        St: 'static,
+        A: Send + 'static,
+        I: Fn() -> St + Send + Sync + 'static,
+        F: Fn(&mut St, usize, T) -> R + Send + Sync + 'static,
+        G: Fn(St) -> A + Send + Sync + 'static,
+    {
+        // State of the calling thread is created only if it processes elements outside of the workers
+        let mut state = None;
+        let mut local = |(idx, val)| f(state.get_or_insert_with(&init), idx, val);
+        let len = input.len();
+        let (decision, sample) =
+            self.plan_with(len, || input.pop().map(|val| local((len - 1, val))));
+        // Workers and the calling thread both finish their states
+        let finish = Arc::new(finish);
+        let (output, mut finished) = if decision.is_parallel() {
+            let finish = {
+                let finish = finish.clone();
+                move |state| finish(state)
+            };
+            expect_spawned(if schedule == Schedule::Static {
+                equal::divide_equal_work_init(self, &OsSpawner, input, init, f, finish)
+            } else {
+                dynamic::divide_work_init(self, &OsSpawner, input, init, f, finish)
+            })
+        } else {
+            (
+                input.into_iter().enumerate().map(local).collect(),
+                Vec::new(),
+            )
+        };
+        finished.extend(state.map(|state| finish(state)));
+        (self.finish(output, sample), finished)
+    }
+
+    /// Collects results of `f` for every element in input order, according to [PanicPolicy]. Elements for which `f`
+    /// panicked are skipped the same way as the ones it returned no results for.
+    fn flat_map<F, T, I>(&self, input: Vec<T>, f: F) -> Vec<I::Item>
+    where
+        T: Send + 'static,
+        I: IntoIterator + 'static,
+        I::Item: Send + 'static,
+        F: Fn(T) -> I + Send + Sync + 'static,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let f = skip_panics(f);
+            self.flat_map_with(input, move |val| f(val).into_iter().flatten())
+        } else {
+            self.flat_map_with(input, f)
+        }
+    }
+
+    fn flat_map_with<F, T, I>(&self, mut input: Vec<T>, f: F) -> Vec<I::Item>
+    where
+        T: Send + 'static,
+        I: IntoIterator + 'static,
+        I::Item: Send + 'static,
+        F: Fn(T) -> I + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let mut output = if decision.is_parallel() {
+            expect_spawned(if self.schedule == Schedule::Static {
+                equal::divide_flat_map(self, &OsSpawner, input, f)
+            } else {
+                dynamic::divide_flat_map(self, &OsSpawner, input, f)
+            })
+        } else {
+            input.into_iter().flat_map(f).collect()
+        };
+        // Sampled element is the last one, so its results go last as well
+        output.extend(sample.into_iter().flatten());
+        self.shrink(output)
+    }
+
+    /// Finds any element for which `f` returns a result according to [PanicPolicy], stopping all workers once one is
+    /// found. Elements for which `f` panicked are skipped the same way as the ones it returned `None` for.
+    fn find_any<F, T, R>(&self, input: Vec<T>, f: F) -> Option<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let f = skip_panics(f);
+            self.find_any_with(input, move |val| f(val).flatten())
+        } else {
+            self.find_any_with(input, f)
+        }
+    }
+
+    fn find_any_with<F, T, R>(&self, mut input: Vec<T>, f: F) -> Option<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        if let Some(found) = sample.flatten() {
+            return Some(found);
+        }
+        if decision.is_parallel() {
+            expect_spawned(equal::divide_find_any(self, &OsSpawner, input, f))
+        } else {
+            input.into_iter().find_map(f)
+        }
+    }
+
+    /// Finds the element with the lowest index for which `f` returns a result, along with the index
+    fn find_first<F, T, R>(&self, mut input: Vec<T>, f: F) -> Option<(usize, R)>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        let len = input.len();
+        let (decision, sample) = self.plan(&mut input, &f);
+        let found = if decision.is_parallel() {
+            expect_spawned(equal::divide_find_first(self, &OsSpawner, input, f))
+        } else {
+            input
+                .into_iter()
+                .enumerate()
+                .find_map(|(idx, val)| Some((idx, f(val)?)))
+        };
+        // Sampled element is the last one, so it only counts if nothing else matched
+        found.or_else(|| sample.flatten().map(|r| (len - 1, r)))
+    }
+
+    fn map_while<F, T, R>(&self, mut input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> Option<R> + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let (mut output, stopped) = if decision.is_parallel() {
+            expect_spawned(equal::divide_map_while(self, &OsSpawner, input, f))
+        } else {
+            let len = input.len();
+            let output: Vec<R> = input.into_iter().map_while(f).collect();
+            let stopped = output.len() < len;
+            (output, stopped)
+        };
+        // Sampled element is the last one, so it only counts if the rest of the input had no `None`
+        if !stopped {
+            output.extend(sample.flatten());
+        }
+        output
+    }
+
+    /// Indices of the elements of borrowed `input` that match `pred`, found either sequentially or on scoped threads
+    fn positions<P, T>(&self, input: &[T], pred: P) -> Vec<usize>
+    where
+        T: Sync,
+        P: Fn(&T) -> bool + Sync,
+    {
+        let len = input.len();
+        let (decision, sample) = self.plan_with(len, || input.last().map(&pred));
+        // Sampled element is the last one, the rest of them form a prefix of the input
+        let prefix = &input[..len - usize::from(sample.is_some())];
+        let positions = if decision.is_parallel() {
+            let pred = &pred;
+            expect_spawned(thread::scope(|s| {
+                equal::divide_positions(self, &ScopedSpawner(s), prefix, pred)
+            }))
+        } else {
+            (0..prefix.len())
+                .filter(|&idx| pred(&prefix[idx]))
+                .collect()
+        };
+        let sample = sample.filter(|&matched| matched).map(|_| len - 1);
+        self.finish(positions, sample)
+    }
+
+    /// Same as [WorkConfig::find_any_with], but for borrowed `input`, which is processed on scoped threads
+    fn find_any_ref<'a, P, T>(&self, input: &'a [T], pred: P) -> Option<&'a T>
+    where
+        T: Sync,
+        P: Fn(&T) -> bool + Sync,
+    {
+        let find = |val: &'a T| if pred(val) { Some(val) } else { None };
+        let len = input.len();
+        let (decision, sample) = self.plan_with(len, || input.last().map(find));
+        if let Some(found) = sample.flatten() {
+            return Some(found);
+        }
+        // Sampled element is the last one, the rest of them form a prefix of the input
+        let input = &input[..len - usize::from(sample.is_some())];
+        if decision.is_parallel() {
+            let find = &find;
+            expect_spawned(thread::scope(|s| {
+                equal::divide_find_any(self, &ScopedSpawner(s), input.iter().collect(), find)
+            }))
+        } else {
+            input.iter().find_map(find)
+        }
+    }
+
+    /// Counts elements that match `pred` with a counter per worker. Elements themselves produce `()`, so collecting
+    /// their results doesn't allocate.
+    fn count<P, T>(&self, input: Vec<T>, pred: P, schedule: Schedule) -> usize
+    where
+        T: Send + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        let count = move |count: &mut usize, val: T| *count += usize::from(pred(&val));
+        let (_, counts) = self.init(input, || 0, count, |count| count, schedule);
+        counts.into_iter().sum()
+    }
+
+    /// Selects one of the elements by their keys, which are computed once for every element. An element is replaced by
+    /// a later one if `later_wins` for ordering of their keys. Chunks are combined in input order, so ties are broken
+    /// the same way as they are sequentially.
+    fn select_by_key<F, W, T, K>(&self, input: Vec<T>, key: F, later_wins: W) -> Option<T>
+    where
+        T: Send + 'static,
+        K: Ord + Send + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+        W: Fn(Ordering) -> bool + Copy + Send + Sync + 'static,
+    {
+        let pick = move |a: (K, T), b: (K, T)| if later_wins(a.0.cmp(&b.0)) { b } else { a };
+        let fold = move |acc: Option<(K, T)>, val: T| {
+            let val = (key(&val), val);
+            Some(match acc {
+                Some(acc) => pick(acc, val),
+                None => val,
+            })
+        };
+        let combine = move |a: Option<(K, T)>, b| match (a, b) {
+            (Some(a), Some(b)) => Some(pick(a, b)),
+            (a, b) => a.or(b),
+        };
+        let best = self.divide_fold(input, || None, fold, combine);
+        best.map(|(_, val)| val)
+    }
+
+    /// Removes consecutive elements for which `same(kept, next)` holds, keeping the first of them. Every chunk is
+    /// deduplicated on its own, and when chunks are combined, the first element of the later chunk is dropped if it
+    /// is the same as the last one of the earlier chunk.
+    fn dedup_by<F, T>(&self, input: Vec<T>, same: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(&T, &T) -> bool + Send + Sync + 'static,
+    {
+        let same = Arc::new(same);
+        let fold = {
+            let same = same.clone();
+            move |mut kept: Vec<T>, val| {
+                if !kept.last().is_some_and(|last| same(last, &val)) {
+                    kept.push(val);
+                }
+                kept
+            }
+        };
+        let combine = move |mut earlier: Vec<T>, later: Vec<T>| {
+            let mut later = later.into_iter();
+            if let (Some(last), Some(first)) = (earlier.last(), later.as_slice().first()) {
+                if same(last, first) {
+                    later.next();
+                }
+            }
+            earlier.extend(later);
+            earlier
+        };
+        self.divide_fold(input, Vec::new, fold, combine)
+    }
+
+    /// Zips `a` and `b` either sequentially or in equal chunks, depending on the configuration
+    fn zip_with<F, A, B, R>(&self, mut a: Vec<A>, mut b: Vec<B>, f: F) -> Vec<R>
+    where
+        A: Send + 'static,
+        B: Send + 'static,
+        R: Send + 'static,
+        F: Fn(A, B) -> R + Send + Sync + 'static,
+    {
+        let (decision, sample) = self.plan_with(a.len(), || Some(f(a.pop()?, b.pop()?)));
+        let output = if decision.is_parallel() {
+            expect_spawned(equal::divide_zip(self, &OsSpawner, a, b, f))
+        } else {
+            a.into_iter().zip(b).map(|(a, b)| f(a, b)).collect()
+        };
+        self.finish(output, sample)
+    }
+
+    /// Same as [WorkConfig::zip_with], but for borrowed inputs, which are processed on scoped threads
+    fn zip_ref_with<F, A, B, R>(&self, a: &[A], b: &[B], f: F) -> Vec<R>
+    where
+        A: Sync,
+        B: Sync,
+        R: Send,
+        F: Fn(&A, &B) -> R + Sync,
+    {
+        let len = a.len();
+        let (decision, sample) = self.plan_with(len, || Some(f(a.last()?, b.last()?)));
+        // Sampled elements are the last ones, the rest of them form prefixes of the inputs
+        let prefix = len - usize::from(sample.is_some());
+        let (a, b) = (&a[..prefix], &b[..prefix]);
+        let output = if decision.is_parallel() {
+            let f = &f;
+            expect_spawned(thread::scope(|s| {
+                equal::divide_zip_ref(self, &ScopedSpawner(s), a, b, f)
+            }))
+        } else {
+            a.iter().zip(b).map(|(a, b)| f(a, b)).collect()
+        };
+        self.finish(output, sample)
+    }
+
+    /// Processes every chunk of [WorkConfig::divide_chunks_owned] with `f`, either sequentially or with the scheduler
+    /// of [WorkConfig::schedule]. Every chunk can be processed by a worker of its own.
+    fn sized_chunks<F, T, R>(&self, chunks: Vec<Vec<T>>, parallel: bool, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(Vec<T>) -> R + Send + Sync + 'static,
+    {
+        if parallel && chunks.len() > 1 {
+            let config = self.clone().min_chunk_size(1);
+            if self.schedule == Schedule::Static {
+                expect_spawned(config.chunks(&OsSpawner, chunks, f))
+            } else {
+                expect_spawned(config.queue(&OsSpawner, chunks, f))
+            }
+        } else {
+            chunks.into_iter().map(f).collect()
+        }
+    }
+
+    fn windows_with<F, T, R>(&self, data: &[T], window: usize, f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&[T]) -> R + Send + Sync,
+    {
+        let len = (data.len() + 1).saturating_sub(window);
+        let (decision, sample) = self.plan_with(len, || Some(f(&data[len.checked_sub(1)?..])));
+        // Sampled window is the last one, the rest of them are windows of the slice without its last element
+        let data = &data[..data.len() - usize::from(sample.is_some())];
+        let output = if decision.is_parallel() {
+            let f = &f;
+            expect_spawned(thread::scope(|s| {
+                equal::divide_windows(self, &ScopedSpawner(s), data, window, f)
+            }))
+        } else {
+            data.windows(window).map(&f).collect()
+        };
+        self.finish(output, sample)
+    }
+
+    /// Collects the key-value pairs produced by `f` into a map of every chunk, which are then combined in input order
+    /// with [merge_maps]. Values of duplicate keys are merged with `merge(earlier, later)`.
+    fn collect_map<F, M, T, K, V, Mp>(&self, input: Vec<T>, f: F, merge: M) -> Mp
+    where
+        T: Send + 'static,
+        V: Send + 'static,
+        F: Fn(T) -> (K, V) + Send + Sync + 'static,
+        M: Fn(V, V) -> V + Send + Sync + 'static,
+        Mp: KeyedMap<K, V> + Send + 'static,
+    {
+        let merge = Arc::new(merge);
+        let fold = {
+            let merge = merge.clone();
+            move |mut map: Mp, val| {
+                let (key, val) = f(val);
+                map.insert_later(key, val, &*merge);
+                map
+            }
+        };
+        let combine = move |earlier, later| merge_maps(earlier, later, &*merge);
+        self.divide_fold(input, Mp::default, fold, combine)
+    }
+
+    /// Processes borrowed `input` according to [PanicPolicy] either sequentially or on scoped threads, with equal
+    /// chunks for [Schedule::Static] and atomic cursor otherwise
+    fn borrowed<F, T, R>(&self, input: &[T], f: F, schedule: Schedule) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Send + Sync,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            self.skipped(self.borrowed_with(input, skip_panics_ref(f), schedule))
+        } else {
+            self.borrowed_with(input, f, schedule)
+        }
+    }
+
+    fn borrowed_with<F, T, R>(&self, input: &[T], f: F, schedule: Schedule) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        let len = input.len();
+        let (decision, sample) = self.plan_with(len, || input.last().map(&f));
+        // Sampled element is the last one, the rest of them form a prefix of the input
+        let input = &input[..len - usize::from(sample.is_some())];
+        let output = if decision.is_parallel() {
+            let f = &f;
+            expect_spawned(thread::scope(|s| {
+                let spawner = ScopedSpawner(s);
+                if schedule == Schedule::Static {
+                    equal::divide_work_ref(self, &spawner, input, f)
+                } else {
+                    cursor::divide_work_shared(self, &spawner, input, input.len(), f)
+                }
+            }))
+        } else {
+            input.iter().map(f).collect()
+        };
+        self.finish(output, sample)
+    }
+
+    /// Mutates elements of `data` either sequentially or in equal chunks on scoped threads, depending on the
+    /// configuration
+    fn equal_mut<F, T>(&self, data: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Sync,
+    {
+        let len = data.len();
+        let (decision, sample) = self.plan_with(len, || data.last_mut().map(&f));
+        // Sampled element is the last one, the rest of them form a prefix of the data
+        let data = &mut data[..len - usize::from(sample.is_some())];
+        if decision.is_parallel() {
+            let f = &f;
+            expect_spawned(thread::scope(|s| {
+                equal::divide_for_each_mut(self, &ScopedSpawner(s), data, f)
+            }));
+        } else {
+            data.iter_mut().for_each(f);
+        }
+    }
+
+    /// Processes `input` shared between workers either sequentially or with atomic cursor, depending on the
+    /// configuration
+    fn shared<F, T, R>(&self, input: Arc<[T]>, f: F) -> Vec<R>
+    where
+        T: Send + Sync + 'static,
+        R: Send + 'static,
+        F: Fn(&T) -> R + Send + Sync + 'static,
+    {
+        let len = input.len();
+        let (decision, sample) = self.plan_with(len, || input.last().map(&f));
+        // Sampled element is the last one, the rest of them form a prefix of the input
+        let end = len - usize::from(sample.is_some());
+        let output = if decision.is_parallel() {
+            expect_spawned(cursor::divide_work_shared(self, &OsSpawner, input, end, f))
+        } else {
+            input[..end].iter().map(f).collect()
+        };
+        self.finish(output, sample)
+    }
+
+    /// Processes `input` with dynamic scheduler according to [PanicPolicy], unless [Schedule::Static] is set. Workers
+    /// are started with `spawner`.
+    pub(crate) fn dynamic<'env, S, F, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<(Vec<R>, Decision), SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+        F: Fn(T) -> R + Send + Sync + 'env,
+    {
+        if self.schedule == Schedule::Static {
+            self.equal(spawner, input, f)
+        } else if self.panic_policy == PanicPolicy::SkipItem {
+            let (output, decision) = self.run(input, skip_panics(f), |config, input, f| {
+                config.queue(spawner, input, f)
+            })?;
+            Ok((self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| config.queue(spawner, input, f))
+        }
+    }
+
+    /// Processes `input` with equal split according to [PanicPolicy]. Workers are started with `spawner`.
+    pub(crate) fn equal<'env, S, F, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<(Vec<R>, Decision), SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+        F: Fn(T) -> R + Send + Sync + 'env,
+    {
+        if self.panic_policy == PanicPolicy::SkipItem {
+            let (output, decision) = self.run(input, skip_panics(f), |config, input, f| {
+                config.chunks(spawner, input, f)
+            })?;
+            Ok((self.skipped(output), decision))
+        } else {
+            self.run(input, f, |config, input, f| {
+                config.chunks(spawner, input, f)
+            })
+        }
+    }
+
+    /// Parallel part of [WorkConfig::dynamic], with a queue per NUMA node if [numa](WorkConfig::numa) is set. `f` is
+    /// put behind dynamic dispatch.
+    fn queue<'env, S, F, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+        F: Fn(T) -> R + Send + Sync + 'env,
+    {
+        self.dyn_queue(spawner, input, DynFn::new(f))
+    }
+
+    /// Same as [WorkConfig::queue], but isn't instantiated for every closure type
+    fn dyn_queue<'env, S, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: DynFn<'env, T, R>,
+    ) -> Result<Vec<R>, SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+    {
+        let f = move |val| f.call(val);
+        #[cfg(feature = "numa")]
+        if let Some(nodes) = self.numa.then(numa::nodes).flatten() {
+            return numa::divide_work(self, spawner, input, f, nodes);
+        }
+        dynamic::divide_work(self, spawner, input, f)
+    }
+
+    /// Parallel part of [WorkConfig::equal], with chunks grouped by NUMA nodes if [numa](WorkConfig::numa) is set. `f`
+    /// is put behind dynamic dispatch.
+    fn chunks<'env, S, F, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: F,
+    ) -> Result<Vec<R>, SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+        F: Fn(T) -> R + Send + Sync + 'env,
+    {
+        self.dyn_chunks(spawner, input, DynFn::new(f))
+    }
+
+    /// Same as [WorkConfig::chunks], but isn't instantiated for every closure type
+    fn dyn_chunks<'env, S, T, R>(
+        &self,
+        spawner: &S,
+        input: Vec<T>,
+        f: DynFn<'env, T, R>,
+    ) -> Result<Vec<R>, SpawnError>
+    where
+        S: Spawner<'env>,
+        T: Send + 'env,
+        R: Send + 'env,
+    {
+        let f = move |val| f.call(val);
+        #[cfg(feature = "numa")]
+        if let Some(nodes) = self.numa.then(numa::nodes).flatten() {
+            return numa::divide_equal_work(self, spawner, input, f, nodes);
+        }
+        equal::divide_equal_work(self, spawner, input, f)
+    }
+
+    /// Processes `input` either sequentially or with `parallel` scheduler, depending on the configuration. Sequential
+    /// path calls `f` directly, so that small inputs don't pay for dynamic dispatch of the schedulers.
+    #[inline]
+    fn run<F, T, R, P>(
+        &self,
+        mut input: Vec<T>,
+        f: F,
+        parallel: P,
+    ) -> Result<(Vec<R>, Decision), SpawnError>
+    where
+        F: Fn(T) -> R,
+        P: FnOnce(&Self, Vec<T>, F) -> Result<Vec<R>, SpawnError>,
+    {
+        let (decision, sample) = self.plan(&mut input, &f);
+        let output = if decision.is_parallel() {
+            parallel(self, input, f)?
+        } else {
+            input.into_iter().map(f).collect()
+        };
+        Ok((self.finish(output, sample), decision))
+    }
+
+    /// Appends result of the sampled element to `output`, shrinking it if [shrink_output](WorkConfig::shrink_output)
+    /// is set. Exactly one slot is reserved for the sample, so that `output` isn't left with doubled capacity.
+    fn finish<R>(&self, mut output: Vec<R>, sample: Option<R>) -> Vec<R> {
+        if let Some(sample) = sample {
+            output.reserve_exact(1);
+            output.push(sample);
+        }
+        self.shrink(output)
+    }
+
+    /// Results of the elements that didn't panic, shrunk if [shrink_output](WorkConfig::shrink_output) is set
+    fn skipped<R>(&self, output: Vec<Option<R>>) -> Vec<R> {
+        self.shrink(output.into_iter().flatten().collect())
+    }
+
+    fn shrink<R>(&self, mut output: Vec<R>) -> Vec<R> {
+        if self.shrink_output {
+            output.shrink_to_fit();
+        }
+        output
+    }
+
+    /// Decides whether `input` should be split between threads. In adaptive mode the last element is taken out of
+    /// `input` and processed to estimate cost of `f`, its result must be appended to the output. The last element is
+    /// sampled, since it can be taken out without shifting the rest of the input.
+    fn plan<F, T, R>(&self, input: &mut Vec<T>, f: &F) -> (Decision, Option<R>)
+    where
+        F: Fn(T) -> R,
+    {
+        self.plan_with(input.len(), || input.pop().map(f))
+    }
+
+    /// Same as [WorkConfig::plan], but for input of `len` elements, the last of which is processed by `sample` in
+    /// adaptive mode
+    fn plan_with<S, R>(&self, len: usize, sample: S) -> (Decision, Option<R>)
+    where
+        S: FnOnce() -> Option<R>,
+    {
+        match self.adaptive_target {
+            Some(target)
+                if len >= 2
+                    && self.worker_count() >= 2
+                    && !crate::is_sequential()
+                    && !self.is_nested() =>
+            {
+                let start = Instant::now();
+                let sample_res = sample();
+                let sample = start.elapsed();
+                let estimate = sample.saturating_mul(u32::try_from(len).unwrap_or(u32::MAX));
+
+                let decision = Decision::Estimated {
+                    sample,
+                    estimate,
+                    target,
+                };
+                (decision, sample_res)
+            }
+            _ => (self.decide(len), None),
+        }
+    }
+
+    /// Pinning of worker number `index`, if workers are pinned
+    #[cfg(feature = "affinity")]
+    pub(crate) fn pin(&self, index: usize) -> Option<Pin> {
+        self.pin_threads.then(|| Pin {
+            cores: self.pin_cores.clone().filter(|cores| !cores.is_empty()),
+            index,
+            hook: self.pin_failure_hook,
+        })
+    }
+
+    /// Whether this call is made from `f` of another call, and must run sequentially
+    fn is_nested(&self) -> bool {
+        !self.nested && worker::in_worker()
+    }
+
+    /// Whether workers stop picking up new elements as soon as `f` panics on any of them
+    pub(crate) fn cancels_on_panic(&self) -> bool {
+        self.panic_policy != PanicPolicy::Propagate
+    }
+
+    /// Amount of worker threads used to split work
+    pub(crate) fn worker_count(&self) -> usize {
+        self.worker_count_for_cores(self.core_count())
+    }
+
+    /// Amount of cores according to [CoreSelection]
+    fn core_count(&self) -> usize {
+        match self.cores {
+            CoreSelection::Logical => threads::default_parallelism(),
+            CoreSelection::Physical => threads::physical_parallelism(),
+            CoreSelection::Exact(cores) => cores.max(1),
+        }
+    }
+
+    /// Amount of worker threads used to split work if there are `cores` available
+    fn worker_count_for_cores(&self, cores: usize) -> usize {
+        self.threads.unwrap_or_else(|| {
+            let threads = cores.saturating_sub(self.reserved_cores).max(1);
+            if self.oversubscription == 1.0 {
+                threads
+            } else {
+                // Float to int casts saturate, NaN becomes 0
+                let threads = (threads as f64 * self.oversubscription).round() as usize;
+                threads.clamp(1, MAX_OVERSUBSCRIBED_THREADS)
+            }
+        })
+    }
+
+    /// Amount of worker threads used to split work for input of `len` elements. There are never more workers than
+    /// elements, since extra workers would have nothing to do.
+    pub(crate) fn workers_for(&self, len: usize) -> usize {
+        self.workers_for_cores(self.core_count(), len)
+    }
+
+    /// Amount of worker threads used to split work for input of `len` elements if there are `cores` available
+    fn workers_for_cores(&self, cores: usize, len: usize) -> usize {
+        self.worker_count_for_cores(cores).min(len)
+    }
+
+    /// Decides whether input of `len` elements should be split between threads, without taking adaptive mode into
+    /// account. There is nothing to split when input has a single element, so threshold below 2 behaves the same as 2.
+    pub(crate) fn decide(&self, len: usize) -> Decision {
+        let threshold = self.threshold.unwrap_or_else(crate::parallel_threshold);
+        if crate::is_sequential() {
+            Decision::ForcedSequential
+        } else if self.is_nested() {
+            Decision::Nested
+        } else if self.worker_count() < 2 {
+            Decision::SingleThread
+        } else if len < threshold.max(2) {
+            Decision::BelowThreshold { len, threshold }
+        } else {
+            Decision::AboveThreshold { len, threshold }
+        }
+    }
+}
+
+/// Catches panics of `f`, so that elements for which it panicked can be skipped
+fn skip_panics<F, T, R>(f: F) -> impl Fn(T) -> Option<R> + Send + Sync
+where
+    F: Fn(T) -> R + Send + Sync,
+{
+    move |val| panic::catch_unwind(AssertUnwindSafe(|| f(val))).ok()
+}
+
+/// Same as [skip_panics], but for `f` that borrows its elements
+fn skip_panics_ref<F, T, R>(f: F) -> impl Fn(&T) -> Option<R> + Send + Sync
+where
+    T: ?Sized,
+    F: Fn(&T) -> R + Send + Sync,
+{
+    move |val| panic::catch_unwind(AssertUnwindSafe(|| f(val))).ok()
+}
+
+/// Catches panics of `f` for elements enumerated with their index in the input, so that they can be reported as
+/// errors
+fn catch_item_panics<F, T, R>(
+    f: F,
+) -> impl Fn((usize, T)) -> Result<R, ItemPanic> + Send + Sync + 'static
+where
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    move |(index, val)| {
+        panic::catch_unwind(AssertUnwindSafe(|| f(val)))
+            .map_err(|payload| ItemPanic { index, payload })
+    }
+}
+
+/// Maps that [WorkConfig::divide_collect_map] and its variants collect into
+trait KeyedMap<K, V>: Default + IntoIterator<Item = (K, V)> {
+    fn len(&self) -> usize;
+
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    fn insert(&mut self, key: K, val: V);
+
+    /// Inserts value of an element that comes after the ones already in the map
+    fn insert_later<M: Fn(V, V) -> V>(&mut self, key: K, val: V, merge: M) {
+        let val = match self.remove(&key) {
+            Some(earlier) => merge(earlier, val),
+            None => val,
+        };
+        self.insert(key, val);
+    }
+
+    /// Inserts value of an element that comes before the ones already in the map
+    fn insert_earlier<M: Fn(V, V) -> V>(&mut self, key: K, val: V, merge: M) {
+        let val = match self.remove(&key) {
+            Some(later) => merge(val, later),
+            None => val,
+        };
+        self.insert(key, val);
+    }
+}
+
+impl<K: Eq + Hash, V> KeyedMap<K, V> for HashMap<K, V> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn insert(&mut self, key: K, val: V) {
+        self.insert(key, val);
+    }
+}
+
+impl<K: Ord, V> KeyedMap<K, V> for BTreeMap<K, V> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove(key)
+    }
+
+    fn insert(&mut self, key: K, val: V) {
+        self.insert(key, val);
+    }
+}
+
+/// Merges a map of earlier elements with a map of later ones by moving the smaller one into the larger one, so that
+/// the larger one grows in place instead of being rebuilt
+fn merge_maps<Mp, M, K, V>(mut earlier: Mp, mut later: Mp, merge: M) -> Mp
+where
+    Mp: KeyedMap<K, V>,
+    M: Fn(V, V) -> V + Copy,
+{
+    if earlier.len() >= later.len() {
+        for (key, val) in later {
+            earlier.insert_later(key, val, merge);
+        }
+        earlier
+    } else {
+        for (key, val) in earlier {
+            later.insert_earlier(key, val, merge);
+        }
+        later
+    }
+}
+
+/// Unwraps result of a scheduler for variants that are not expected to fail
+pub(crate) fn expect_spawned<T>(res: Result<T, SpawnError>) -> T {
+    res.unwrap_or_else(|err| panic!("{}", WorkError::<Infallible>::from(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PARALLEL_WORK_THRESHOLD;
+    use std::sync::atomic;
+
+    #[test]
+    fn default_matches_free_functions() {
+        let config = WorkConfig::default();
+        assert_eq!(config.threshold, None);
+        assert_eq!(config.worker_count(), threads::default_parallelism());
+    }
+
+    #[test]
+    fn reuse_cloned_config() {
+        let config = WorkConfig::new().threads(3).threshold(2);
+        let stored = config.clone();
+
+        let expected: Vec<i32> = (1..21).collect();
+        assert_eq!(config.divide_work((0..20).collect(), |x| x + 1), expected);
+        assert_eq!(stored.divide_work((0..20).collect(), |x| x + 1), expected);
+        assert_eq!(
+            stored.divide_equal_work((0..20).collect(), |x| x + 1),
+            expected
+        );
+    }
+
+    #[test]
+    fn oversubscription() {
+        let cores = threads::default_parallelism();
+        assert_eq!(
+            WorkConfig::io_bound(4.0).worker_count(),
+            (cores * 4).min(MAX_OVERSUBSCRIBED_THREADS)
+        );
+        assert_eq!(
+            WorkConfig::io_bound(1e9).worker_count(),
+            MAX_OVERSUBSCRIBED_THREADS
+        );
+        assert_eq!(WorkConfig::io_bound(0.0).worker_count(), 1);
+        assert_eq!(WorkConfig::io_bound(f64::NAN).worker_count(), 1);
+        assert_eq!(WorkConfig::io_bound(4.0).threads(3).worker_count(), 3);
+
+        // Still capped by input length
+        assert_eq!(WorkConfig::io_bound(1e9).workers_for(20), 20);
+    }
+
+    #[test]
+    fn reserved_cores() {
+        let config = WorkConfig::new().reserve_cores(1);
+        assert_eq!(config.worker_count_for_cores(16), 15);
+        assert_eq!(config.worker_count_for_cores(2), 1);
+        assert_eq!(config.worker_count_for_cores(1), 1);
+        assert_eq!(
+            WorkConfig::new()
+                .reserve_cores(100)
+                .worker_count_for_cores(16),
+            1
+        );
+
+        assert_eq!(
+            config.clone().oversubscribe(2.0).worker_count_for_cores(16),
+            30
+        );
+        // Explicit thread count wins
+        assert_eq!(config.threads(16).worker_count_for_cores(16), 16);
+    }
+
+    #[test]
+    fn workers_capped_by_len() {
+        let config = WorkConfig::new();
+        assert_eq!(
+            config.workers_for_cores(64, PARALLEL_WORK_THRESHOLD + 1),
+            PARALLEL_WORK_THRESHOLD + 1
+        );
+        assert_eq!(config.workers_for_cores(64, 12), 12);
+        assert_eq!(config.workers_for_cores(64, 1000), 64);
+    }
+
+    #[test]
+    fn decisions() {
+        let config = WorkConfig::new().threads(2).threshold(5);
+        let (_, decision) = config.divide_work_with_decision(vec![1, 2, 3], |x| x);
+        assert_eq!(
+            decision,
+            Decision::BelowThreshold {
+                len: 3,
+                threshold: 5
+            }
+        );
+        let (_, decision) = config.divide_equal_work_with_decision(vec![1; 5], |x| x);
+        assert_eq!(
+            decision,
+            Decision::AboveThreshold {
+                len: 5,
+                threshold: 5
+            }
+        );
+
+        let (_, decision) = config
+            .threads(1)
+            .divide_work_with_decision(vec![1; 5], |x| x);
+        assert_eq!(decision, Decision::SingleThread);
+    }
+
+    #[test]
+    fn adaptive_cheap_closure() {
+        let config = WorkConfig::new()
+            .threads(2)
+            .adaptive_target(Duration::from_secs(1));
+        let expected: Vec<i32> = (1..11).collect();
+
+        let (output, decision) = config.divide_work_with_decision((0..10).collect(), |x| x + 1);
+        assert_eq!(output, expected);
+        assert!(matches!(decision, Decision::Estimated { .. }));
+        assert!(!decision.is_parallel());
+
+        let (output, decision) =
+            config.divide_equal_work_with_decision((0..10).collect(), |x| x + 1);
+        assert_eq!(output, expected);
+        assert!(!decision.is_parallel());
+    }
+
+    #[test]
+    fn adaptive_expensive_closure() {
+        // Threshold is ignored in adaptive mode
+        let config = WorkConfig::adaptive().threads(2).threshold(100);
+        let slow = |x| {
+            std::thread::sleep(Duration::from_millis(2));
+            x + 1
+        };
+        let expected: Vec<i32> = (1..5).collect();
+
+        let (output, decision) = config.divide_work_with_decision((0..4).collect(), slow);
+        assert_eq!(output, expected);
+        assert!(decision.is_parallel());
+
+        let (output, decision) = config.divide_equal_work_with_decision((0..4).collect(), slow);
+        assert_eq!(output, expected);
+        assert!(decision.is_parallel());
+    }
+
+    #[test]
+    fn shrunk_output() {
+        let config = WorkConfig::new()
+            .threads(3)
+            .panic_policy(PanicPolicy::SkipItem)
+            .shrink_output(true);
+        let f = |x: i32| {
+            assert!(x % 10 != 0, "skipped");
+            x
+        };
+        let output = config.divide_work((0..1000).collect(), f);
+        assert_eq!(output.len(), 900);
+        assert_eq!(output.capacity(), output.len());
+        let output = config.divide_equal_work((0..1000).collect(), f);
+        assert_eq!(output.capacity(), output.len());
+        let output = config.divide_work_unordered((0..1000).collect(), f);
+        assert_eq!(output.capacity(), output.len());
+
+        // Sampled result is appended to the output of the sequential path
+        let config = WorkConfig::adaptive().threads(2).shrink_output(true);
+        let output = config.divide_work((0..10).collect(), |x: i32| x + 1);
+        assert_eq!(output.capacity(), output.len());
+    }
+
+    #[test]
+    fn iterator_inputs() {
+        let config = WorkConfig::new().threads(3);
+        let expected: Vec<i32> = (0..100).map(|x| x * 2).collect();
+
+        // Front of the buffer is pushed last, so the deque wraps around
+        let mut deque = std::collections::VecDeque::with_capacity(100);
+        for x in (0..50).rev() {
+            deque.push_front(x);
+        }
+        for x in 50..100 {
+            deque.push_back(x);
+        }
+        assert!(!deque.as_slices().1.is_empty());
+        assert_eq!(config.divide_work_iter(deque.clone(), |x| x * 2), expected);
+        assert_eq!(config.divide_equal_work_iter(deque, |x| x * 2), expected);
+
+        let boxed: Box<[i32]> = (0..100).collect();
+        assert_eq!(config.divide_work_iter(boxed.clone(), |x| x * 2), expected);
+        assert_eq!(config.divide_equal_work_iter(boxed, |x| x * 2), expected);
+
+        let mapped = (0..100).map(|x| x * 2);
+        assert_eq!(config.divide_work_iter(mapped.clone(), |x| x), expected);
+        assert_eq!(config.divide_equal_work_iter(mapped, |x| x), expected);
+    }
+
+    #[test]
+    fn filter_keeps_order() {
+        let configs = [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).batch_size(7),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+        ];
+        for config in &configs {
+            for seed in 0..10 {
+                let mut input: Vec<u64> = (0..1000).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                let pred = move |x: &u64| (x ^ seed) % 3 == 0;
+                let expected: Vec<u64> = input.iter().copied().filter(pred).collect();
+                assert_eq!(config.divide_filter(input, pred), expected);
+            }
+
+            let all: Vec<u64> = (0..1000).collect();
+            assert_eq!(config.divide_filter(all.clone(), |_| true), all);
+            assert_eq!(config.divide_filter(all, |_| false), Vec::<u64>::new());
+        }
+    }
+
+    #[test]
+    fn filter_expensive_predicate() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+        ] {
+            let pred = |x: &u32| {
+                std::thread::sleep(Duration::from_micros(200));
+                x % 10 == 0
+            };
+            assert_eq!(
+                config.divide_filter((0..100).collect(), pred),
+                (0..100).step_by(10).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn filter_map_edge_cases() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let parse = |line: String| line.parse::<u32>().ok();
+            let lines: Vec<String> = (0..1000).map(|x| format!("{}", x)).collect();
+            assert_eq!(
+                config.divide_filter_map(lines.clone(), parse),
+                (0..1000).collect::<Vec<_>>()
+            );
+
+            let none = config.divide_filter_map(lines.clone(), |_| None::<u32>);
+            assert!(none.is_empty());
+
+            let one = config.divide_filter_map(lines, |line| (line == "617").then_some(line));
+            assert_eq!(one, ["617"]);
+        }
+    }
+
+    #[test]
+    fn flat_map_keeps_order() {
+        // Every third element expands to nothing, and every hundredth one to thousands of results
+        let expand = |x: usize| match x % 100 {
+            0 => vec![x; 5000],
+            n if n % 3 == 0 => Vec::new(),
+            n => vec![x; n],
+        };
+        let expected: Vec<usize> = (0..1000).flat_map(expand).collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).batch_size(3),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            assert_eq!(
+                config.divide_flat_map((0..1000).collect(), expand),
+                expected
+            );
+        }
+        let output = WorkConfig::new()
+            .threads(4)
+            .divide_flat_map((0..1000).collect(), |_: usize| std::iter::empty::<u8>());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn fold_in_chunk_order() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3).min_chunk_size(1000),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let squares = |acc: u64, x: u64| acc + x * x;
+            let expected = (0..1_000_000).fold(0, squares);
+            let sum = config.divide_fold((0..1_000_000).collect(), || 0, squares, |a, b| a + b);
+            assert_eq!(sum, expected);
+
+            // Concatenation isn't commutative, so accumulators combined out of order would shuffle the digits
+            let digits = |mut acc: String, x: u32| {
+                acc.push(char::from_digit(x % 10, 10).unwrap());
+                acc
+            };
+            let expected = (0..1000).fold(String::new(), digits);
+            let concat =
+                config.divide_fold((0..1000).collect(), String::new, digits, |a, b| a + &b);
+            assert_eq!(concat, expected);
+        }
+    }
+
+    #[test]
+    fn reduce_in_chunk_order() {
+        // Multiplication of 2x2 matrices is associative, but not commutative
+        type Matrix = [[u64; 2]; 2];
+        let mul = |a: Matrix, b: Matrix| {
+            let cell = |i: usize, j: usize| (a[i][0] * b[0][j] + a[i][1] * b[1][j]) % 1_000_007;
+            [[cell(0, 0), cell(0, 1)], [cell(1, 0), cell(1, 1)]]
+        };
+        let input: Vec<Matrix> = (0..1000).map(|x| [[1, x], [x % 7, 1]]).collect();
+        let expected = input.iter().copied().reduce(mul);
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            assert_eq!(config.divide_reduce(input.clone(), mul), expected);
+            assert_eq!(config.divide_reduce(Vec::new(), mul), None);
+        }
+
+        let concat = |a: String, b: String| a + &b;
+        let words: Vec<String> = (0..100).map(|x| x.to_string()).collect();
+        let expected = words.concat();
+        let config = WorkConfig::new().threads(4);
+        assert_eq!(config.divide_reduce(words, concat), Some(expected));
+
+        let config = WorkConfig::new().threads(4).threshold(2);
+        assert!(!config.decide(1).is_parallel());
+        assert_eq!(
+            config
+                .divide_reduce(vec![String::from("one")], concat)
+                .unwrap(),
+            "one"
+        );
+        let config = WorkConfig::adaptive().threads(4);
+        assert_eq!(
+            config
+                .divide_reduce(vec![String::from("one")], concat)
+                .unwrap(),
+            "one"
+        );
+    }
+
+    #[test]
+    fn sum_and_product() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let input: Vec<u64> = (0..100_000).collect();
+            assert_eq!(
+                config.divide_sum::<_, u64>(input.clone()),
+                input.iter().sum()
+            );
+            let squares: u64 = config.divide_sum_by(input, |x| x * x);
+            assert_eq!(squares, (0..100_000u64).map(|x| x * x).sum());
+
+            let input: Vec<u32> = (0..1000)
+                .map(|x| if x % 100 == 0 { 2 } else { 1 })
+                .collect();
+            assert_eq!(config.divide_product::<_, u32>(input), 1024);
+            assert_eq!(config.divide_sum::<u8, u8>(Vec::new()), 0);
+            assert_eq!(config.divide_product::<u8, u8>(Vec::new()), 1);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overflow")]
+    fn sum_overflow() {
+        WorkConfig::new()
+            .threads(4)
+            .divide_sum::<u8, u8>(vec![1; 1000]);
+    }
+
+    #[test]
+    fn extremes_break_ties_like_std() {
+        // Keys repeat across the whole input, so ties land in different chunks
+        let input: Vec<(u32, usize)> = (0..1000).map(|idx| ((idx as u32 * 7) % 10, idx)).collect();
+        let key = |&(key, _): &(u32, usize)| key;
+        let compare = |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0);
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let max = input.iter().copied().max_by_key(key);
+            let min = input.iter().copied().min_by_key(key);
+            assert_eq!(max, Some((9, 997)));
+            assert_eq!(min, Some((0, 0)));
+            assert_eq!(config.divide_max_by_key(input.clone(), key), max);
+            assert_eq!(config.divide_min_by_key(input.clone(), key), min);
+            assert_eq!(config.divide_max_by(input.clone(), compare), max);
+            assert_eq!(config.divide_min_by(input.clone(), compare), min);
+            assert_eq!(config.divide_max_by_key(Vec::new(), key), None);
+        }
+    }
+
+    #[test]
+    fn count_matches_filter() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threads(4).threshold(2000),
+        ] {
+            for seed in 0..10 {
+                let mut input: Vec<u64> = (0..1000).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                input.truncate(500 + seed as usize * 50);
+                let pred = move |x: &u64| (x ^ seed) % 7 < 3;
+                let expected = input.iter().filter(|x| pred(x)).count();
+                assert_eq!(config.divide_count(input.clone(), pred), expected);
+                assert_eq!(config.divide_equal_count(input, pred), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn any_stops_early() {
+        let config = WorkConfig::new().threads(4);
+        let evaluated = Arc::new(atomic::AtomicUsize::new(0));
+        let is_zero = {
+            let evaluated = evaluated.clone();
+            move |&x: &u32| {
+                evaluated.fetch_add(1, atomic::Ordering::Relaxed);
+                std::thread::sleep(Duration::from_micros(10));
+                x == 0
+            }
+        };
+        assert!(config.divide_any((0..100_000).collect(), is_zero.clone()));
+        let evaluated_any = evaluated.swap(0, atomic::Ordering::Relaxed);
+        assert!(evaluated_any < 10_000, "{} evaluated", evaluated_any);
+
+        // Every element but the first one is a counterexample
+        assert!(!config.divide_all((0..100_000).collect(), is_zero));
+        let evaluated_all = evaluated.load(atomic::Ordering::Relaxed);
+        assert!(evaluated_all < 10_000, "{} evaluated", evaluated_all);
+    }
+
+    #[test]
+    fn any_and_all_match_sequential() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let input: Vec<u32> = (0..1000).collect();
+            assert!(!config.divide_any(input.clone(), |&x| x >= 1000));
+            assert!(config.divide_any(input.clone(), |&x| x == 999));
+            assert!(config.divide_all(input.clone(), |&x| x < 1000));
+            assert!(!config.divide_all(input.clone(), |&x| x != 500));
+            assert!(!config.divide_any(Vec::new(), |_: &u32| true));
+            assert!(config.divide_all(Vec::new(), |_: &u32| false));
+        }
+    }
+
+    #[test]
+    fn find_any_match() {
+        let config = WorkConfig::new().threads(4);
+        for seed in 0..10 {
+            // A handful of matches scattered over different regions of the input
+            let mut input: Vec<u32> = (0..1000).collect();
+            crate::shuffle::shuffle(&mut input, seed);
+            let is_match = |&x: &u32| x % 200 == 7;
+            let found = config.divide_find_any_ref(&input, is_match);
+            assert!(is_match(found.unwrap()));
+            let found = config.divide_find_any(input, is_match);
+            assert!(is_match(&found.unwrap()));
+        }
+        assert_eq!(
+            config.divide_find_any((0..1000).collect(), |&x| x > 1000),
+            None
+        );
+        assert_eq!(
+            config.divide_find_any_ref(&[1, 2, 3], |&x| x == 2),
+            Some(&2)
+        );
+
+        // Every tenth element matches, so workers stop long before they get through the input
+        let evaluated = Arc::new(atomic::AtomicUsize::new(0));
+        let common = {
+            let evaluated = evaluated.clone();
+            move |&x: &u32| {
+                evaluated.fetch_add(1, atomic::Ordering::Relaxed);
+                std::thread::sleep(Duration::from_micros(10));
+                x % 10 == 9
+            }
+        };
+        let found = config.divide_find_any((0..100_000).collect(), common);
+        assert_eq!(found.map(|x| x % 10), Some(9));
+        let evaluated = evaluated.load(atomic::Ordering::Relaxed);
+        assert!(evaluated < 1000, "{} evaluated", evaluated);
+    }
+
+    #[test]
+    fn find_first_lowest_index() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..10 {
+                let mut input: Vec<u32> = (0..1000).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                let is_match = |&x: &u32| x % 300 == 299;
+                let expected = input.iter().position(is_match).map(|idx| (idx, input[idx]));
+                assert_eq!(config.divide_find_first(input, is_match), expected);
+            }
+            let input: Vec<u32> = (0..1000).collect();
+            assert_eq!(
+                config.divide_find_first(input.clone(), |&x| x == 999),
+                Some((999, 999))
+            );
+            assert_eq!(config.divide_find_first(input, |&x| x > 1000), None);
+            assert_eq!(config.divide_find_first(Vec::new(), |_: &u32| true), None);
+        }
+
+        // Match of the first chunk takes a while, so matches of the later chunks are found before it
+        let slow_early = |&x: &u32| {
+            if x == 10 {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            x == 10 || x % 100 == 50
+        };
+        let config = WorkConfig::new().threads(4);
+        assert_eq!(
+            config.divide_find_first((0..1000).collect(), slow_early),
+            Some((10, 10))
+        );
+    }
+
+    #[test]
+    fn positions_ascending() {
+        let input: Vec<u32> = (0..1000).collect();
+        let positions = |pred: &dyn Fn(&u32) -> bool| -> Vec<usize> {
+            (0..input.len()).filter(|&idx| pred(&input[idx])).collect()
+        };
+        let clustered = |&x: &u32| (200..260).contains(&x) || x > 990;
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            assert_eq!(
+                config.divide_positions(&input, clustered),
+                positions(&clustered)
+            );
+            assert_eq!(
+                config.divide_positions(&input, |_| false),
+                Vec::<usize>::new()
+            );
+            assert_eq!(
+                config.divide_positions(&input, |_| true),
+                positions(&|_| true)
+            );
+            assert_eq!(
+                config.divide_positions(&input[..0], |_| true),
+                Vec::<usize>::new()
+            );
+        }
+    }
+
+    #[test]
+    fn stable_partition() {
+        // Elements carry their original index, so any reordering within a side is visible
+        let input: Vec<(usize, u32)> = (0..1000).map(|idx| (idx, (idx as u32 * 37) % 11)).collect();
+        let pred = |&(_, x): &(usize, u32)| x < 4;
+        let expected: (Vec<_>, Vec<_>) = input.iter().copied().partition(pred);
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            assert_eq!(config.divide_partition(input.clone(), pred), expected);
+            assert_eq!(
+                config.divide_partition(input.clone(), |_| true),
+                (input.clone(), Vec::new())
+            );
+            assert_eq!(
+                config.divide_partition(input.clone(), |_| false),
+                (Vec::new(), input.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn partition_map_keeps_order() {
+        let classify = |x: u32| {
+            if x % 3 == 0 {
+                Either::Left(x)
+            } else {
+                Either::Right(x.to_string())
+            }
+        };
+        let input: Vec<u32> = (0..1000).collect();
+        let left: Vec<u32> = (0..1000).filter(|x| x % 3 == 0).collect();
+        let right: Vec<String> = (0..1000)
+            .filter(|x| x % 3 != 0)
+            .map(|x| x.to_string())
+            .collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let (l, r) = config.divide_partition_map(input.clone(), classify);
+            assert_eq!((&l, &r), (&left, &right));
+            let (l, r) = config.divide_partition_map(input.clone(), Either::<u32, ()>::Left);
+            assert_eq!((l, r), (input.clone(), Vec::new()));
+
+            let parse = |x: u32| {
+                if x % 3 == 0 {
+                    Ok(x)
+                } else {
+                    Err(x.to_string())
+                }
+            };
+            let (ok, errors) = config.divide_partition_results(input.clone(), parse);
+            assert_eq!((&ok, &errors), (&left, &right));
+            let (ok, errors) = config.divide_partition_results(input.clone(), Err::<(), u32>);
+            assert_eq!((ok, errors), (Vec::new(), input.clone()));
+        }
+    }
+
+    #[test]
+    fn collect_map_later_wins() {
+        // First half collides on a few keys, so that later chunks have the larger maps
+        let key = |x: u32| if x < 500 { x % 3 } else { x };
+        let input: Vec<u32> = (0..1000).collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(7),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let expected: HashMap<u32, u32> = input.iter().map(|&x| (key(x), x)).collect();
+            let map = config.divide_collect_map(input.clone(), move |x| (key(x), x));
+            assert_eq!(map, expected);
+            assert_eq!(map[&1], 499);
+            let map = config.divide_collect_btree_map(input.clone(), move |x| (key(x), x));
+            assert_eq!(map, expected.into_iter().collect());
+
+            let mut expected: BTreeMap<u32, String> = BTreeMap::new();
+            for &x in &input {
+                expected
+                    .entry(key(x))
+                    .or_default()
+                    .push_str(&format!("{},", x));
+            }
+            let concat = |a: String, b: String| a + &b;
+            let map = config.divide_collect_btree_map_with(
+                input.clone(),
+                move |x| (key(x), format!("{},", x)),
+                concat,
+            );
+            assert_eq!(map, expected);
+            let map = config.divide_collect_map_with(
+                input.clone(),
+                move |x| (key(x), format!("{},", x)),
+                concat,
+            );
+            assert_eq!(map, expected.into_iter().collect());
+        }
+        let map = WorkConfig::new().divide_collect_map(Vec::<u32>::new(), |x| (x, x));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn group_by_keeps_order() {
+        // Key 0 appears in every chunk, the other keys are unique to the chunks of one half of the input
+        let key = |x: &u32| match x % 4 {
+            0 => 0,
+            _ if *x < 500 => 1,
+            _ => 2,
+        };
+        let input: Vec<u32> = (0..1000).collect();
+        let mut expected: HashMap<u32, Vec<String>> = HashMap::new();
+        for x in &input {
+            expected.entry(key(x)).or_default().push(x.to_string());
+        }
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(7),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let groups = config.divide_group_by(input.clone(), key, |x| x.to_string());
+            assert_eq!(groups, expected);
+            let groups = config.divide_group_by(Vec::new(), key, |x| x);
+            assert!(groups.is_empty());
+        }
+    }
+
+    #[test]
+    fn dedup_matches_vec() {
+        for config in [
+            WorkConfig::new().threads(4).threshold(1),
+            WorkConfig::new().threads(7).threshold(1),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            // Runs of 1 to 50 elements straddle the boundaries of chunks of any size
+            for input in [
+                (0..1000u32).map(|x| x / 50).collect(),
+                (0..1000u32).map(|x| (x as f64).sqrt() as u32).collect(),
+                vec![7u32; 1000],
+                vec![1, 2, 2],
+                Vec::new(),
+            ] {
+                let mut expected = input.clone();
+                expected.dedup();
+                assert_eq!(config.divide_dedup(input.clone()), expected);
+
+                let mut expected = input.clone();
+                expected.dedup_by_key(|x| *x / 3);
+                assert_eq!(config.divide_dedup_by_key(input, |x| *x / 3), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn retain_matches_vec() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..10 {
+                let mut input: Vec<String> = (0..seed * 100).map(|x| x.to_string()).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                for pred in [
+                    |s: &String| s.len() % 2 == 0 || s.ends_with('7'),
+                    |_: &String| false,
+                    |_: &String| true,
+                ] {
+                    let mut expected = input.clone();
+                    expected.retain(pred);
+                    let mut vec = input.clone();
+                    let capacity = vec.capacity();
+                    config.divide_retain(&mut vec, pred);
+                    assert_eq!(vec, expected);
+                    assert_eq!(vec.capacity(), capacity);
+                }
+            }
+        }
+
+        let config = WorkConfig::new()
+            .threads(4)
+            .panic_policy(PanicPolicy::SkipItem);
+        let mut vec: Vec<u32> = (0..100).collect();
+        config.divide_retain(&mut vec, |&x| {
+            assert_ne!(x, 50, "bad item");
+            x % 10 == 0
+        });
+        assert_eq!(vec, [0, 10, 20, 30, 40, 60, 70, 80, 90]);
+    }
+
+    #[test]
+    fn zip_matches_sequential() {
+        let f = |a: u64, b: String| format!("{}{}", b, a % 97);
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..5 {
+                let mut a: Vec<u64> = (0..seed * 300).collect();
+                crate::shuffle::shuffle(&mut a, seed);
+                let b: Vec<String> = a.iter().map(|x| (x * 7 % 13).to_string()).collect();
+                let expected: Vec<String> =
+                    a.iter().zip(&b).map(|(&a, b)| f(a, b.clone())).collect();
+                assert_eq!(
+                    config.divide_zip_ref(&a, &b, |&a, b| f(a, b.clone())),
+                    expected
+                );
+                assert_eq!(config.divide_zip(a, b, f), expected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "zipped vectors must have the same length")]
+    fn zip_length_mismatch() {
+        WorkConfig::new().divide_zip(vec![1, 2, 3], vec![1, 2], |a: i32, b: i32| a + b);
+    }
+
+    #[test]
+    #[should_panic(expected = "zipped slices must have the same length")]
+    fn zip_ref_length_mismatch() {
+        WorkConfig::new().divide_zip_ref(&[1], &[] as &[i32], |a, b| a + b);
+    }
+
+    #[test]
+    fn enumerate_passes_original_indices() {
+        // Some elements take longer to process, so that elements after them finish first
+        let f = |idx: usize, val: u64| {
+            if val % 16 == 0 {
+                thread::sleep(Duration::from_micros(50));
+            }
+            (idx, val)
+        };
+        for config in [
+            WorkConfig::new().threads(4).threshold(1),
+            WorkConfig::new().threads(4).threshold(1).batch_size(3),
+            WorkConfig::new().threads(3).schedule(Schedule::Static),
+            WorkConfig::new().threads(3).partition(Partition::Strided),
+            WorkConfig::new()
+                .threads(3)
+                .partition(Partition::Shuffled { seed: 5 }),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..5 {
+                let mut input: Vec<u64> = (0..seed * 100 + 1).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                let expected: Vec<(usize, u64)> = input.iter().copied().enumerate().collect();
+                assert_eq!(config.divide_work_enumerate(input.clone(), f), expected);
+                assert_eq!(config.divide_equal_work_enumerate(input, f), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_in_order() {
+        for config in [
+            WorkConfig::new().threads(4).threshold(1),
+            WorkConfig::new().threads(3).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for (len, size) in [(0, 3), (5, 100), (10, 10), (100, 7), (1000, 1), (1001, 50)] {
+                let input: Vec<u64> = (0..len).collect();
+                // Output of a chunk is longer than the chunk itself
+                let expected: Vec<u64> = input
+                    .chunks(size)
+                    .flat_map(|chunk| iter::once(chunk.len() as u64).chain(chunk.iter().copied()))
+                    .collect();
+                let output = config.divide_chunks(input.clone(), size, |chunk| {
+                    iter::once(chunk.len() as u64)
+                        .chain(chunk.iter().copied())
+                        .collect()
+                });
+                assert_eq!(output, expected);
+                let output = config.divide_chunks_owned(input, size, |chunk| {
+                    iter::once(chunk.len() as u64).chain(chunk).collect()
+                });
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_skip_panics() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let config = config.panic_policy(PanicPolicy::SkipItem);
+            // Chunk that contains the bad element is left out as a whole, the rest keep their order
+            let output = config.divide_chunks((0..1000).collect(), 10, |chunk: &[u32]| {
+                assert!(!chunk.contains(&555), "bad chunk");
+                chunk.to_vec()
+            });
+            let expected: Vec<u32> = (0..1000).filter(|x| !(550..560).contains(x)).collect();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk size must be positive")]
+    fn zero_chunk_size() {
+        WorkConfig::new().divide_chunks(vec![1, 2, 3], 0, |chunk: &[i32]| chunk.to_vec());
+    }
+
+    #[test]
+    fn windows_match_sequential() {
+        let sum = |part: &[u64]| part.iter().sum::<u64>();
+        for config in [
+            WorkConfig::new().threads(2).threshold(1),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::new().threads(8).threshold(1),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..10 {
+                let mut data: Vec<u64> = (0..seed * 77 + 1).collect();
+                crate::shuffle::shuffle(&mut data, seed);
+                for window in [1, 2, 5, 64, data.len(), data.len() + 1] {
+                    let expected: Vec<u64> = data.windows(window).map(sum).collect();
+                    assert_eq!(config.divide_windows(&data, window, sum), expected);
+                }
+                let output = config.divide_windows(&data, 1, |part| part[0] * 2);
+                assert_eq!(output, data.iter().map(|x| x * 2).collect::<Vec<_>>());
+                assert_eq!(config.divide_windows(&data, data.len(), sum), [sum(&data)]);
+            }
+            assert!(config.divide_windows(&[] as &[u64], 3, sum).is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "window size must be positive")]
+    fn zero_window() {
+        WorkConfig::new().divide_windows(&[1, 2, 3], 0, |part: &[i32]| part.len());
+    }
+
+    #[test]
+    fn map_while_matches_sequential() {
+        let evaluated = Arc::new(atomic::AtomicUsize::new(0));
+        let cutoff = |cutoff: u32| {
+            let evaluated = evaluated.clone();
+            move |x: u32| {
+                evaluated.fetch_add(1, atomic::Ordering::Relaxed);
+                (x < cutoff).then(|| x * 2)
+            }
+        };
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let input: Vec<u32> = (0..1000).collect();
+            // Break at the start, in the middle, at the end, and none at all
+            for at in [0, 1, 333, 500, 999, 1000] {
+                let expected: Vec<u32> = input
+                    .iter()
+                    .map_while(|&x| (x < at).then(|| x * 2))
+                    .collect();
+                assert_eq!(config.divide_map_while(input.clone(), cutoff(at)), expected);
+                assert!(evaluated.swap(0, atomic::Ordering::Relaxed) <= 1000);
+            }
+            // Order of the prefix is kept even if elements past the break are not ordered
+            for seed in 0..5 {
+                let mut input: Vec<u32> = (0..1000).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                let expected: Vec<u32> = input
+                    .iter()
+                    .map_while(|&x| (x < 900).then(|| x * 2))
+                    .collect();
+                assert_eq!(config.divide_map_while(input, cutoff(900)), expected);
+            }
+            assert!(config.divide_map_while(Vec::new(), cutoff(10)).is_empty());
+        }
+
+        // Elements past the break in the first chunk are abandoned by the workers of later chunks
+        let config = WorkConfig::new().threads(4);
+        let counter = evaluated.clone();
+        let slow = move |x: u32| {
+            counter.fetch_add(1, atomic::Ordering::Relaxed);
+            if x == 0 {
+                return None;
+            }
+            std::thread::sleep(Duration::from_micros(100));
+            Some(x)
+        };
+        assert!(config
+            .divide_map_while((0..1000).collect(), slow)
+            .is_empty());
+    }
+
+    #[test]
+    fn map_while_skips_panics() {
+        let config = WorkConfig::new()
+            .threads(4)
+            .panic_policy(PanicPolicy::SkipItem);
+        let output = config.divide_map_while((0..1000).collect(), |x: u32| {
+            assert_ne!(x, 10, "bad item");
+            (x < 500).then_some(x)
+        });
+        assert_eq!(output, (0..500).filter(|&x| x != 10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn try_fold_matches_sequential() {
+        let fold = |mut acc: Vec<u32>, x: u32| {
+            if x == u32::MAX {
+                return ControlFlow::Break(x);
+            }
+            acc.push(x);
+            ControlFlow::Continue(acc)
+        };
+        let combine = |mut a: Vec<u32>, b: Vec<u32>| {
+            a.extend(b);
+            a
+        };
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..5 {
+                let mut input: Vec<u32> = (0..seed as u32 * 300).collect();
+                crate::shuffle::shuffle(&mut input, seed);
+                let expected = input.iter().copied().try_fold(Vec::new(), fold);
+                assert_eq!(
+                    config.divide_try_fold(input, Vec::new, fold, combine),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn try_fold_breaks_early() {
+        let evaluated = Arc::new(atomic::AtomicUsize::new(0));
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let counter = evaluated.clone();
+            // Sum of parsed records that stops on the first malformed one
+            let parse = move |sum: u64, record: String| {
+                counter.fetch_add(1, atomic::Ordering::Relaxed);
+                std::thread::sleep(Duration::from_micros(20));
+                match record.parse::<u64>() {
+                    Ok(val) => ControlFlow::Continue(sum + val),
+                    Err(_) => ControlFlow::Break(record),
+                }
+            };
+            let mut records: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+            records[10] = "bad".to_string();
+            let output = config.divide_try_fold(records, || 0, parse, |a, b| a + b);
+            assert_eq!(output, ControlFlow::Break("bad".to_string()));
+            assert!(evaluated.swap(0, atomic::Ordering::Relaxed) < 1000);
+        }
+    }
+}
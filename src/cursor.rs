@@ -0,0 +1,177 @@
+use crate::output::{Output, Sink};
+use crate::worker::{self, CancelOnPanic, Spawner, StopFlag};
+use crate::{SpawnError, WorkConfig};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Claims elements of `input[..end]` with `cursor` and processes them until all of them are claimed or work is
+/// cancelled. Results are written to `output` at indices of their elements. If `cancel_on_panic` is set, work is
+/// cancelled when `f` panics on any of the workers.
+fn process_shared<F, T, R>(
+    input: &[T],
+    end: usize,
+    cursor: &AtomicUsize,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    f: &F,
+    output: &Sink<R>,
+) where
+    F: Fn(&T) -> R,
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    let mut writer = output.writer();
+    while !stop.is_stopped() {
+        // Each index is handed out exactly once. Every worker increments the cursor past `end` at most once before
+        // stopping, so it can't overflow.
+        let idx = cursor.fetch_add(1, Ordering::Relaxed);
+        if idx >= end {
+            break;
+        }
+        let r = f(&input[idx]);
+        // SAFETY: Cursor hands out each index exactly once
+        unsafe { writer.write(idx, r) };
+    }
+}
+
+/// Parallel part of [divide_work_shared](crate::divide_work_shared) and [divide_work_ref](crate::divide_work_ref).
+/// Processes first `end` elements of `input`, which is either shared or borrowed, and are expected to be enough to be
+/// split.
+pub(crate) fn divide_work_shared<'env, S, D, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: D,
+    end: usize,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    D: Deref<Target = [T]> + Clone + Send + Sync + 'env,
+    T: Sync,
+    R: Send + 'env,
+    F: Fn(&T) -> R + Send + Sync + 'env,
+{
+    let threads = config.workers_for(end);
+    let cursor = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(StopFlag::new());
+    let mut output = Output::new(end);
+
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let sink = output.sink();
+        let cancel_on_panic = config.cancels_on_panic();
+        move || process_shared(&input, end, &cursor, &stop, cancel_on_panic, &*f, &sink)
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    Ok(output.finish(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::FailingSpawner;
+    use crate::{PanicPolicy, SpawnFailurePolicy};
+
+    #[test]
+    fn ordered_output() {
+        let output = WorkConfig::new()
+            .threads(4)
+            .divide_work_shared((0..1000).collect::<Vec<_>>(), |x| x * 2);
+        assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn input_is_kept_by_caller() {
+        let input: Arc<[String]> = (0..100).map(|x| x.to_string()).collect();
+        let lengths = WorkConfig::new()
+            .threads(4)
+            .divide_work_shared(input.clone(), |s| s.len());
+        assert_eq!(lengths.len(), input.len());
+        assert!(input.iter().zip(lengths).all(|(s, len)| s.len() == len));
+    }
+
+    #[test]
+    fn adaptive_sample_is_in_place() {
+        let output = WorkConfig::adaptive()
+            .threads(4)
+            .divide_work_shared((0..100).collect::<Vec<_>>(), |x| x + 1);
+        assert_eq!(output, (1..101).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_any_worker() {
+        WorkConfig::new()
+            .threads(4)
+            .divide_work_shared((0..100).collect::<Vec<_>>(), |&x| {
+                assert_ne!(x, 50, "bad item");
+                x
+            });
+    }
+
+    #[test]
+    fn skipped_panics() {
+        let output = WorkConfig::new()
+            .threads(4)
+            .panic_policy(PanicPolicy::SkipItem)
+            .divide_work_shared((0..100).collect::<Vec<_>>(), |&x| {
+                assert_ne!(x % 10, 0, "bad item");
+                x
+            });
+        assert_eq!(output, (0..100).filter(|x| x % 10 != 0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn spawn_failure() {
+        let input: Arc<[usize]> = (0..1000).collect();
+        let config = WorkConfig::new().threads(4);
+        let res = divide_work_shared(
+            &config,
+            &FailingSpawner::new(1),
+            input.clone(),
+            1000,
+            |&x| x,
+        );
+        assert!(res.is_err());
+
+        let config = config.on_spawn_failure(SpawnFailurePolicy::Degrade);
+        let output = divide_work_shared(&config, &FailingSpawner::new(1), input, 999, |&x| x);
+        assert_eq!(output.unwrap(), (0..999).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn borrowed_input() {
+        let input: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+        let suffix = String::from("!");
+        let expected: Vec<String> = input.iter().map(|s| format!("{}{}", s, suffix)).collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).threshold(usize::MAX),
+            WorkConfig::adaptive().threads(4),
+        ] {
+            // `f` borrows a local, which wouldn't be allowed with `'static` bounds
+            let output = config.divide_work_ref(&input, |s| format!("{}{}", s, suffix));
+            assert_eq!(output, expected);
+            let output = config.divide_equal_work_ref(&input, |s| format!("{}{}", s, suffix));
+            assert_eq!(output, expected);
+        }
+        // Input is untouched and still usable
+        assert_eq!(input.len(), 1000);
+        assert!(input.iter().enumerate().all(|(x, s)| *s == x.to_string()));
+    }
+
+    #[test]
+    fn borrowed_skipped_panics() {
+        let input: Vec<u32> = (0..100).collect();
+        let output = WorkConfig::new()
+            .threads(4)
+            .panic_policy(PanicPolicy::SkipItem)
+            .divide_equal_work_ref(&input, |&x| {
+                assert_ne!(x % 10, 0, "bad item");
+                x
+            });
+        assert_eq!(output, (0..100).filter(|x| x % 10 != 0).collect::<Vec<_>>());
+    }
+}
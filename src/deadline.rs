@@ -0,0 +1,119 @@
+use crate::ItemTimedOut;
+use std::time::{Duration, Instant};
+
+/// Context of an element being processed by [divide_work_with_deadline](crate::divide_work_with_deadline). Threads
+/// can't be interrupted, so long running `f` is expected to check the deadline periodically and give up early.
+#[derive(Debug, Clone)]
+pub struct ItemCtx {
+    start: Instant,
+    limit: Duration,
+}
+
+impl ItemCtx {
+    fn new(limit: Duration) -> Self {
+        ItemCtx {
+            start: Instant::now(),
+            limit,
+        }
+    }
+
+    /// Time when processing of the element started
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Time by which the element is expected to be processed
+    pub fn deadline(&self) -> Instant {
+        self.start + self.limit
+    }
+
+    /// Time spent processing the element so far
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time left until the deadline, zero if it has already passed
+    pub fn remaining(&self) -> Duration {
+        self.limit.saturating_sub(self.elapsed())
+    }
+
+    /// Whether the deadline has passed. Result of the element is discarded in this case, so `f` may stop right away.
+    pub fn is_expired(&self) -> bool {
+        self.elapsed() > self.limit
+    }
+}
+
+/// Wraps `f`, so that elements that take longer than `limit` are reported as timed out
+pub(crate) fn with_deadline<F, T, R>(
+    limit: Duration,
+    f: F,
+) -> impl Fn(T) -> Result<R, ItemTimedOut> + Send + Sync + 'static
+where
+    F: Fn(T, &ItemCtx) -> R + Send + Sync + 'static,
+{
+    move |val| {
+        let ctx = ItemCtx::new(limit);
+        let res = f(val, &ctx);
+        let elapsed = ctx.elapsed();
+        if elapsed > limit {
+            Err(ItemTimedOut { elapsed, limit })
+        } else {
+            Ok(res)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorkConfig;
+
+    /// Sleeps for `x` milliseconds unless the deadline passes first
+    fn cooperative(x: u64, ctx: &ItemCtx) -> u64 {
+        let until = ctx.start() + Duration::from_millis(x);
+        while Instant::now() < until && !ctx.is_expired() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        x
+    }
+
+    #[test]
+    fn slow_elements_time_out() {
+        let limit = Duration::from_millis(100);
+        let input: Vec<u64> = (0..8).map(|x| if x % 4 == 3 { 300 } else { x }).collect();
+        let config = WorkConfig::new().threads(4);
+        let outputs = vec![
+            config.divide_work_with_deadline(input.clone(), limit, cooperative),
+            config.divide_equal_work_with_deadline(input.clone(), limit, cooperative),
+        ];
+
+        for output in outputs {
+            assert_eq!(output.len(), 8);
+            for (x, res) in input.iter().zip(output) {
+                match res {
+                    Ok(r) => assert_eq!(r, *x),
+                    Err(err) => {
+                        assert_eq!(*x, 300);
+                        assert!(err.elapsed > limit);
+                        // Cooperative closure gives up soon after the deadline
+                        assert!(err.elapsed < Duration::from_millis(300));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overruns_are_reported_for_uncooperative_closures() {
+        let output = crate::divide_work_with_deadline(
+            vec![0, 50],
+            Duration::from_millis(20),
+            |x: u64, _: &ItemCtx| {
+                std::thread::sleep(Duration::from_millis(x));
+                x
+            },
+        );
+        assert_eq!(output[0], Ok(0));
+        assert!(output[1].is_err());
+    }
+}
@@ -0,0 +1,1024 @@
+use crate::cancel::Until;
+use crate::output::{concat, Output};
+use crate::queue::{Batching, Queue};
+use crate::worker::{self, CancelOnPanic, FirstError, Spawner, StopFlag};
+use crate::{SpawnError, WorkConfig};
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pops batches of elements from `queue` and processes them until it is empty or work is cancelled. Results are passed
+/// to `emit` along with their indices in the original input, and `batch_done` is called with the amount of elements
+/// after each batch. If `cancel_on_panic` is set, work is cancelled when `f` panics on any of the workers. Batches are
+/// not picked up once `until` is reached, the ones that are already being processed are finished.
+#[allow(clippy::too_many_arguments)]
+fn process_queue<F, T, R, W, B>(
+    queue: &Queue<T>,
+    batching: Batching,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    until: &Until,
+    mut f: F,
+    mut emit: W,
+    mut batch_done: B,
+) where
+    F: FnMut(usize, T) -> R,
+    W: FnMut(usize, R),
+    B: FnMut(usize),
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    while !stop.is_stopped() {
+        if until.reached() {
+            break;
+        }
+        let batch = match queue.pop(batching) {
+            Some(batch) => batch,
+            None => break,
+        };
+        let mut processed = 0;
+        for (idx, val) in batch {
+            if stop.is_stopped() {
+                break;
+            }
+            emit(idx, f(idx, val));
+            processed += 1;
+        }
+        batch_done(processed);
+    }
+}
+
+/// Rough cost of starting a worker, which remaining work must outweigh for
+/// [adaptive_spawn](WorkConfig::adaptive_spawn) to start one more
+const SPAWN_COST: Duration = Duration::from_micros(50);
+
+/// Decides when the calling thread starts another worker with [adaptive_spawn](WorkConfig::adaptive_spawn). After
+/// each batch of the calling thread, remaining elements of the queue are estimated to take as long as that batch did
+/// per element, and another worker is started if the running ones would take longer than [SPAWN_COST] to finish them.
+struct Ramp<'a> {
+    start_next: &'a mut dyn FnMut() -> bool,
+    /// Threads that process the queue, including the calling thread
+    running: usize,
+    max: usize,
+    batch_start: Instant,
+}
+
+impl Ramp<'_> {
+    fn batch_done<T>(&mut self, processed: usize, queue: &Queue<T>) {
+        let now = Instant::now();
+        let elapsed = now - std::mem::replace(&mut self.batch_start, now);
+        if processed == 0 || self.running >= self.max {
+            return;
+        }
+        let remaining = elapsed.as_secs_f64() / processed as f64 * queue.len() as f64;
+        if remaining / self.running as f64 > SPAWN_COST.as_secs_f64() {
+            if (self.start_next)() {
+                self.running += 1;
+            } else {
+                self.max = self.running;
+            }
+        }
+        // Starting a worker takes a while, which shouldn't count towards the next batch
+        self.batch_start = Instant::now();
+    }
+}
+
+/// Same as [process_queue], but also cancels work as soon as any worker reports an error
+fn try_process_queue<F, T, R, E, W>(
+    queue: &Queue<T>,
+    batching: Batching,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    error: &FirstError<E>,
+    f: &F,
+    mut emit: W,
+) where
+    F: Fn(T) -> Result<R, E>,
+    W: FnMut(usize, R),
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    while !stop.is_stopped() {
+        let batch = match queue.pop(batching) {
+            Some(batch) => batch,
+            None => break,
+        };
+        for (idx, val) in batch {
+            if stop.is_stopped() {
+                break;
+            }
+            match f(val) {
+                Ok(r) => emit(idx, r),
+                Err(err) => {
+                    error.set(err);
+                    stop.stop();
+                }
+            }
+        }
+    }
+}
+
+/// Parallel part of [divide_work](crate::divide_work). `input` is expected to be large enough to be split.
+pub(crate) fn divide_work<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let res = divide_work_init(
+        config,
+        spawner,
+        input,
+        || (),
+        move |_, _, val| f(val),
+        |_| (),
+    );
+    res.map(|(output, _)| output)
+}
+
+/// Parallel part of [divide_work_init](crate::divide_work_init) and
+/// [divide_work_with_state](crate::divide_work_with_state). Every worker creates its state with `init` before taking
+/// elements from the queue, and hands it to `finish` once the queue is empty. `f` also gets the index of every element
+/// in `input`. Returns results of `finish` of every worker along with the output. `input` is expected to be large
+/// enough to be split.
+pub(crate) fn divide_work_init<'env, S, I, F, G, T, R, St, A>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    init: I,
+    f: F,
+    finish: G,
+) -> Result<(Vec<R>, Vec<A>), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    A: Send + 'env,
+    I: Fn() -> St + Send + Sync + 'env,
+    F: Fn(&mut St, usize, T) -> R + Send + Sync + 'env,
+    G: Fn(St) -> A + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    let batching = Batching::new(config, threads);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+    let mut output = Output::new(length);
+
+    let shared = Arc::new((init, f, finish));
+    let work = {
+        let stop = stop.clone();
+        let sink = output.sink();
+        let cancel_on_panic = config.cancels_on_panic();
+        move |batch_done: &mut dyn FnMut(usize, &Queue<T>)| {
+            let (init, f, finish) = &*shared;
+            let mut state = init();
+            let mut writer = sink.writer();
+            let until = Until::default();
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |idx, val| f(&mut state, idx, val),
+                |idx, r| {
+                    // SAFETY: Every element is popped from the queue exactly once
+                    unsafe { writer.write(idx, r) }
+                },
+                |processed| batch_done(processed, &queue),
+            );
+            finish(state)
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| {
+        let work = work.clone();
+        (index, move || work(&mut |_, _| {}))
+    });
+    let results = if config.adaptive_spawn {
+        worker::run_ramped(config, spawner, jobs, |start_next| {
+            let mut ramp = Ramp {
+                start_next,
+                running: 1,
+                max: threads,
+                batch_start: Instant::now(),
+            };
+            work(&mut |processed, queue| ramp.batch_done(processed, queue))
+        })
+    } else {
+        worker::run(config, spawner, &stop, jobs, || work(&mut |_, _| {}))?
+    };
+    let mut states = Vec::with_capacity(results.len());
+    let results = results
+        .into_iter()
+        .map(|res| res.map(|state| states.push(state)))
+        .collect();
+    Ok((output.finish(results), states))
+}
+
+/// Parallel part of [divide_unzip](crate::divide_unzip). Each worker writes the components of its results straight
+/// into two outputs at the indices of their elements. `input` is expected to be large enough to be split.
+pub(crate) fn divide_unzip<'env, S, F, T, A, B>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<(Vec<A>, Vec<B>), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    A: Send + 'env,
+    B: Send + 'env,
+    F: Fn(T) -> (A, B) + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    let batching = Batching::new(config, threads);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+    let mut left = Output::new(length);
+    let mut right = Output::new(length);
+
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let sinks = (left.sink(), right.sink());
+        let cancel_on_panic = config.cancels_on_panic();
+        move || {
+            let (mut left, mut right) = (sinks.0.writer(), sinks.1.writer());
+            let until = Until::default();
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |_, val| f(val),
+                |idx, (a, b)| {
+                    // SAFETY: Every element is popped from the queue exactly once
+                    unsafe {
+                        left.write(idx, a);
+                        right.write(idx, b);
+                    }
+                },
+                |_| {},
+            );
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    // Panics are propagated by the first output, which drops the results of both on the way
+    let left = left.finish(results);
+    Ok((left, right.finish(Vec::new())))
+}
+
+/// Parallel part of [divide_work_by_cost](crate::divide_work_by_cost). `input` is expected to be large enough to be
+/// split.
+pub(crate) fn divide_work_by_cost<'env, S, C, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    cost: C,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    C: Fn(&T) -> u64,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    // Elements are popped from the back, so the most expensive ones go last. Original indices travel along with the
+    // elements, since their positions in the queue no longer match.
+    let mut input: Vec<(usize, T)> = input.into_iter().enumerate().collect();
+    input.sort_by_cached_key(|(_, val)| cost(val));
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+    let mut output = Output::new(length);
+
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let sink = output.sink();
+        let cancel_on_panic = config.cancels_on_panic();
+        move || {
+            let mut writer = sink.writer();
+            let until = Until::default();
+            // Batches would hand the most expensive elements to the same worker
+            let batching = Batching::single();
+            let f = |(idx, val)| (idx, f(val));
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |_, val| f(val),
+                |_, (idx, r)| {
+                    // SAFETY: Every element is popped from the queue exactly once, and carries its unique index
+                    unsafe { writer.write(idx, r) }
+                },
+                |_| {},
+            )
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    Ok(output.finish(results))
+}
+
+/// Parallel part of [divide_work_unordered](crate::divide_work_unordered). `input` is expected to be large enough to be
+/// split.
+pub(crate) fn divide_work_unordered<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let threads = config.workers_for(input.len());
+    let batching = Batching::new(config, threads);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+
+    // Each worker collects its own results, which are concatenated as is, so there is no output buffer to share
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let cancel_on_panic = config.cancels_on_panic();
+        move || {
+            let mut res = Vec::new();
+            let until = Until::default();
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |_, val| f(val),
+                |_, r| res.push(r),
+                |_| {},
+            );
+            res
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    let results = results
+        .into_iter()
+        .collect::<thread::Result<Vec<_>>>()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_flat_map](crate::divide_flat_map) and its filtering variants. Workers keep results of
+/// runs of consecutive elements along with the index of the first element of each run, and the runs of all workers are
+/// merged by their indices once the queue is empty. `input` is expected to be large enough to be split.
+pub(crate) fn divide_flat_map<'env, S, F, T, I>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<I::Item>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    I: IntoIterator,
+    I::Item: Send + 'env,
+    F: Fn(T) -> I + Send + Sync + 'env,
+{
+    let threads = config.workers_for(input.len());
+    let batching = Batching::new(config, threads);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let cancel_on_panic = config.cancels_on_panic();
+        move || {
+            // Index of the first element of a run, index past its last element, and results of the run
+            let mut runs: Vec<(usize, usize, Vec<I::Item>)> = Vec::new();
+            let until = Until::default();
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |_, val| f(val),
+                |idx, r| match runs.last_mut() {
+                    Some((_, end, results)) if *end == idx => {
+                        *end += 1;
+                        results.extend(r);
+                    }
+                    _ => runs.push((idx, idx + 1, r.into_iter().collect())),
+                },
+                |_| {},
+            );
+            runs
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    let mut runs: Vec<_> = results
+        .into_iter()
+        .collect::<thread::Result<Vec<_>>>()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+        .into_iter()
+        .flatten()
+        .collect();
+    // Runs don't overlap, so their first indices alone put them in input order
+    runs.sort_unstable_by_key(|&(start, _, _)| start);
+    Ok(concat(
+        runs.into_iter().map(|(_, _, results)| results).collect(),
+    ))
+}
+
+/// Parallel part of [divide_work_timeout](crate::divide_work_timeout) and
+/// [divide_work_cancellable](crate::divide_work_cancellable). `input` is expected to be large enough to be split.
+/// Returns results at their indices in the original input, with `None` for elements that weren't processed before
+/// `until` was reached, and unprocessed elements themselves in input order.
+pub(crate) fn divide_work_until<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    until: Until,
+    f: F,
+) -> Result<(Vec<Option<R>>, Vec<T>), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+
+    let f = Arc::new(f);
+    let work = {
+        let queue = queue.clone();
+        let stop = stop.clone();
+        let cancel_on_panic = config.cancels_on_panic();
+        // Elements that weren't processed by the time `until` is reached must be left in the queue
+        move || {
+            let mut res = Vec::new();
+            let batching = Batching::single();
+            process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &until,
+                |_, val| f(val),
+                |idx, r| res.push((idx, r)),
+                |_| {},
+            );
+            res
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    let results = results
+        .into_iter()
+        .collect::<thread::Result<Vec<_>>>()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+
+    let mut completed: Vec<Option<R>> = (0..length).map(|_| None).collect();
+    for (idx, r) in results.into_iter().flatten() {
+        completed[idx] = Some(r);
+    }
+    // Elements are popped from the back, so the ones that are left are at the start of the input
+    let remaining = queue.take_remaining();
+    Ok((completed, remaining))
+}
+
+/// Parallel part of [try_divide_work](crate::try_divide_work). `input` is expected to be large enough to be split.
+pub(crate) fn try_divide_work<'env, S, F, T, R, E>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Result<Vec<R>, E>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    E: Send + 'env,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    let batching = Batching::new(config, threads);
+    let queue = Arc::new(Queue::new(input));
+    let stop = Arc::new(StopFlag::new());
+    let error = Arc::new(FirstError::new());
+    let mut output = Output::new(length);
+
+    let f = Arc::new(f);
+    let work = {
+        let stop = stop.clone();
+        let error = error.clone();
+        let sink = output.sink();
+        let cancel_on_panic = config.cancels_on_panic();
+        move || {
+            let mut writer = sink.writer();
+            try_process_queue(
+                &queue,
+                batching,
+                &stop,
+                cancel_on_panic,
+                &error,
+                &*f,
+                |idx, r| {
+                    // SAFETY: Every element is popped from the queue exactly once
+                    unsafe { writer.write(idx, r) }
+                },
+            )
+        }
+    };
+    let jobs = (0..threads - 1).map(|index| (index, work.clone()));
+    let results = worker::run(config, spawner, &stop, jobs, work.clone())?;
+    // Panics take precedence over errors, since they are not expected to happen
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .map(Ok)
+        .collect();
+    Ok(match error.take() {
+        Some(err) => Err(err),
+        None => Ok(output.finish(results)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::{FailingSpawner, OsSpawner};
+    use crate::{PanicPolicy, Schedule, SpawnError, SpawnFailurePolicy};
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn many_threads_few_elements() {
+        let input: Vec<usize> = (0..11).collect();
+        let output = WorkConfig::new()
+            .threads(64)
+            .divide_work(input, |x| (x, std::thread::current().id()));
+
+        assert_eq!(
+            output.iter().map(|(x, _)| *x).collect::<Vec<_>>(),
+            (0..11).collect::<Vec<_>>()
+        );
+
+        let threads: HashSet<_> = output.into_iter().map(|(_, id)| id).collect();
+        assert!(threads.len() <= 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_any_worker() {
+        WorkConfig::new()
+            .threads(2)
+            .divide_work((0..20).collect(), |x| {
+                assert_ne!(x, 10, "bad item");
+                x
+            });
+    }
+
+    #[test]
+    fn try_stops_on_error() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        // Batches are popped from the back of the queue, so the last element is processed with one of the first batches
+        let output = WorkConfig::new()
+            .threads(2)
+            .try_divide_work((0..1000).collect(), move |x| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                if x == 999 {
+                    Err(x)
+                } else {
+                    Ok(x)
+                }
+            });
+
+        assert!(matches!(output, Err(crate::WorkError::ItemError(999))));
+        assert!(processed.load(Ordering::Relaxed) < 500);
+    }
+
+    #[test]
+    fn panic_cancels_work() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        let res = std::panic::catch_unwind(|| {
+            // The last element is processed with one of the first batches
+            WorkConfig::new()
+                .threads(4)
+                .divide_work((0..10_000).collect(), move |x| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    assert_ne!(x, 9_999, "bad item");
+                    std::thread::sleep(std::time::Duration::from_micros(100));
+                    x
+                })
+        });
+
+        assert!(res.is_err());
+        assert!(processed.load(Ordering::Relaxed) < 5_000);
+    }
+
+    #[test]
+    fn spawn_failure_joins_spawned_workers() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        let config = WorkConfig::new().threads(4);
+        let res = divide_work(
+            &config,
+            &FailingSpawner::new(2),
+            (0..1000).collect(),
+            move |x| {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                counter.fetch_add(1, Ordering::Relaxed);
+                x
+            },
+        );
+
+        let err = res.unwrap_err();
+        assert_eq!(err.io_error().kind(), std::io::ErrorKind::WouldBlock);
+        // Spawned workers were stopped and joined before returning, so no elements are processed after that
+        let processed_on_return = processed.load(Ordering::Relaxed);
+        assert!(processed_on_return < 1000);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(processed.load(Ordering::Relaxed), processed_on_return);
+    }
+
+    static SWALLOWED: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_swallowed(_: &SpawnError) {
+        SWALLOWED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn spawn_failure_degrades() {
+        let config = WorkConfig::new()
+            .threads(8)
+            .on_spawn_failure(SpawnFailurePolicy::Degrade)
+            .spawn_failure_hook(count_swallowed);
+        for successes in 1..7 {
+            let output = divide_work(
+                &config,
+                &FailingSpawner::new(successes),
+                (0..1000).collect(),
+                |x| x * 2,
+            );
+            assert_eq!(
+                output.unwrap(),
+                (0..1000).map(|x| x * 2).collect::<Vec<_>>()
+            );
+        }
+        // Spawning is not attempted again after the first failure
+        assert_eq!(SWALLOWED.load(Ordering::Relaxed), 6);
+
+        let res = divide_work(&config, &FailingSpawner::new(0), (0..1000).collect(), |x| {
+            x * 2
+        });
+        assert!(res.is_err());
+        assert_eq!(SWALLOWED.load(Ordering::Relaxed), 6);
+    }
+
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct DropCounter(#[allow(dead_code)] Vec<u8>);
+
+    impl DropCounter {
+        fn new() -> Self {
+            CREATED.fetch_add(1, Ordering::Relaxed);
+            DropCounter(vec![0; 64])
+        }
+    }
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn panic_drops_written_results() {
+        for policy in [PanicPolicy::Propagate, PanicPolicy::CancelAll] {
+            let res = std::panic::catch_unwind(|| {
+                WorkConfig::new()
+                    .threads(4)
+                    .panic_policy(policy)
+                    .divide_work((0..1000).collect(), |x| {
+                        // Panics late, so that other workers have plenty of results by then
+                        assert_ne!(x, 10, "bad item");
+                        DropCounter::new()
+                    })
+            });
+            assert!(res.is_err());
+            assert!(CREATED.load(Ordering::Relaxed) > 0);
+            assert_eq!(
+                CREATED.load(Ordering::Relaxed),
+                DROPPED.load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    #[test]
+    fn panics_dont_poison_queue() {
+        for _ in 0..20 {
+            let res = std::panic::catch_unwind(|| {
+                WorkConfig::new()
+                    .threads(8)
+                    .panic_policy(PanicPolicy::Propagate)
+                    .divide_work((0..200).collect(), |x| {
+                        assert_ne!(x % 3, 0, "bad item");
+                        x
+                    })
+            });
+            let payload = res.unwrap_err();
+            let msg = payload.downcast_ref::<String>().unwrap();
+            assert!(msg.contains("bad item"), "{}", msg);
+        }
+    }
+
+    #[test]
+    fn unordered_output() {
+        for len in [2, 3, 100, 1001] {
+            let config = WorkConfig::new().threads(4);
+            let mut output =
+                divide_work_unordered(&config, &OsSpawner, (0..len).collect(), |x| x * 2).unwrap();
+            output.sort_unstable();
+            let mut expected: Vec<_> = (0..len).map(|x| x * 2).collect();
+            expected.sort_unstable();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn unordered_panic() {
+        let config = WorkConfig::new().threads(4);
+        let _ = divide_work_unordered(&config, &OsSpawner, (0..100).collect(), |x| {
+            assert_ne!(x, 50, "bad item");
+            x
+        });
+    }
+
+    #[test]
+    fn expensive_elements_first() {
+        let output = WorkConfig::new().threads(2).divide_work_by_cost(
+            (0..100).collect(),
+            |&x| x,
+            |x| (x, std::time::Instant::now()),
+        );
+        assert_eq!(
+            output.iter().map(|(x, _)| *x).collect::<Vec<_>>(),
+            (0..100).collect::<Vec<_>>()
+        );
+        // Each of the two workers starts with one of the most expensive elements
+        let first = output.iter().min_by_key(|(_, at)| *at).unwrap().0;
+        assert!(first >= 98, "{}", first);
+    }
+
+    #[test]
+    fn schedules_keep_order() {
+        for schedule in [
+            Schedule::Static,
+            Schedule::Guided { min_chunk: 0 },
+            Schedule::Guided { min_chunk: 16 },
+        ] {
+            let config = WorkConfig::new().threads(4).schedule(schedule);
+            let output = config.divide_work((0..1000).collect(), |x| x * 2);
+            assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+            let output = config.try_divide_work((0..1000).collect(), |x| Ok::<_, ()>(x * 2));
+            assert_eq!(
+                output.unwrap(),
+                (0..1000).map(|x| x * 2).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn batches_keep_order() {
+        for batch_size in [1, 7, 64, 5000] {
+            let config = WorkConfig::new().threads(4).batch_size(batch_size);
+            let output = config.divide_work((0..1000).collect(), |x| x * 2);
+            assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+            let output = config.try_divide_work((0..1000).collect(), |x| Ok::<_, ()>(x * 2));
+            assert_eq!(
+                output.unwrap(),
+                (0..1000).map(|x| x * 2).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn timeout_leaves_batches_in_queue() {
+        let outcome = WorkConfig::new()
+            .threads(2)
+            .batch_size(500)
+            .divide_work_timeout(
+                (0..1000).collect(),
+                std::time::Duration::from_millis(20),
+                |x| {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    x
+                },
+            );
+        match outcome {
+            crate::TimeoutOutcome::TimedOut {
+                completed,
+                remaining,
+            } => {
+                let done = completed.iter().filter(|r| r.is_some()).count();
+                assert_eq!(done + remaining.len(), 1000);
+            }
+            crate::TimeoutOutcome::Completed(_) => panic!("work was expected to time out"),
+        }
+    }
+
+    #[test]
+    fn scoped_borrows() {
+        let buffer: String = (0..1000).map(|x| format!("{} ", x)).collect();
+        let local_string = String::from("#");
+        let expected: Vec<String> = (0..1000).map(|x| format!("#{}", x)).collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).threshold(usize::MAX),
+        ] {
+            // Elements borrow `buffer`, and `f` borrows `local_string`
+            let words: Vec<&str> = buffer.split_whitespace().collect();
+            let output =
+                config.divide_work_scoped(words, |word| format!("{}{}", &local_string, word));
+            assert_eq!(output, expected);
+            let words: Vec<&str> = buffer.split_whitespace().collect();
+            let output =
+                config.divide_equal_work_scoped(words, |word| format!("{}{}", &local_string, word));
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn init_once_per_worker() {
+        let config = WorkConfig::new().threads(4);
+        let inits = Arc::new(AtomicUsize::new(0));
+        let init = {
+            let inits = inits.clone();
+            move || {
+                inits.fetch_add(1, Ordering::Relaxed);
+                Vec::<u8>::with_capacity(1024)
+            }
+        };
+        let f = |scratch: &mut Vec<u8>, x: usize| {
+            // Scratch buffer is reused between elements of the worker
+            assert_eq!(scratch.capacity(), 1024);
+            scratch.clear();
+            x * 2
+        };
+        let expected: Vec<usize> = (0..1000).map(|x| x * 2).collect();
+        assert_eq!(
+            config.divide_work_init((0..1000).collect(), init.clone(), f),
+            expected
+        );
+        assert_eq!(inits.swap(0, Ordering::Relaxed), 4);
+        assert_eq!(
+            config.divide_equal_work_init((0..1000).collect(), init.clone(), f),
+            expected
+        );
+        assert_eq!(inits.swap(0, Ordering::Relaxed), 4);
+
+        // Calling thread creates a single state when work isn't split
+        let config = config.threshold(usize::MAX);
+        assert_eq!(
+            config.divide_work_init((0..1000).collect(), init, f),
+            expected
+        );
+        assert_eq!(inits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn workers_started_on_demand() {
+        let config = WorkConfig::new()
+            .threads(4)
+            .threshold(1)
+            .adaptive_spawn(true);
+        let inits = Arc::new(AtomicUsize::new(0));
+        let init = {
+            let inits = inits.clone();
+            move || {
+                inits.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+        let cheap = |_: &mut (), x: usize| x * 2;
+        // First run of freshly loaded code is slow enough to be worth another worker, so the code is warmed up first
+        config.divide_work_init((0..8).collect(), init.clone(), cheap);
+        inits.store(0, Ordering::Relaxed);
+        let output = config.divide_work_init((0..8).collect(), init.clone(), cheap);
+        assert_eq!(output, (0..8).map(|x| x * 2).collect::<Vec<_>>());
+        // Cheap input is finished by the calling thread before any worker is worth starting
+        assert_eq!(inits.swap(0, Ordering::Relaxed), 1);
+
+        let slow = |_: &mut (), x: usize| {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            x * 2
+        };
+        let output = config.divide_work_init((0..100).collect(), init, slow);
+        assert_eq!(output, (0..100).map(|x| x * 2).collect::<Vec<_>>());
+        let inits = inits.load(Ordering::Relaxed);
+        assert!(inits > 1 && inits <= 4, "{} workers", inits);
+    }
+
+    #[test]
+    fn states_cover_every_element() {
+        let count = |count: &mut usize, x: usize| {
+            *count += 1;
+            x + 1
+        };
+        let expected: Vec<usize> = (1..=1000).collect();
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let (output, counts) = config.divide_work_with_state((0..1000).collect(), || 0, count);
+            assert_eq!(output, expected);
+            assert_eq!(counts.iter().sum::<usize>(), 1000);
+        }
+
+        let config = WorkConfig::new().threads(4);
+        let (_, counts) = config.divide_work_with_state((0..1000).collect(), || 0, count);
+        assert_eq!(counts.len(), 4);
+        // Calling thread doesn't keep a state if there is nothing to process
+        let (output, counts) = config.divide_work_with_state(Vec::new(), || 0, count);
+        assert!(output.is_empty() && counts.is_empty());
+    }
+
+    #[test]
+    fn unzip_matches_sequential() {
+        let f = |x: usize| (vec![x; x % 5], format!("#{}", x));
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).batch_size(7),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for len in [0, 1, 2, 1001] {
+                let expected: (Vec<_>, Vec<_>) = config
+                    .divide_work((0..len).collect(), f)
+                    .into_iter()
+                    .unzip();
+                assert_eq!(config.divide_unzip((0..len).collect(), f), expected);
+            }
+        }
+    }
+
+    /// Counts how many values are alive
+    struct Alive(Arc<AtomicUsize>);
+
+    impl Alive {
+        fn new(alive: &Arc<AtomicUsize>) -> Self {
+            alive.fetch_add(1, Ordering::Relaxed);
+            Alive(alive.clone())
+        }
+    }
+
+    impl Drop for Alive {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn unzip_panic_drops_written_results() {
+        let alive = Arc::new(AtomicUsize::new(0));
+        let counter = alive.clone();
+        let res = std::panic::catch_unwind(|| {
+            WorkConfig::new()
+                .threads(4)
+                .divide_unzip((0..1000).collect(), move |x| {
+                    assert_ne!(x, 600, "bad item");
+                    (Alive::new(&counter), Alive::new(&counter))
+                })
+        });
+        assert!(res.is_err());
+        assert_eq!(alive.load(Ordering::Relaxed), 0);
+    }
+}
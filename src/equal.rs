@@ -0,0 +1,1472 @@
+use crate::output::concat;
+use crate::worker::{self, CancelOnPanic, FirstError, Spawner, StopFlag};
+use crate::{shuffle, Either, Partition, SpawnError, WorkConfig};
+use std::mem::ManuallyDrop;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{panic, ptr};
+
+/// Index of the first element of `chunk` when `length` elements are split into `chunks` contiguous chunks. Chunk
+/// sizes differ by at most one, and none of them is empty as long as `chunks <= length`.
+pub(crate) fn chunk_start(length: usize, chunks: usize, chunk: usize) -> usize {
+    // First `length % chunks` chunks get an extra element. Integer math is exact for any length, unlike floats.
+    let (size, extra) = (length / chunks, length % chunks);
+    chunk * size + chunk.min(extra)
+}
+
+/// Allocation of the input vector shared by its chunks. Elements are owned by the chunks, the buffer only frees the
+/// memory once all of them are dropped.
+struct Buffer<T> {
+    ptr: *mut T,
+    capacity: usize,
+}
+
+// SAFETY: The buffer never accesses its elements, they are moved out and dropped by chunks on their own threads
+unsafe impl<T: Send> Send for Buffer<T> {}
+unsafe impl<T: Send> Sync for Buffer<T> {}
+
+impl<T> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `capacity` come from a vector that is no longer used. Length of `0` means that only the
+        //         memory is freed, elements were already moved out or dropped by chunks.
+        unsafe { drop(Vec::from_raw_parts(self.ptr, 0, self.capacity)) }
+    }
+}
+
+/// Elements `next, next + step, ...` of the input up to `end`. Elements are moved out of the buffer as the chunk is
+/// iterated, and the ones that are left are dropped along with the chunk, so splitting the input doesn't allocate or
+/// copy any elements.
+struct Chunk<T> {
+    buffer: Arc<Buffer<T>>,
+    next: usize,
+    end: usize,
+    step: usize,
+}
+
+// SAFETY: Chunks own disjoint ranges of the buffer, so sending one to another thread is the same as sending its
+//         elements
+unsafe impl<T: Send> Send for Chunk<T> {}
+
+impl<T> Iterator for Chunk<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next >= self.end {
+            return None;
+        }
+        // SAFETY: `next` is one of the elements owned by this chunk, and it is moved past the element, so the element
+        //         is read exactly once
+        let val = unsafe { ptr::read(self.buffer.ptr.add(self.next)) };
+        self.next += self.step;
+        Some(val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end.saturating_sub(self.next) + self.step - 1) / self.step;
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for Chunk<T> {}
+
+impl<T> Drop for Chunk<T> {
+    fn drop(&mut self) {
+        // Dropping elements through the iterator reads each of them exactly once
+        self.for_each(drop);
+    }
+}
+
+/// Elements of a [Chunk] along with their positions in the buffer
+struct Positioned<T>(Chunk<T>);
+
+impl<T> Iterator for Positioned<T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        let pos = self.0.next;
+        self.0.next().map(|val| (pos, val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Positioned<T> {}
+
+/// Moves elements of `input` into a buffer that is shared by its chunks
+fn share<T>(input: Vec<T>) -> Arc<Buffer<T>> {
+    let mut input = ManuallyDrop::new(input);
+    Arc::new(Buffer {
+        ptr: input.as_mut_ptr(),
+        capacity: input.capacity(),
+    })
+}
+
+/// Splits `input` into `chunks` chunks in input order according to `partition`. Elements are moved only to shuffle
+/// them with [Partition::Shuffled], in which case original index of every element is returned as well.
+fn split<T>(
+    mut input: Vec<T>,
+    chunks: usize,
+    partition: Partition,
+) -> (Vec<Chunk<T>>, Option<Vec<usize>>) {
+    let length = input.len();
+    let order = match partition {
+        Partition::Shuffled { seed } => Some(shuffle::shuffle(&mut input, seed)),
+        Partition::Contiguous | Partition::Strided => None,
+    };
+    let buffer = share(input);
+    let chunks = (0..chunks)
+        .map(|chunk| match partition {
+            Partition::Contiguous | Partition::Shuffled { .. } => Chunk {
+                buffer: buffer.clone(),
+                next: chunk_start(length, chunks, chunk),
+                end: chunk_start(length, chunks, chunk + 1),
+                step: 1,
+            },
+            Partition::Strided => Chunk {
+                buffer: buffer.clone(),
+                next: chunk,
+                end: length,
+                step: chunks,
+            },
+        })
+        .collect();
+    (chunks, order)
+}
+
+/// Joins complete results of chunks produced by [split] back in input order
+fn assemble<R>(results: Vec<Vec<R>>, partition: Partition, order: Option<Vec<usize>>) -> Vec<R> {
+    match partition {
+        Partition::Contiguous => concat(results),
+        Partition::Shuffled { .. } => {
+            let mut output = concat(results);
+            shuffle::restore(&mut output, order.expect("shuffled input has order"));
+            output
+        }
+        Partition::Strided => {
+            let length = results.iter().map(Vec::len).sum();
+            let chunks = results.len();
+            let mut results: Vec<_> = results.into_iter().map(Vec::into_iter).collect();
+            (0..length)
+                .map(|idx| {
+                    results[idx % chunks]
+                        .next()
+                        .expect("chunk results are complete")
+                })
+                .collect()
+        }
+    }
+}
+
+/// Processes elements of a chunk in order until the chunk is exhausted or work is cancelled. If `cancel_on_panic` is
+/// set, work is cancelled when `f` panics on any of the workers. Chunks are either [Chunk]s of owned elements, or
+/// iterators over parts of a borrowed slice.
+fn process_chunk<I, F, R>(tasks: I, stop: &StopFlag, cancel_on_panic: bool, mut f: F) -> Vec<R>
+where
+    I: ExactSizeIterator,
+    F: FnMut(I::Item) -> R,
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    let mut res = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        if stop.is_stopped() {
+            break;
+        }
+        res.push(f(task));
+    }
+    res
+}
+
+/// Same as [process_chunk], but stops at the first error of `f` and cancels work of other workers
+fn try_process_chunk<F, T, R, E>(
+    tasks: Chunk<T>,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    error: &FirstError<E>,
+    f: &F,
+) -> Vec<R>
+where
+    F: Fn(T) -> Result<R, E>,
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    let mut res = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        if stop.is_stopped() {
+            break;
+        }
+        match f(task) {
+            Ok(r) => res.push(r),
+            Err(err) => {
+                error.set(err);
+                stop.stop();
+                break;
+            }
+        }
+    }
+    res
+}
+
+/// Amount of chunks input of `length` elements is split into
+pub(crate) fn chunk_count(config: &WorkConfig, length: usize) -> usize {
+    config
+        .workers_for(length)
+        .min(length / config.min_chunk_size)
+        .max(1)
+}
+
+/// Boundaries of `chunks` contiguous chunks of elements with `weights`, such that total weight of the chunks is
+/// roughly the same. Returns index of the first element of every chunk, followed by the length of the input. Each
+/// chunk has at least one element, so an element that is heavier than the rest combined gets a chunk of its own.
+/// Weights that are all zero give no clue, so the chunks get equal amounts of elements instead.
+fn weighted_bounds(weights: &[u64], chunks: usize) -> Vec<usize> {
+    let length = weights.len();
+    // Sums of `u64`s can't overflow `u128` for any input that fits in memory
+    let prefix: Vec<u128> = std::iter::once(0)
+        .chain(weights.iter().scan(0, |sum, &w| {
+            *sum += u128::from(w);
+            Some(*sum)
+        }))
+        .collect();
+    let total = prefix[length];
+    if total == 0 {
+        return (0..=chunks)
+            .map(|c| chunk_start(length, chunks, c))
+            .collect();
+    }
+
+    let mut bounds = vec![0];
+    for chunk in 1..chunks {
+        // `total * chunk / chunks` without overflowing
+        let c = chunk as u128;
+        let n = chunks as u128;
+        let target = total / n * c + total % n * c / n;
+        // Whichever of the neighbouring boundaries is closer to the target
+        let after = prefix.partition_point(|&sum| sum < target);
+        let before = after.saturating_sub(1);
+        let closest = if target - prefix[before] < prefix[after] - target {
+            before
+        } else {
+            after
+        };
+        // Leave at least one element for this chunk and each of the following ones
+        let previous = bounds[chunk - 1];
+        bounds.push(closest.max(previous + 1).min(length - (chunks - chunk)));
+    }
+    bounds.push(length);
+    bounds
+}
+
+/// Processes `tasks` on workers, one chunk each. Returns results of every chunk in the same order.
+fn process_chunks<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    tasks: Vec<Chunk<T>>,
+    f: F,
+) -> Result<Vec<Vec<R>>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let res = process_chunks_init(config, spawner, tasks, || (), move |_, val| f(val), |_| ());
+    res.map(|(results, _)| results)
+}
+
+/// Same as [process_chunks], but every worker gets state created with `init`, which is passed to `f` along with each
+/// of its elements, and to `finish` once the chunk is done. Results of `finish` are returned in order of the chunks.
+/// Chunks are usually [Chunk]s, but any chunks of owned elements will do.
+fn process_chunks_init<'env, S, I, F, G, C, R, St, A>(
+    config: &WorkConfig,
+    spawner: &S,
+    mut tasks: Vec<C>,
+    init: I,
+    f: F,
+    finish: G,
+) -> Result<(Vec<Vec<R>>, Vec<A>), SpawnError>
+where
+    S: Spawner<'env>,
+    C: ExactSizeIterator + Send + 'env,
+    R: Send + 'env,
+    A: Send + 'env,
+    I: Fn() -> St + Send + Sync + 'env,
+    F: Fn(&mut St, C::Item) -> R + Send + Sync + 'env,
+    G: Fn(St) -> A + Send + Sync + 'env,
+{
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline_tasks = tasks.pop().expect("there is at least one chunk");
+
+    let shared = Arc::new((init, f, finish));
+    let jobs = tasks.into_iter().enumerate().map(|(chunk, tasks)| {
+        let stop = stop.clone();
+        let shared = shared.clone();
+        (chunk, move || {
+            let (init, f, finish) = &*shared;
+            let mut state = init();
+            let res = process_chunk(tasks, &stop, cancel_on_panic, |val| f(&mut state, val));
+            (res, finish(state))
+        })
+    });
+    let inline = || {
+        let (init, f, finish) = &*shared;
+        let mut state = init();
+        let res = process_chunk(inline_tasks, &stop, cancel_on_panic, |val| {
+            f(&mut state, val)
+        });
+        (res, finish(state))
+    };
+    // Workers are given chunks in input order, so their results are in input order as well
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+
+    // If function `f` panics, we should panic too, so that output vector has results for all input values. Original
+    // panic payload is propagated, so that the caller sees what went wrong.
+    Ok(results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .unzip())
+}
+
+/// Parallel part of [divide_equal_work](crate::divide_equal_work). `input` is expected to be large enough to be split.
+pub(crate) fn divide_equal_work<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (tasks, order) = split(input, chunks, config.partition);
+    let results = process_chunks(config, spawner, tasks, f)?;
+    Ok(assemble(results, config.partition, order))
+}
+
+/// Splits `input` into contiguous vectors of the sizes of the chunks of [divide_equal_work], for algorithms that need
+/// every chunk as a whole
+pub(crate) fn split_vecs<T>(config: &WorkConfig, mut input: Vec<T>) -> Vec<Vec<T>> {
+    let length = input.len();
+    let chunks = chunk_count(config, length);
+    let mut vecs: Vec<Vec<T>> = (0..chunks)
+        .rev()
+        .map(|chunk| input.split_off(chunk_start(length, chunks, chunk)))
+        .collect();
+    vecs.reverse();
+    vecs
+}
+
+/// Splits `input` into consecutive chunks of `size` elements, the last of which may be shorter
+pub(crate) fn split_sized<T>(mut input: Vec<T>, size: usize) -> Vec<Vec<T>> {
+    let chunks = input.len() / size + usize::from(input.len() % size != 0);
+    let mut vecs: Vec<Vec<T>> = (0..chunks)
+        .rev()
+        .map(|chunk| input.split_off(chunk * size))
+        .collect();
+    vecs.reverse();
+    vecs
+}
+
+/// Same as [divide_equal_work], but every element gets a worker of its own regardless of
+/// [min_chunk_size](WorkConfig::min_chunk_size), since the elements are chunks already
+pub(crate) fn divide_each<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    divide_equal_work(&config.clone().min_chunk_size(1), spawner, input, f)
+}
+
+/// Parallel part of [divide_equal_work_init](crate::divide_equal_work_init), same as
+/// [dynamic::divide_work_init](crate::dynamic::divide_work_init) but with equal chunks. `input` is expected to be large
+/// enough to be split.
+pub(crate) fn divide_equal_work_init<'env, S, I, F, G, T, R, St, A>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    init: I,
+    f: F,
+    finish: G,
+) -> Result<(Vec<R>, Vec<A>), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    A: Send + 'env,
+    I: Fn() -> St + Send + Sync + 'env,
+    F: Fn(&mut St, usize, T) -> R + Send + Sync + 'env,
+    G: Fn(St) -> A + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (tasks, order) = split(input, chunks, config.partition);
+    let tasks: Vec<_> = tasks.into_iter().map(Positioned).collect();
+    // Positions of shuffled elements are translated back into their original indices
+    let indices = order.clone();
+    let f = move |state: &mut St, (pos, val)| {
+        let idx = indices.as_ref().map_or(pos, |indices| indices[pos]);
+        f(state, idx, val)
+    };
+    let (results, states) = process_chunks_init(config, spawner, tasks, init, f, finish)?;
+    Ok((assemble(results, config.partition, order), states))
+}
+
+/// Parallel part of [divide_weighted_work](crate::divide_weighted_work). `input` is expected to be large enough to be
+/// split.
+pub(crate) fn divide_weighted_work<'env, S, W, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    weight: W,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    W: Fn(&T) -> u64,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let weights: Vec<u64> = input.iter().map(weight).collect();
+    let bounds = weighted_bounds(&weights, chunk_count(config, input.len()));
+    let buffer = share(input);
+    let tasks = bounds
+        .windows(2)
+        .map(|range| Chunk {
+            buffer: buffer.clone(),
+            next: range[0],
+            end: range[1],
+            step: 1,
+        })
+        .collect();
+    let results = process_chunks(config, spawner, tasks, f)?;
+    Ok(concat(results))
+}
+
+/// Parallel part of [try_divide_equal_work](crate::try_divide_equal_work). `input` is expected to be large enough to
+/// be split.
+pub(crate) fn try_divide_equal_work<'env, S, F, T, R, E>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Result<Vec<R>, E>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    E: Send + 'env,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+    let error = Arc::new(FirstError::new());
+
+    let (mut tasks, order) = split(input, chunks, config.partition);
+    let inline_tasks = tasks.pop().expect("there is at least one chunk");
+
+    let f = Arc::new(f);
+    let jobs = tasks.into_iter().enumerate().map(|(chunk, tasks)| {
+        let stop = stop.clone();
+        let error = error.clone();
+        let f = f.clone();
+        (chunk, move || {
+            try_process_chunk(tasks, &stop, cancel_on_panic, &error, &*f)
+        })
+    });
+    let inline = || try_process_chunk(inline_tasks, &stop, cancel_on_panic, &error, &*f);
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+
+    // Panics take precedence over errors, since they are not expected to happen
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(match error.take() {
+        Some(err) => Err(err),
+        None => Ok(assemble(results, config.partition, order)),
+    })
+}
+
+/// Parallel part of [divide_flat_map](crate::divide_flat_map) and its filtering variants. Input is split into
+/// contiguous chunks regardless of [WorkConfig::partition], every worker collects results of its chunk into a single
+/// vector, and the vectors are concatenated in order of the chunks. `input` is expected to be large enough to be split.
+pub(crate) fn divide_flat_map<'env, S, F, T, I>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Vec<I::Item>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    I: IntoIterator,
+    I::Item: Send + 'env,
+    F: Fn(T) -> I + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (tasks, _) = split(input, chunks, Partition::Contiguous);
+    let collect = move |results: &mut Vec<I::Item>, val| results.extend(f(val));
+    let (_, results) =
+        process_chunks_init(config, spawner, tasks, Vec::new, collect, |results| results)?;
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_fold](crate::divide_fold). Input is split into contiguous chunks regardless of
+/// [WorkConfig::partition], and every worker folds its chunk into an accumulator created with `identity`. Returns the
+/// accumulators in order of the chunks. `input` is expected to be large enough to be split.
+pub(crate) fn divide_fold<'env, S, I, F, T, A>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    identity: I,
+    fold: F,
+) -> Result<Vec<A>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    A: Send + 'env,
+    I: Fn() -> A + Send + Sync + 'env,
+    F: Fn(A, T) -> A + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (tasks, _) = split(input, chunks, Partition::Contiguous);
+    // Accumulator is moved in and out of `fold`, so it is only missing while `fold` runs
+    let init = move || Some(identity());
+    let fold = move |acc: &mut Option<A>, val| {
+        let folded = fold(
+            acc.take()
+                .expect("accumulator is put back after every element"),
+            val,
+        );
+        *acc = Some(folded);
+    };
+    let finish = |acc: Option<A>| acc.expect("accumulator is put back after every element");
+    let (_, accs) = process_chunks_init(config, spawner, tasks, init, fold, finish)?;
+    Ok(accs)
+}
+
+/// Parallel part of [divide_try_fold](crate::divide_try_fold), same as [divide_fold] but `fold` can break. The first
+/// worker to break stops all of them, same as with [divide_find_any], and its value is returned. If several workers
+/// break before they are stopped, the value of the earliest chunk is returned. Otherwise returns the accumulators in
+/// order of the chunks. `input` is expected to be large enough to be split.
+pub(crate) fn divide_try_fold<'env, S, I, F, T, A, B>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    identity: I,
+    fold: F,
+) -> Result<ControlFlow<B, Vec<A>>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    A: Send + 'env,
+    B: Send + 'env,
+    I: Fn() -> A + Send + Sync + 'env,
+    F: Fn(A, T) -> ControlFlow<B, A> + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (mut tasks, _) = split(input, chunks, Partition::Contiguous);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let shared = Arc::new((identity, fold));
+    let work = |tasks: Chunk<T>| {
+        let stop = stop.clone();
+        let shared = shared.clone();
+        move || {
+            let _guard = cancel_on_panic.then(|| CancelOnPanic(&stop));
+            let (identity, fold) = &*shared;
+            let mut acc = identity();
+            for val in tasks {
+                // Accumulators are discarded once a worker breaks, so the rest of the chunk is dropped
+                if stop.is_stopped() {
+                    break;
+                }
+                match fold(acc, val) {
+                    ControlFlow::Continue(next) => acc = next,
+                    ControlFlow::Break(b) => {
+                        stop.stop();
+                        return ControlFlow::Break(b);
+                    }
+                }
+            }
+            ControlFlow::Continue(acc)
+        }
+    };
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline = work(tasks.pop().expect("there is at least one chunk"));
+    let jobs = tasks.into_iter().map(work).enumerate();
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let mut accs = Vec::with_capacity(results.len());
+    for res in results {
+        match res.unwrap_or_else(|payload| panic::resume_unwind(payload)) {
+            ControlFlow::Continue(acc) => accs.push(acc),
+            ControlFlow::Break(b) => return Ok(ControlFlow::Break(b)),
+        }
+    }
+    Ok(ControlFlow::Continue(accs))
+}
+
+/// Parallel part of [divide_partition_map](crate::divide_partition_map) and its variants. Input is split into
+/// contiguous chunks regardless of [WorkConfig::partition], every worker sorts results of its chunk by their sides, and
+/// both sides are concatenated in order of the chunks. `input` is expected to be large enough to be split.
+pub(crate) fn divide_partition_map<'env, S, F, T, A, B>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<(Vec<A>, Vec<B>), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    A: Send + 'env,
+    B: Send + 'env,
+    F: Fn(T) -> Either<A, B> + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (tasks, _) = split(input, chunks, Partition::Contiguous);
+    let sort = move |(left, right): &mut (Vec<A>, Vec<B>), val| f(val).push_to(left, right);
+    let init = || (Vec::new(), Vec::new());
+    let (_, sides) = process_chunks_init(config, spawner, tasks, init, sort, |sides| sides)?;
+    let (left, right) = sides.into_iter().unzip();
+    Ok((concat(left), concat(right)))
+}
+
+/// Parallel part of [divide_find_any](crate::divide_find_any) and its variants. Input is split into contiguous chunks
+/// regardless of [WorkConfig::partition], and every worker goes through its chunk from the start until `f` returns a
+/// result for one of the elements. The first worker to find a result stops all of them, and the elements that are left
+/// are dropped without being processed. `input` is expected to be large enough to be split.
+pub(crate) fn divide_find_any<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Option<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> Option<R> + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, input.len());
+    let (mut tasks, _) = split(input, chunks, Partition::Contiguous);
+    let stop = Arc::new(StopFlag::new());
+    let found = Arc::new(Mutex::new(None));
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let f = Arc::new(f);
+    let work = |tasks: Chunk<T>| {
+        let stop = stop.clone();
+        let found = found.clone();
+        let f = f.clone();
+        move || {
+            process_chunk(tasks, &stop, cancel_on_panic, |val| {
+                if let Some(r) = f(val) {
+                    worker::lock(&found).get_or_insert(r);
+                    stop.stop();
+                }
+            })
+        }
+    };
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline = work(tasks.pop().expect("there is at least one chunk"));
+    let jobs = tasks.into_iter().map(work).enumerate();
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    // Results are `()`, so collecting them doesn't allocate
+    for res in results {
+        res.unwrap_or_else(|payload| panic::resume_unwind(payload));
+    }
+    let found = worker::lock(&found).take();
+    Ok(found)
+}
+
+/// Parallel part of [divide_find_first](crate::divide_find_first). Input is split into contiguous chunks regardless of
+/// [WorkConfig::partition], and every worker goes through its chunk from the start until `f` returns a result for one
+/// of the elements. Workers share the lowest index of a result found so far, and stop once they get past it, since
+/// none of their remaining elements can come first. Returns the result with the lowest index along with the index.
+/// `input` is expected to be large enough to be split.
+pub(crate) fn divide_find_first<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<Option<(usize, R)>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> Option<R> + Send + Sync + 'env,
+{
+    let length = input.len();
+    let chunks = chunk_count(config, length);
+    let (mut tasks, _) = split(input, chunks, Partition::Contiguous);
+    let stop = Arc::new(StopFlag::new());
+    let first = Arc::new(AtomicUsize::new(usize::MAX));
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let f = Arc::new(f);
+    let work = |chunk: usize, tasks: Chunk<T>| {
+        let stop = stop.clone();
+        let first = first.clone();
+        let f = f.clone();
+        move || {
+            let _guard = cancel_on_panic.then(|| CancelOnPanic(&stop));
+            for (idx, val) in (chunk_start(length, chunks, chunk)..).zip(tasks) {
+                if stop.is_stopped() || idx > first.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Some(r) = f(val) {
+                    first.fetch_min(idx, Ordering::Relaxed);
+                    return Some((idx, r));
+                }
+            }
+            None
+        }
+    };
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline = work(
+        chunks - 1,
+        tasks.pop().expect("there is at least one chunk"),
+    );
+    let jobs = tasks
+        .into_iter()
+        .enumerate()
+        .map(|(chunk, tasks)| (chunk, work(chunk, tasks)));
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let found = results
+        .into_iter()
+        .filter_map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .min_by_key(|&(idx, _)| idx);
+    Ok(found)
+}
+
+/// Parallel part of [divide_map_while](crate::divide_map_while). Input is split into contiguous chunks regardless of
+/// [WorkConfig::partition], and every worker goes through its chunk from the start until `f` returns `None`. Workers
+/// share the lowest index of `None` found so far, same as with [divide_find_first], and stop once they get past it.
+/// Returns results of the longest prefix for which `f` returned `Some`, and whether `f` returned `None` at all.
+/// `input` is expected to be large enough to be split.
+pub(crate) fn divide_map_while<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+) -> Result<(Vec<R>, bool), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> Option<R> + Send + Sync + 'env,
+{
+    let length = input.len();
+    let chunks = chunk_count(config, length);
+    let (mut tasks, _) = split(input, chunks, Partition::Contiguous);
+    let stop = Arc::new(StopFlag::new());
+    let first = Arc::new(AtomicUsize::new(usize::MAX));
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let f = Arc::new(f);
+    let work = |chunk: usize, tasks: Chunk<T>| {
+        let stop = stop.clone();
+        let first = first.clone();
+        let f = f.clone();
+        move || {
+            let _guard = cancel_on_panic.then(|| CancelOnPanic(&stop));
+            let mut results = Vec::with_capacity(tasks.len());
+            for (idx, val) in (chunk_start(length, chunks, chunk)..).zip(tasks) {
+                // Results past the first `None` are discarded anyway, so a chunk cut short there doesn't matter
+                if stop.is_stopped() || idx > first.load(Ordering::Relaxed) {
+                    break;
+                }
+                match f(val) {
+                    Some(r) => results.push(r),
+                    None => {
+                        first.fetch_min(idx, Ordering::Relaxed);
+                        return (results, true);
+                    }
+                }
+            }
+            (results, false)
+        }
+    };
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline = work(
+        chunks - 1,
+        tasks.pop().expect("there is at least one chunk"),
+    );
+    let jobs = tasks
+        .into_iter()
+        .enumerate()
+        .map(|(chunk, tasks)| (chunk, work(chunk, tasks)));
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let mut output = Vec::new();
+    for res in results {
+        let (results, stopped) = res.unwrap_or_else(|payload| panic::resume_unwind(payload));
+        output.extend(results);
+        if stopped {
+            return Ok((output, true));
+        }
+    }
+    Ok((output, false))
+}
+
+/// Parallel part of [divide_for_each_mut](crate::divide_for_each_mut). `data` is split into contiguous chunks of equal
+/// size with [split_at_mut](slice::split_at_mut), so workers mutate their elements in place. `data` is expected to be
+/// large enough to be split.
+pub(crate) fn divide_for_each_mut<'env, S, F, T>(
+    config: &WorkConfig,
+    spawner: &S,
+    data: &'env mut [T],
+    f: &'env F,
+) -> Result<(), SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    let length = data.len();
+    let chunks = chunk_count(config, length);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let mut parts = Vec::with_capacity(chunks);
+    let mut rest = data;
+    for chunk in 0..chunks {
+        let size = chunk_start(length, chunks, chunk + 1) - chunk_start(length, chunks, chunk);
+        let (part, tail) = rest.split_at_mut(size);
+        parts.push(part);
+        rest = tail;
+    }
+
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline_part = parts.pop().expect("there is at least one chunk");
+    let jobs = parts.into_iter().enumerate().map(|(chunk, part)| {
+        let stop = stop.clone();
+        (chunk, move || {
+            process_chunk(part.iter_mut(), &stop, cancel_on_panic, f)
+        })
+    });
+    let inline = || process_chunk(inline_part.iter_mut(), &stop, cancel_on_panic, f);
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    // Results are `()`, so collecting them doesn't allocate
+    for res in results {
+        res.unwrap_or_else(|payload| panic::resume_unwind(payload));
+    }
+    Ok(())
+}
+
+/// Parallel part of [divide_equal_work_ref](crate::divide_equal_work_ref). `input` is split into contiguous chunks of
+/// equal size, which workers process by reference. `input` is expected to be large enough to be split.
+pub(crate) fn divide_work_ref<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: &'env [T],
+    f: &'env F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Sync,
+    R: Send + 'env,
+    F: Fn(&T) -> R + Sync,
+{
+    let length = input.len();
+    let chunks = chunk_count(config, length);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let mut parts: Vec<&[T]> = (0..chunks)
+        .map(|chunk| {
+            &input[chunk_start(length, chunks, chunk)..chunk_start(length, chunks, chunk + 1)]
+        })
+        .collect();
+    let inline_part = parts.pop().expect("there is at least one chunk");
+    let jobs = parts.into_iter().enumerate().map(|(chunk, part)| {
+        let stop = stop.clone();
+        (chunk, move || {
+            process_chunk(part.iter(), &stop, cancel_on_panic, f)
+        })
+    });
+    let inline = || process_chunk(inline_part.iter(), &stop, cancel_on_panic, f);
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_positions](crate::divide_positions). `input` is split into contiguous chunks of equal
+/// size, and every worker collects indices of the matching elements of its chunk, which are concatenated in order of
+/// the chunks. `input` is expected to be large enough to be split.
+pub(crate) fn divide_positions<'env, S, P, T>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: &'env [T],
+    pred: &'env P,
+) -> Result<Vec<usize>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Sync,
+    P: Fn(&T) -> bool + Sync,
+{
+    let length = input.len();
+    let chunks = chunk_count(config, length);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let work = |chunk: usize| {
+        let stop = stop.clone();
+        let start = chunk_start(length, chunks, chunk);
+        let part = &input[start..chunk_start(length, chunks, chunk + 1)];
+        move || {
+            let _guard = cancel_on_panic.then(|| CancelOnPanic(&stop));
+            let mut positions = Vec::new();
+            for (idx, val) in (start..).zip(part) {
+                if stop.is_stopped() {
+                    break;
+                }
+                if pred(val) {
+                    positions.push(idx);
+                }
+            }
+            positions
+        }
+    };
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let inline = work(chunks - 1);
+    let jobs = (0..chunks - 1).map(|chunk| (chunk, work(chunk)));
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_zip](crate::divide_zip). Both inputs are split into contiguous chunks with the same
+/// boundaries, so every worker owns matching elements of both. Inputs are expected to have the same length, large
+/// enough to be split.
+pub(crate) fn divide_zip<'env, S, F, A, B, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    a: Vec<A>,
+    b: Vec<B>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    A: Send + 'env,
+    B: Send + 'env,
+    R: Send + 'env,
+    F: Fn(A, B) -> R + Send + Sync + 'env,
+{
+    let chunks = chunk_count(config, a.len());
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let (a, _) = split(a, chunks, Partition::Contiguous);
+    let (b, _) = split(b, chunks, Partition::Contiguous);
+    let mut tasks: Vec<_> = a.into_iter().zip(b).collect();
+    // Last chunk is processed on the calling thread instead of waiting idle for other workers
+    let (inline_a, inline_b) = tasks.pop().expect("there is at least one chunk");
+
+    let f = Arc::new(f);
+    let jobs = tasks.into_iter().enumerate().map(|(chunk, (a, b))| {
+        let stop = stop.clone();
+        let f = f.clone();
+        (chunk, move || {
+            process_chunk(a.zip(b), &stop, cancel_on_panic, |(a, b)| f(a, b))
+        })
+    });
+    let inline = || {
+        process_chunk(inline_a.zip(inline_b), &stop, cancel_on_panic, |(a, b)| {
+            f(a, b)
+        })
+    };
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_zip_ref](crate::divide_zip_ref), same as [divide_zip] but for borrowed inputs
+pub(crate) fn divide_zip_ref<'env, S, F, A, B, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    a: &'env [A],
+    b: &'env [B],
+    f: &'env F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    A: Sync,
+    B: Sync,
+    R: Send + 'env,
+    F: Fn(&A, &B) -> R + Sync,
+{
+    let length = a.len();
+    let chunks = chunk_count(config, length);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let work = |chunk: usize| {
+        let stop = stop.clone();
+        let range = chunk_start(length, chunks, chunk)..chunk_start(length, chunks, chunk + 1);
+        let part = a[range.clone()].iter().zip(&b[range]);
+        move || process_chunk(part, &stop, cancel_on_panic, |(a, b)| f(a, b))
+    };
+    let inline = work(chunks - 1);
+    let jobs = (0..chunks - 1).map(|chunk| (chunk, work(chunk)));
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(concat(results))
+}
+
+/// Parallel part of [divide_windows](crate::divide_windows). Every chunk is a range of windows, and its slice of
+/// `data` extends `window - 1` elements into the next chunk, so that no window is split. There are expected to be
+/// enough windows in `data` to split them.
+pub(crate) fn divide_windows<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    data: &'env [T],
+    window: usize,
+    f: &'env F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Sync,
+    R: Send + 'env,
+    F: Fn(&[T]) -> R + Sync,
+{
+    let length = data.len() - (window - 1);
+    let chunks = chunk_count(config, length);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+
+    let work = |chunk: usize| {
+        let stop = stop.clone();
+        let start = chunk_start(length, chunks, chunk);
+        let end = chunk_start(length, chunks, chunk + 1) + (window - 1);
+        let part = data[start..end].windows(window);
+        move || process_chunk(part, &stop, cancel_on_panic, f)
+    };
+    let inline = work(chunks - 1);
+    let jobs = (0..chunks - 1).map(|chunk| (chunk, work(chunk)));
+    let results = worker::run(config, spawner, &stop, jobs, inline)?;
+    let results = results
+        .into_iter()
+        .map(|res| res.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+        .collect();
+    Ok(concat(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::FailingSpawner;
+    use crate::SpawnFailurePolicy;
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn no_empty_chunks() {
+        for length in 1..200 {
+            for chunks in 1..=length.min(70) {
+                let starts: Vec<usize> = (0..=chunks)
+                    .map(|c| chunk_start(length, chunks, c))
+                    .collect();
+                assert_eq!(starts[0], 0);
+                assert_eq!(starts[chunks], length);
+                assert!(
+                    starts.windows(2).all(|w| w[0] < w[1]),
+                    "{} in {}",
+                    length,
+                    chunks
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn large_inputs_split_evenly() {
+        for length in [u32::MAX as usize / 2, 16_777_217, usize::MAX / 64] {
+            for chunks in [3, 7, 24, 61, 64] {
+                let sizes: Vec<usize> = (0..chunks)
+                    .map(|c| chunk_start(length, chunks, c + 1) - chunk_start(length, chunks, c))
+                    .collect();
+                assert_eq!(chunk_start(length, chunks, chunks), length);
+                assert_eq!(sizes.iter().sum::<usize>(), length);
+                let (min, max) = (sizes.iter().min().unwrap(), sizes.iter().max().unwrap());
+                assert!(
+                    max - min <= 1,
+                    "{} in {}: {} to {}",
+                    length,
+                    chunks,
+                    min,
+                    max
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn many_threads_few_elements() {
+        let input: Vec<usize> = (0..11).collect();
+        let output = WorkConfig::new()
+            .threads(64)
+            .divide_equal_work(input, |x| (x, std::thread::current().id()));
+
+        assert_eq!(
+            output.iter().map(|(x, _)| *x).collect::<Vec<_>>(),
+            (0..11).collect::<Vec<_>>()
+        );
+
+        // Each element has a chunk of its own
+        let mut threads: Vec<_> = output.into_iter().map(|(_, id)| id).collect();
+        threads.dedup();
+        assert_eq!(threads.len(), 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_spawned_chunk() {
+        WorkConfig::new()
+            .threads(2)
+            .divide_equal_work((0..20).collect(), |x| {
+                assert_ne!(x, 0, "bad item");
+                x
+            });
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_inline_chunk() {
+        WorkConfig::new()
+            .threads(2)
+            .divide_equal_work((0..20).collect(), |x| {
+                assert_ne!(x, 19, "bad item");
+                x
+            });
+    }
+
+    #[test]
+    fn min_chunk_size() {
+        let thread_count = |len, min_chunk_size| {
+            let config = WorkConfig::new().threads(32).min_chunk_size(min_chunk_size);
+            let mut threads =
+                config.divide_equal_work((0..len).collect(), |_| std::thread::current().id());
+            threads.dedup();
+            threads.len()
+        };
+
+        assert_eq!(thread_count(1000, 500), 2);
+        assert_eq!(thread_count(999, 500), 1);
+        assert_eq!(thread_count(1000, 100), 10);
+        assert_eq!(thread_count(1000, 1), 32);
+        assert_eq!(thread_count(1000, 0), 32);
+
+        let output = WorkConfig::new()
+            .threads(32)
+            .min_chunk_size(300)
+            .divide_equal_work((0..1000).collect(), |x| x + 1);
+        assert_eq!(output, (1..1001).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn panic_cancels_work() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        let res = std::panic::catch_unwind(|| {
+            WorkConfig::new()
+                .threads(2)
+                .divide_equal_work((0..10_000).collect(), move |x| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    assert_ne!(x, 0, "bad item");
+                    std::thread::sleep(std::time::Duration::from_micros(100));
+                    x
+                })
+        });
+
+        assert!(res.is_err());
+        assert!(processed.load(Ordering::Relaxed) < 5_000);
+    }
+
+    #[test]
+    fn spawn_failure_joins_spawned_workers() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        let config = WorkConfig::new().threads(4);
+        let res = divide_equal_work(
+            &config,
+            &FailingSpawner::new(1),
+            (0..1000).collect(),
+            move |x| {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                counter.fetch_add(1, Ordering::Relaxed);
+                x
+            },
+        );
+
+        assert!(res.is_err());
+        // Spawned worker was stopped and joined before returning, inline chunk was never processed
+        let processed_on_return = processed.load(Ordering::Relaxed);
+        assert!(processed_on_return < 250);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(processed.load(Ordering::Relaxed), processed_on_return);
+    }
+
+    #[test]
+    fn spawn_failure_degrades() {
+        let config = WorkConfig::new()
+            .threads(8)
+            .on_spawn_failure(SpawnFailurePolicy::Degrade);
+        for successes in 1..7 {
+            let output = divide_equal_work(
+                &config,
+                &FailingSpawner::new(successes),
+                (0..1000).collect(),
+                |x| (x, std::thread::current().id()),
+            );
+            let output = output.unwrap();
+            assert_eq!(
+                output.iter().map(|(x, _)| *x).collect::<Vec<_>>(),
+                (0..1000).collect::<Vec<_>>()
+            );
+
+            // Chunks are spawned in input order, so the ones at the end are processed by the calling thread
+            let caller = std::thread::current().id();
+            assert_eq!(output[999].1, caller);
+            let threads: HashSet<_> = output.into_iter().map(|(_, id)| id).collect();
+            assert_eq!(threads.len(), successes + 1);
+        }
+
+        let res = divide_equal_work(&config, &FailingSpawner::new(0), (0..1000).collect(), |x| x);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn try_ordered_output() {
+        let output = WorkConfig::new()
+            .threads(4)
+            .try_divide_equal_work((0..1000).collect(), |x| Ok::<_, ()>(x * 2));
+        assert_eq!(
+            output.unwrap(),
+            (0..1000).map(|x| x * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn try_error_in_last_chunk_stops_others() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        // Last chunk is 750..1000, it fails in the middle
+        let output =
+            WorkConfig::new()
+                .threads(4)
+                .try_divide_equal_work((0..1000).collect(), move |x| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    std::thread::sleep(std::time::Duration::from_micros(100));
+                    if x == 875 {
+                        Err(x)
+                    } else {
+                        Ok(x)
+                    }
+                });
+
+        assert!(matches!(output, Err(crate::WorkError::ItemError(875))));
+        // Each of the other chunks would have 250 elements if it didn't stop early
+        assert!(processed.load(Ordering::Relaxed) < 126 + 3 * 250);
+    }
+
+    #[test]
+    fn chunks_drop_unprocessed_elements() {
+        let input: Vec<Arc<()>> = (0..100).map(|_| Arc::new(())).collect();
+        let counters: Vec<_> = input.clone();
+        let (mut chunks, _) = split(input, 3, Partition::Contiguous);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            [34, 33, 33]
+        );
+
+        let first: Vec<_> = chunks.remove(0).collect();
+        chunks[0].next();
+        drop(chunks);
+        assert!(counters[..34].iter().all(|c| Arc::strong_count(c) == 2));
+        assert!(counters[34..].iter().all(|c| Arc::strong_count(c) == 1));
+        drop(first);
+        assert!(counters.iter().all(|c| Arc::strong_count(c) == 1));
+    }
+
+    #[test]
+    fn panic_drops_all_elements() {
+        let input: Vec<Arc<()>> = (0..1000).map(|_| Arc::new(())).collect();
+        let counters = input.clone();
+        let res = std::panic::catch_unwind(|| {
+            WorkConfig::new()
+                .threads(4)
+                .divide_equal_work(input, |x| assert_eq!(Arc::strong_count(&x), 1, "bad item"))
+        });
+        assert!(res.is_err());
+        assert!(counters.iter().all(|c| Arc::strong_count(c) == 1));
+    }
+
+    #[test]
+    fn strided_chunks() {
+        let (chunks, _) = split((0..10).collect(), 3, Partition::Strided);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            [4, 3, 3]
+        );
+        let results: Vec<Vec<_>> = chunks.into_iter().map(|c| c.collect()).collect();
+        assert_eq!(results, [vec![0, 3, 6, 9], vec![1, 4, 7], vec![2, 5, 8]]);
+        assert_eq!(
+            assemble(results, Partition::Strided, None),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn strided_ordered_output() {
+        for len in [2, 5, 64, 1001] {
+            let config = WorkConfig::new().threads(4).partition(Partition::Strided);
+            let output = config.divide_equal_work((0..len).collect(), |x| x * 2);
+            assert_eq!(output, (0..len).map(|x| x * 2).collect::<Vec<_>>());
+            let output = config.try_divide_equal_work((0..len).collect(), |x| Ok::<_, ()>(x * 2));
+            assert_eq!(output.unwrap(), (0..len).map(|x| x * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn strided_chunks_drop_unprocessed_elements() {
+        let input: Vec<Arc<()>> = (0..10).map(|_| Arc::new(())).collect();
+        let counters = input.clone();
+        let (mut chunks, _) = split(input, 3, Partition::Strided);
+        let first = chunks[0].next();
+        drop(chunks);
+        assert_eq!(Arc::strong_count(&counters[0]), 2);
+        assert!(counters[1..].iter().all(|c| Arc::strong_count(c) == 1));
+        drop(first);
+        assert_eq!(Arc::strong_count(&counters[0]), 1);
+    }
+
+    #[test]
+    fn shuffled_ordered_output() {
+        for seed in [0, 1, 12345] {
+            let config = WorkConfig::new()
+                .threads(4)
+                .partition(Partition::Shuffled { seed });
+            let output = config.divide_equal_work((0..1001).collect(), |x| x * 2);
+            assert_eq!(output, (0..1001).map(|x| x * 2).collect::<Vec<_>>());
+            let output = config.try_divide_equal_work((0..1001).collect(), |x| Ok::<_, ()>(x * 2));
+            assert_eq!(
+                output.unwrap(),
+                (0..1001).map(|x| x * 2).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn shuffled_spreads_hot_spots() {
+        // Expensive elements at the start of the input all belong to the first chunk unless shuffled
+        let config = WorkConfig::new()
+            .threads(4)
+            .partition(Partition::Shuffled { seed: 7 });
+        let output = config.divide_equal_work((0..1000).collect(), |_| std::thread::current().id());
+        let threads: HashSet<_> = output[..100].iter().collect();
+        assert_eq!(threads.len(), 4);
+    }
+
+    #[test]
+    fn weighted_bounds_balance_weight() {
+        assert_eq!(weighted_bounds(&[1, 1, 1, 1, 1, 1], 3), [0, 2, 4, 6]);
+        assert_eq!(weighted_bounds(&[3, 1, 1, 1, 1, 1], 2), [0, 2, 6]);
+        assert_eq!(weighted_bounds(&[1, 1, 1, 1, 4], 2), [0, 4, 5]);
+    }
+
+    #[test]
+    fn weighted_bounds_edge_cases() {
+        // All-zero weights fall back to equal amounts of elements
+        assert_eq!(weighted_bounds(&[0; 10], 3), [0, 4, 7, 10]);
+        // Heavy element gets a chunk of its own, the rest are still split
+        assert_eq!(weighted_bounds(&[100, 1, 1, 1], 2), [0, 1, 4]);
+        assert_eq!(weighted_bounds(&[100, 1, 1, 1], 4), [0, 1, 2, 3, 4]);
+        assert_eq!(weighted_bounds(&[1, 1, 1, 100], 4), [0, 1, 2, 3, 4]);
+        // Sums don't overflow
+        assert_eq!(weighted_bounds(&[u64::MAX; 4], 2), [0, 2, 4]);
+        assert_eq!(weighted_bounds(&[u64::MAX, 0, 0, u64::MAX], 2), [0, 1, 4]);
+    }
+
+    #[test]
+    fn weighted_ordered_output() {
+        let output = WorkConfig::new().threads(4).divide_weighted_work(
+            (0..1000).collect(),
+            |&x| x,
+            |x| x * 2,
+        );
+        assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn mutated_in_place() {
+        let normalize = |x: &mut f64| *x = (*x / 3.0).sqrt();
+        let mut expected: Vec<f64> = (0..10_000).map(f64::from).collect();
+        expected.iter_mut().for_each(normalize);
+
+        // Below the threshold work is done on the calling thread
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let mut data: Vec<f64> = (0..10_000).map(f64::from).collect();
+            config.divide_for_each_mut(&mut data, normalize);
+            assert_eq!(data, expected);
+            let mut data: Vec<f64> = (0..10_000).map(f64::from).collect();
+            config.divide_for_each_mut_dynamic(&mut data, normalize);
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test]
+    fn mutated_by_all_workers() {
+        let caller = std::thread::current().id();
+        let mut data = vec![None; 100];
+        WorkConfig::new()
+            .threads(4)
+            .divide_for_each_mut(&mut data, |id| *id = Some(std::thread::current().id()));
+        let threads: HashSet<_> = data.iter().map(|id| id.unwrap()).collect();
+        assert_eq!(threads.len(), 4);
+        assert!(threads.contains(&caller));
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_while_mutating() {
+        let mut data: Vec<i32> = (0..100).collect();
+        WorkConfig::new()
+            .threads(4)
+            .divide_for_each_mut(&mut data, |x| {
+                assert_ne!(*x, 50, "bad item");
+                *x += 1;
+            });
+    }
+}
@@ -0,0 +1,303 @@
+use crate::cancel::Cancelled;
+use std::any::Any;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// The OS failed to create a worker thread, for example because of resource limits
+#[derive(Debug)]
+pub struct SpawnError {
+    error: io::Error,
+}
+
+impl SpawnError {
+    pub(crate) fn new(error: io::Error) -> Self {
+        SpawnError { error }
+    }
+
+    /// Error reported by the OS
+    pub fn io_error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Converts into error reported by the OS
+    pub fn into_io_error(self) -> io::Error {
+        self.error
+    }
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to spawn worker thread: {}", self.error)
+    }
+}
+
+impl Error for SpawnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// `f` panicked while processing element at `index` of the input
+pub struct ItemPanic {
+    /// Index of the element in the input
+    pub index: usize,
+    /// Payload of the panic, as returned by [std::panic::catch_unwind]
+    pub payload: Box<dyn Any + Send>,
+}
+
+impl ItemPanic {
+    /// Panic message, if payload is a string as produced by `panic!` with a message
+    pub fn message(&self) -> Option<&str> {
+        payload_message(self.payload.as_ref())
+    }
+}
+
+impl fmt::Debug for ItemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemPanic")
+            .field("index", &self.index)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for ItemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(msg) => write!(f, "element {} panicked: {}", self.index, msg),
+            None => write!(f, "element {} panicked", self.index),
+        }
+    }
+}
+
+impl Error for ItemPanic {}
+
+/// Processing of an element took longer than allowed by
+/// [divide_work_with_deadline](crate::divide_work_with_deadline)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemTimedOut {
+    /// Time it took to process the element
+    pub elapsed: Duration,
+    /// Time that was allowed
+    pub limit: Duration,
+}
+
+impl fmt::Display for ItemTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "element took {:?}, but only {:?} was allowed",
+            self.elapsed, self.limit
+        )
+    }
+}
+
+impl Error for ItemTimedOut {}
+
+/// Reason why `f` didn't produce a result for an element in [divide_work_or](crate::divide_work_or)
+pub enum PanicOrError<E> {
+    /// `f` panicked with this payload
+    Panicked(Box<dyn Any + Send>),
+    /// `f` returned an error
+    Failed(E),
+}
+
+impl<E> PanicOrError<E> {
+    /// Panic message, if `f` panicked with a string payload as produced by `panic!` with a message
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            PanicOrError::Panicked(payload) => payload_message(payload.as_ref()),
+            PanicOrError::Failed(_) => None,
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for PanicOrError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PanicOrError::Panicked(_) => f
+                .debug_tuple("Panicked")
+                .field(&self.panic_message())
+                .finish(),
+            PanicOrError::Failed(err) => f.debug_tuple("Failed").field(err).finish(),
+        }
+    }
+}
+
+/// Error of a fallible call of this crate. `E` is the error type of fallible `f`, it defaults to
+/// [Infallible](std::convert::Infallible) for calls where `f` can't fail.
+pub enum WorkError<E = Infallible> {
+    /// `f` panicked while processing element at `index` of the input
+    ItemPanicked {
+        index: usize,
+        payload: Box<dyn Any + Send>,
+    },
+    /// The OS failed to create a worker thread
+    SpawnFailed(io::Error),
+    /// Work was cancelled before the whole input was processed, converted from [Cancelled]
+    Cancelled,
+    /// Work didn't finish before its deadline, see [TimeoutOutcome::into_result](crate::TimeoutOutcome::into_result)
+    TimedOut,
+    /// `f` returned an error
+    ItemError(E),
+}
+
+impl<E> WorkError<E> {
+    /// Panic message, if `f` panicked with a string payload as produced by `panic!` with a message
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            WorkError::ItemPanicked { payload, .. } => payload_message(payload.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl WorkError<ItemPanic> {
+    /// Reports panics caught per element the same way as panics of workers
+    pub(crate) fn flatten_panic(self) -> WorkError {
+        match self {
+            WorkError::ItemError(panic) => panic.into(),
+            WorkError::ItemPanicked { index, payload } => {
+                WorkError::ItemPanicked { index, payload }
+            }
+            WorkError::SpawnFailed(err) => WorkError::SpawnFailed(err),
+            WorkError::Cancelled => WorkError::Cancelled,
+            WorkError::TimedOut => WorkError::TimedOut,
+        }
+    }
+}
+
+impl<E> From<SpawnError> for WorkError<E> {
+    fn from(err: SpawnError) -> Self {
+        WorkError::SpawnFailed(err.into_io_error())
+    }
+}
+
+/// Discards partial results, so that `?` can be used on [divide_work_cancellable](crate::divide_work_cancellable) in
+/// functions that return [WorkError]
+impl<T, R, E> From<Cancelled<T, R>> for WorkError<E> {
+    fn from(_: Cancelled<T, R>) -> Self {
+        WorkError::Cancelled
+    }
+}
+
+impl<E> From<ItemPanic> for WorkError<E> {
+    fn from(panic: ItemPanic) -> Self {
+        WorkError::ItemPanicked {
+            index: panic.index,
+            payload: panic.payload,
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for WorkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkError::ItemPanicked { index, .. } => f
+                .debug_struct("ItemPanicked")
+                .field("index", index)
+                .field("message", &self.panic_message())
+                .finish(),
+            WorkError::SpawnFailed(err) => f.debug_tuple("SpawnFailed").field(err).finish(),
+            WorkError::Cancelled => f.write_str("Cancelled"),
+            WorkError::TimedOut => f.write_str("TimedOut"),
+            WorkError::ItemError(err) => f.debug_tuple("ItemError").field(err).finish(),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for WorkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkError::ItemPanicked { index, .. } => match self.panic_message() {
+                Some(msg) => write!(f, "element {} panicked: {}", index, msg),
+                None => write!(f, "element {} panicked", index),
+            },
+            WorkError::SpawnFailed(err) => write!(f, "failed to spawn worker thread: {}", err),
+            WorkError::Cancelled => f.write_str("work was cancelled"),
+            WorkError::TimedOut => f.write_str("work timed out"),
+            WorkError::ItemError(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for WorkError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WorkError::SpawnFailed(err) => Some(err),
+            WorkError::ItemError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Message of a panic `payload`, if it is a string as produced by `panic!` with a message
+fn payload_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        Some(msg)
+    } else {
+        payload.downcast_ref::<String>().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_send<T: Send>() {}
+
+    #[test]
+    fn work_error_is_send() {
+        is_send::<WorkError>();
+        is_send::<WorkError<String>>();
+    }
+
+    #[test]
+    fn work_error_variants() {
+        let panicked = WorkError::<Infallible>::ItemPanicked {
+            index: 7,
+            payload: Box::new("bad item"),
+        };
+        assert_eq!(panicked.to_string(), "element 7 panicked: bad item");
+        assert_eq!(panicked.panic_message(), Some("bad item"));
+        assert!(panicked.source().is_none());
+
+        let payload = Box::new(String::from("bad item"));
+        let panicked = WorkError::<Infallible>::from(ItemPanic { index: 3, payload });
+        assert_eq!(
+            format!("{:?}", panicked),
+            "ItemPanicked { index: 3, message: Some(\"bad item\") }"
+        );
+
+        let opaque = WorkError::<Infallible>::ItemPanicked {
+            index: 7,
+            payload: Box::new(7),
+        };
+        assert_eq!(opaque.to_string(), "element 7 panicked");
+        assert_eq!(opaque.panic_message(), None);
+
+        let io_error = io::Error::new(io::ErrorKind::WouldBlock, "out of threads");
+        let spawn = WorkError::<Infallible>::from(SpawnError::new(io_error));
+        assert_eq!(
+            spawn.to_string(),
+            "failed to spawn worker thread: out of threads"
+        );
+        assert!(spawn.source().unwrap().is::<io::Error>());
+
+        assert_eq!(
+            WorkError::<Infallible>::Cancelled.to_string(),
+            "work was cancelled"
+        );
+        assert_eq!(
+            WorkError::<Infallible>::TimedOut.to_string(),
+            "work timed out"
+        );
+
+        let item = WorkError::ItemError(io::Error::new(io::ErrorKind::NotFound, "no such record"));
+        assert_eq!(item.to_string(), "no such record");
+        assert!(item.source().unwrap().is::<io::Error>());
+    }
+}
@@ -1,134 +1,1838 @@
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "affinity")]
+mod affinity;
+mod cancel;
+mod config;
+mod cursor;
+mod deadline;
+mod dynamic;
+mod equal;
+mod error;
+#[cfg(feature = "numa")]
+mod numa;
+mod output;
+mod pool;
+mod queue;
+mod scan;
+mod shuffle;
+mod sort;
+mod stats;
+mod steal;
+mod threads;
+mod worker;
+
+#[cfg(feature = "affinity")]
+pub use affinity::PinError;
+pub use cancel::{CancelToken, Cancelled};
+pub use config::{
+    CoreSelection, Decision, Either, PanicPolicy, Partition, ReportedError, Schedule,
+    SpawnFailurePolicy, TimeoutOutcome, WorkConfig,
+};
+pub use deadline::ItemCtx;
+pub use error::{ItemPanic, ItemTimedOut, PanicOrError, SpawnError, WorkError};
+pub use pool::{set_pool_idle_timeout, shutdown_pool, Pool, PoolConfig, PoolStats, Scope};
+pub use stats::{WorkStats, WorkerStats};
+pub use threads::{default_parallelism, physical_parallelism};
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::iter::{Product, Sum};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 // For practical purposes should probably be larger
 const PARALLEL_WORK_THRESHOLD: usize = 10;
 
-/// Splits work between threads if amount of elements in `input` is greater than or equal to
-/// `PARALLEL_WORK_THRESHOLD = 10`. This function is better suitable for tiny or equal chunks of work regardless of
-/// input value. If computational time required to complete `f` varies greatly from input values, [divide_work]
-/// works better.
-pub fn divide_equal_work<F, T, R>(mut input: Vec<T>, f: F) -> Vec<R>
+static THRESHOLD: AtomicUsize = AtomicUsize::new(PARALLEL_WORK_THRESHOLD);
+static SEQUENTIAL: AtomicBool = AtomicBool::new(false);
+
+/// Minimal amount of elements required to split work between threads, unless overridden with
+/// [WorkConfig::threshold]. It is 10 unless changed with [set_parallel_threshold].
+pub fn parallel_threshold() -> usize {
+    THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Changes process-wide [parallel_threshold]. Affects all following calls that don't override threshold with
+/// [WorkConfig::threshold]. Threshold of `0` or `1` means that work is split whenever there is more than one element.
+pub fn set_parallel_threshold(threshold: usize) {
+    THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+/// Whether all work is forced to be done sequentially with [set_sequential]
+pub fn is_sequential() -> bool {
+    SEQUENTIAL.load(Ordering::Relaxed)
+}
+
+/// Forces all following calls to do work sequentially on the calling thread regardless of input size and
+/// configuration, or lifts this restriction. This is meant for debugging: no threads are spawned, so execution is
+/// deterministic, and panics of `f` propagate as is with simple backtraces.
+pub fn set_sequential(sequential: bool) {
+    SEQUENTIAL.store(sequential, Ordering::Relaxed);
+}
+
+/// Splits work between threads if amount of elements in `input` is greater than or equal to [parallel_threshold].
+/// This function is better suitable for tiny or equal chunks of work regardless of input value. If computational time
+/// required to complete `f` varies greatly from input values, [divide_work] works better.
+///
+/// # Panics
+///
+/// If `f` panics, other workers stop picking up new elements, and the panic is propagated to the caller with its
+/// original payload once all workers have finished. See [WorkConfig::panic_policy] for alternatives. Also panics if
+/// the OS fails to create a worker thread, use [divide_equal_work_checked] to handle this case.
+#[inline]
+pub fn divide_equal_work<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work(input, f)
+}
+
+/// Same as [divide_equal_work], but takes any input with an exact amount of elements, see [divide_work_iter]
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+#[inline]
+pub fn divide_equal_work_iter<I, F, T, R>(input: I, f: F) -> Vec<R>
+where
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work_iter(input, f)
+}
+
+/// Same as [divide_equal_work], but splits work between threads only if amount of elements in `input` is greater
+/// than or equal to `threshold`. Threshold of `0` or `1` means that work is split whenever there is more than one
+/// element.
+pub fn divide_equal_work_with_threshold<F, T, R>(input: Vec<T>, threshold: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::new()
+        .threshold(threshold)
+        .divide_equal_work(input, f)
+}
+
+/// Same as [divide_equal_work], but splits work into `n_threads` chunks instead of [default_parallelism] chunks. If
+/// `input` has less than `n_threads` elements, there will be one chunk per element. `n_threads` of `0` or `1` means
+/// that work is done sequentially on the calling thread.
+pub fn divide_equal_work_threads<F, T, R>(input: Vec<T>, n_threads: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::new()
+        .threads(n_threads)
+        .divide_equal_work(input, f)
+}
+
+/// Same as [divide_equal_work], but splits `input` into contiguous chunks of roughly equal total `weight` instead of
+/// equal amounts of elements. `weight` is an estimate of how expensive `f` is for an element, such as the length of a
+/// document, and is computed for every element on the calling thread before work is split. An element that is
+/// heavier than the rest combined gets a chunk of its own. If all weights are zero, chunks get equal amounts of
+/// elements. [WorkConfig::partition] doesn't apply, chunks are always contiguous.
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_weighted_work<W, F, T, R>(input: Vec<T>, weight: W, f: F) -> Vec<R>
+where
+    W: Fn(&T) -> u64,
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_weighted_work(input, weight, f)
+}
+
+/// Same as [divide_work], but only runs `f` for its side effects, like sending results to a channel or writing them
+/// to files. Nothing is collected, so there is no output buffer to allocate or fill.
+///
+/// ```
+/// use and_conquer::divide_for_each;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// let sum = Arc::new(AtomicU64::new(0));
+/// let total = sum.clone();
+/// divide_for_each((0..1000).collect(), move |x| {
+///     total.fetch_add(x, Ordering::Relaxed);
+/// });
+/// assert_eq!(sum.load(Ordering::Relaxed), 499_500);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_for_each<F, T>(input: Vec<T>, f: F)
+where
+    T: Send + 'static,
+    F: Fn(T) + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_for_each(input, f)
+}
+
+/// Same as [divide_for_each], but splits work evenly like [divide_equal_work]
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_for_each<F, T>(input: Vec<T>, f: F)
+where
+    T: Send + 'static,
+    F: Fn(T) + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_for_each(input, f)
+}
+
+/// Folds elements of `input` into a single value without collecting an output. Input is split into contiguous chunks
+/// like with [divide_equal_work], every worker folds its chunk into an accumulator that starts from `identity()`, and
+/// accumulators are combined on the calling thread in order of the chunks: `combine(combine(chunk_0, chunk_1),
+/// chunk_2)`, and so on. For the result to match a sequential fold, `identity()` must be neutral for `combine`, and
+/// `combine` must be associative relative to `fold`, but it doesn't have to be commutative.
+///
+/// ```
+/// use and_conquer::divide_fold;
+///
+/// let sum = divide_fold((0..1000u64).collect(), || 0, |acc, x| acc + x * x, |a, b| a + b);
+/// assert_eq!(sum, (0..1000u64).map(|x| x * x).sum());
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work], except that panics are always propagated regardless of [WorkConfig::panic_policy],
+/// since the accumulator is lost along with the panic.
+pub fn divide_fold<I, F, C, T, A>(input: Vec<T>, identity: I, fold: F, combine: C) -> A
+where
+    T: Send + 'static,
+    A: Send + 'static,
+    I: Fn() -> A + Send + Sync + 'static,
+    F: Fn(A, T) -> A + Send + Sync + 'static,
+    C: Fn(A, A) -> A,
+{
+    WorkConfig::default().divide_fold(input, identity, fold, combine)
+}
+
+/// Same as [divide_fold], but `fold` can stop the whole computation early by returning [Break](std::ops::ControlFlow).
+/// The first worker to break stops all of them, so the elements that are left are dropped without being folded, and
+/// the value it broke with is returned. Which value that is depends on timing if `fold` breaks on several elements.
+/// If every element continues, accumulators are combined in order of the chunks, same as with [divide_fold]. Fallible
+/// folds that return [Result] map errors to `Break`.
+///
+/// ```
+/// use and_conquer::divide_try_fold;
+/// use std::ops::ControlFlow;
+///
+/// let mut records: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+/// let parse = |sum: u64, record: String| match record.parse::<u64>() {
+///     Ok(val) => ControlFlow::Continue(sum + val),
+///     Err(err) => ControlFlow::Break(err),
+/// };
+/// let sum = divide_try_fold(records.clone(), || 0, parse, |a, b| a + b);
+/// assert_eq!(sum, ControlFlow::Continue(499_500));
+///
+/// records[500] = "bad".to_string();
+/// let sum = divide_try_fold(records, || 0, parse, |a, b| a + b);
+/// assert!(sum.is_break());
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_try_fold<I, F, C, T, A, B>(
+    input: Vec<T>,
+    identity: I,
+    fold: F,
+    combine: C,
+) -> std::ops::ControlFlow<B, A>
+where
+    T: Send + 'static,
+    A: Send + 'static,
+    B: Send + 'static,
+    I: Fn() -> A + Send + Sync + 'static,
+    F: Fn(A, T) -> std::ops::ControlFlow<B, A> + Send + Sync + 'static,
+    C: Fn(A, A) -> A,
+{
+    WorkConfig::default().divide_try_fold(input, identity, fold, combine)
+}
+
+/// Reduces elements of `input` to a single one with `op`, or returns `None` if `input` is empty. Same as [divide_fold],
+/// each worker reduces its contiguous chunk, and results of the chunks are reduced on the calling thread in order of
+/// the chunks, so an associative `op` gives the same result as a sequential [reduce](Iterator::reduce) even if it isn't
+/// commutative.
+///
+/// ```
+/// use and_conquer::divide_reduce;
+///
+/// let words: Vec<String> = (0..100).map(|x| x.to_string()).collect();
+/// let joined = words.concat();
+/// assert_eq!(divide_reduce(words, |a, b| a + &b), Some(joined));
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_reduce<F, T>(input: Vec<T>, op: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: Fn(T, T) -> T + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_reduce(input, op)
+}
+
+/// Sums elements of `input` in parallel, same as [Iterator::sum] does sequentially. Every worker keeps a running sum of
+/// its chunk, and the sums are added up in order of the chunks once workers are done, so no output is collected.
+/// Overflow behaves the same as in [Iterator::sum]: it panics in debug builds and wraps in release ones. Addition of
+/// floats isn't associative, so their sum may differ from the sequential one in last digits.
+///
+/// ```
+/// use and_conquer::divide_sum;
+///
+/// let sum: u64 = divide_sum((1..=1000).collect());
+/// assert_eq!(sum, 500_500);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_sum<T, S>(input: Vec<T>) -> S
+where
+    T: Send + 'static,
+    S: Sum<T> + Sum<S> + Send + 'static,
+{
+    WorkConfig::default().divide_sum(input)
+}
+
+/// Same as [divide_sum], but sums results of `f` for elements of `input`, mapping and summing in one pass
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_sum_by<F, T, R, S>(input: Vec<T>, f: F) -> S
+where
+    T: Send + 'static,
+    R: 'static,
+    S: Sum<R> + Sum<S> + Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_sum_by(input, f)
+}
+
+/// Same as [divide_sum], but multiplies elements of `input`, same as [Iterator::product]
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_product<T, P>(input: Vec<T>) -> P
+where
+    T: Send + 'static,
+    P: Product<T> + Product<P> + Send + 'static,
+{
+    WorkConfig::default().divide_product(input)
+}
+
+/// Checks whether `pred` returns `true` for any element of `input`. Input is split into contiguous chunks like with
+/// [divide_equal_work], and every worker checks its chunk from the start. Once a match is found, all workers stop, and
+/// the rest of the elements are dropped without calling `pred`, so expensive predicates are cut short. For a pure
+/// `pred` the answer is the same as of [Iterator::any].
+///
+/// ```
+/// use and_conquer::divide_any;
+///
+/// assert!(divide_any((0..1000).collect(), |&x| x == 500));
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_any<P, T>(input: Vec<T>, pred: P) -> bool
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_any(input, pred)
+}
+
+/// Splits `input` into elements for which `pred` returns `true` and the rest of them, same as [Iterator::partition].
+/// Both sides keep the original relative order of their elements. Input is split into contiguous chunks like with
+/// [divide_equal_work], every worker partitions its chunk, and the parts are concatenated in order of the chunks.
+///
+/// ```
+/// use and_conquer::divide_partition;
+///
+/// let (even, odd) = divide_partition((0..1000).collect(), |x| x % 2 == 0);
+/// assert_eq!(even[..3], [0, 2, 4]);
+/// assert_eq!(odd[..3], [1, 3, 5]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work], except that panics are always propagated regardless of [WorkConfig::panic_policy],
+/// since the element would belong to neither side.
+pub fn divide_partition<P, T>(input: Vec<T>, pred: P) -> (Vec<T>, Vec<T>)
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_partition(input, pred)
+}
+
+/// Same as [divide_partition], but `f` maps every element to a result of one of two types, and results of each type
+/// are collected separately, in the same relative order as their elements in `input`.
+///
+/// ```
+/// use and_conquer::{divide_partition_map, Either};
+///
+/// let lines: Vec<String> = vec!["1".into(), "x".into(), "3".into()];
+/// let (numbers, invalid) = divide_partition_map(lines, |line| match line.parse::<u32>() {
+///     Ok(n) => Either::Left(n),
+///     Err(_) => Either::Right(line),
+/// });
+/// assert_eq!(numbers, [1, 3]);
+/// assert_eq!(invalid, ["x"]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_partition].
+pub fn divide_partition_map<F, T, A, B>(input: Vec<T>, f: F) -> (Vec<A>, Vec<B>)
+where
+    T: Send + 'static,
+    A: Send + 'static,
+    B: Send + 'static,
+    F: Fn(T) -> Either<A, B> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_partition_map(input, f)
+}
+
+/// Same as [divide_partition_map], but separates successful results of a fallible `f` from its errors. Unlike
+/// [try_divide_work], work doesn't stop at the first error.
+///
+/// # Panics
+///
+/// Same as [divide_partition].
+pub fn divide_partition_results<F, T, R, E>(input: Vec<T>, f: F) -> (Vec<R>, Vec<E>)
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    E: Send + 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_partition_results(input, f)
+}
+
+/// Same as [divide_work], but `f` returns pairs, which are split into two vectors, both in order of `input`. Workers
+/// write each component straight into its vector, so unlike unzipping the output of [divide_work], results are never
+/// collected as pairs first. Elements are always taken from a shared queue, regardless of [WorkConfig::schedule].
+///
+/// ```
+/// use and_conquer::divide_unzip;
+///
+/// let (squares, names) = divide_unzip((0..1000).collect(), |x: u64| (x * x, x.to_string()));
+/// assert_eq!(squares[..3], [0, 1, 4]);
+/// assert_eq!(names[999], "999");
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_partition].
+pub fn divide_unzip<F, T, A, B>(input: Vec<T>, f: F) -> (Vec<A>, Vec<B>)
+where
+    T: Send + 'static,
+    A: Send + 'static,
+    B: Send + 'static,
+    F: Fn(T) -> (A, B) + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_unzip(input, f)
+}
+
+/// Splits `input` into consecutive chunks of `chunk_size` elements, the last of which may be shorter, and processes
+/// each chunk with `f` as a whole. Outputs of the chunks are concatenated in order of the chunks, and don't need to
+/// have the same length as the chunks. Useful when `f` is vectorized or calls into a batched API, and per-element
+/// calls are too fine-grained. Whether work is split depends on amount of elements, not chunks, and chunks are
+/// scheduled the same way as elements of [divide_work].
+///
+/// ```
+/// use and_conquer::divide_chunks;
+///
+/// let sums: Vec<u32> = divide_chunks((1..=10).collect(), 4, |chunk: &[u32]| vec![chunk.iter().sum()]);
+/// assert_eq!(sums, [10, 26, 19]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `chunk_size` is zero. Otherwise same as [divide_work], except that [PanicPolicy::SkipItem] applies to
+/// whole chunks: `f` gets all elements of a chunk at once, so if it panics, output of the whole chunk is left out.
+pub fn divide_chunks<F, T, R>(input: Vec<T>, chunk_size: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(&[T]) -> Vec<R> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_chunks(input, chunk_size, f)
+}
+
+/// Same as [divide_chunks], but `f` takes ownership of the chunks
+///
+/// ```
+/// use and_conquer::divide_chunks_owned;
+///
+/// let words: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+/// let joined = divide_chunks_owned(words, 2, |chunk| vec![chunk.concat()]);
+/// assert_eq!(joined, ["ab", "c"]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_chunks].
+pub fn divide_chunks_owned<F, T, R>(input: Vec<T>, chunk_size: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(Vec<T>) -> Vec<R> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_chunks_owned(input, chunk_size, f)
+}
+
+/// Processes every window of `window` consecutive elements of `data` with `f`, same as `data.windows(window)`, and
+/// returns results in order of the windows. There are `len - window + 1` windows, or none if `window` is longer than
+/// `data`. Windows overlap, so workers borrow `data` instead of taking elements out of it, and ranges of windows of
+/// the workers overlap by `window - 1` elements, so that no window is split.
+///
+/// ```
+/// use and_conquer::divide_windows;
+///
+/// let signal: Vec<f64> = (0..1000).map(|x| x as f64).collect();
+/// let averages = divide_windows(&signal, 4, |window| window.iter().sum::<f64>() / 4.0);
+/// assert_eq!(averages.len(), 997);
+/// assert_eq!(averages[0], 1.5);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `window` is zero. Otherwise same as [divide_equal_work_ref].
+pub fn divide_windows<F, T, R>(data: &[T], window: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&[T]) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_windows(data, window, f)
+}
+
+/// Processes pairs of elements of `a` and `b` at the same positions with `f`, and returns results in order of the
+/// inputs. Both inputs are split into contiguous chunks with the same boundaries like with [divide_equal_work], so
+/// each worker owns matching ranges of both, and the pairs are never collected into a vector of tuples.
+///
+/// ```
+/// use and_conquer::divide_zip;
+///
+/// let products = divide_zip((0..1000).collect(), (0..1000).collect(), |a: u64, b: u64| a * b);
+/// assert_eq!(products[..4], [0, 1, 4, 9]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `a` and `b` have different lengths. Otherwise same as [divide_equal_work].
+pub fn divide_zip<F, A, B, R>(a: Vec<A>, b: Vec<B>, f: F) -> Vec<R>
+where
+    A: Send + 'static,
+    B: Send + 'static,
+    R: Send + 'static,
+    F: Fn(A, B) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_zip(a, b, f)
+}
+
+/// Same as [divide_zip], but borrows `a` and `b`, like [divide_equal_work_ref]
+///
+/// ```
+/// use and_conquer::divide_zip_ref;
+///
+/// let names = ["a", "b", "c"];
+/// let counts = [1, 2, 3];
+/// let labels = divide_zip_ref(&names, &counts, |name, count| format!("{}{}", name, count));
+/// assert_eq!(labels, ["a1", "b2", "c3"]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_zip].
+pub fn divide_zip_ref<F, A, B, R>(a: &[A], b: &[B], f: F) -> Vec<R>
+where
+    A: Sync,
+    B: Sync,
+    R: Send,
+    F: Fn(&A, &B) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_zip_ref(a, b, f)
+}
+
+/// Collects key-value pairs that `f` produces for the elements of `input` into a [HashMap]. Input is split into
+/// contiguous chunks like with [divide_equal_work], every worker collects its chunk into its own map, and the maps are
+/// merged by moving the smaller one into the larger one. If several elements produce the same key, the value of the
+/// last one of them in `input` is kept, same as when collecting sequentially.
+///
+/// ```
+/// use and_conquer::divide_collect_map;
+///
+/// let squares = divide_collect_map((0..1000u64).collect(), |x| (x % 10, x * x));
+/// assert_eq!(squares.len(), 10);
+/// assert_eq!(squares[&3], 993 * 993);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work], except that panics of `f` are always propagated regardless of
+/// [WorkConfig::panic_policy], since the map of the chunk is lost along with the panic.
+pub fn divide_collect_map<F, T, K, V>(input: Vec<T>, f: F) -> HashMap<K, V>
+where
+    T: Send + 'static,
+    K: Eq + Hash + Send + 'static,
+    V: Send + 'static,
+    F: Fn(T) -> (K, V) + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_collect_map(input, f)
+}
+
+/// Same as [divide_collect_map], but values of duplicate keys are merged with `merge(earlier, later)`, where `earlier`
+/// comes from elements that are before the ones of `later` in `input`. Like `combine` of [divide_fold], `merge` must be
+/// associative for the result to match merging sequentially.
+///
+/// ```
+/// use and_conquer::divide_collect_map_with;
+///
+/// let words: Vec<String> = "a b a c b a".split(' ').map(String::from).collect();
+/// let counts = divide_collect_map_with(words, |word| (word, 1), |a, b| a + b);
+/// assert_eq!(counts["a"], 3);
+/// assert_eq!(counts["c"], 1);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_collect_map], and panics of `merge` are propagated the same way.
+pub fn divide_collect_map_with<F, M, T, K, V>(input: Vec<T>, f: F, merge: M) -> HashMap<K, V>
+where
+    T: Send + 'static,
+    K: Eq + Hash + Send + 'static,
+    V: Send + 'static,
+    F: Fn(T) -> (K, V) + Send + Sync + 'static,
+    M: Fn(V, V) -> V + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_collect_map_with(input, f, merge)
+}
+
+/// Same as [divide_collect_map], but collects into a [BTreeMap]
+///
+/// # Panics
+///
+/// Same as [divide_collect_map].
+pub fn divide_collect_btree_map<F, T, K, V>(input: Vec<T>, f: F) -> BTreeMap<K, V>
+where
+    T: Send + 'static,
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+    F: Fn(T) -> (K, V) + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_collect_btree_map(input, f)
+}
+
+/// Same as [divide_collect_map_with], but collects into a [BTreeMap]
+///
+/// # Panics
+///
+/// Same as [divide_collect_map_with].
+pub fn divide_collect_btree_map_with<F, M, T, K, V>(input: Vec<T>, f: F, merge: M) -> BTreeMap<K, V>
+where
+    T: Send + 'static,
+    K: Ord + Send + 'static,
+    V: Send + 'static,
+    F: Fn(T) -> (K, V) + Send + Sync + 'static,
+    M: Fn(V, V) -> V + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_collect_btree_map_with(input, f, merge)
+}
+
+/// Groups the elements of `input` by `key` and maps each of them with `map`. Input is split into contiguous chunks like
+/// with [divide_equal_work], every worker groups its chunk, and the groups are merged in order of the chunks by
+/// appending the values of each key, so values of every group are in the same order as their elements in `input`.
+///
+/// ```
+/// use and_conquer::divide_group_by;
+///
+/// let groups = divide_group_by((0..1000u32).collect(), |x| x % 3, |x| x * 2);
+/// assert_eq!(groups.len(), 3);
+/// assert_eq!(groups[&1][..3], [2, 8, 14]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_group_by<F, G, T, K, V>(input: Vec<T>, key: F, map: G) -> HashMap<K, Vec<V>>
+where
+    T: Send + 'static,
+    K: Eq + Hash + Send + 'static,
+    V: Send + 'static,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+    G: Fn(T) -> V + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_group_by(input, key, map)
+}
+
+/// Sorts `input` in parallel. Input is split into contiguous runs like with [divide_equal_work], every worker sorts
+/// its run with [slice::sort], and adjacent runs are then merged pairwise, with the pairs of each round merged in
+/// parallel. Input below the threshold is sorted with [slice::sort] right away. Like [slice::sort], the sort is
+/// stable: equal elements keep their order.
+///
+/// ```
+/// use and_conquer::divide_sort;
+///
+/// let sorted = divide_sort((0..1000).rev().collect());
+/// assert_eq!(sorted, (0..1000).collect::<Vec<_>>());
+/// ```
+///
+/// # Panics
+///
+/// Panics if [Ord] implementation of `T` panics, regardless of [WorkConfig::panic_policy]. Unlike [slice::sort], the
+/// elements are dropped along with the panic, since the input is consumed.
+pub fn divide_sort<T>(input: Vec<T>) -> Vec<T>
+where
+    T: Ord + Send + 'static,
+{
+    WorkConfig::default().divide_sort(input)
+}
+
+/// Same as [divide_sort], but with a comparator function, like [slice::sort_by]
+///
+/// ```
+/// use and_conquer::divide_sort_by;
+///
+/// let sorted = divide_sort_by((0..1000).collect(), |a, b| b.cmp(a));
+/// assert_eq!(sorted[..3], [999, 998, 997]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_sort], but for `compare`.
+pub fn divide_sort_by<F, T>(input: Vec<T>, compare: F) -> Vec<T>
+where
+    T: Send + 'static,
+    F: Fn(&T, &T) -> std::cmp::Ordering + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_sort_by(input, compare)
+}
+
+/// Same as [divide_sort], but with a key extraction function, like [slice::sort_by_key]. `key` is called on every
+/// comparison.
+///
+/// ```
+/// use and_conquer::divide_sort_by_key;
+///
+/// let sorted = divide_sort_by_key((0..1000).collect(), |x: &i32| (x % 10, -x));
+/// assert_eq!(sorted[..3], [990, 980, 970]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_sort], but for `key` and [Ord] implementation of `K`.
+pub fn divide_sort_by_key<F, T, K>(input: Vec<T>, key: F) -> Vec<T>
+where
+    T: Send + 'static,
+    K: Ord,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_sort_by_key(input, key)
+}
+
+/// Same as [divide_sort_by_key], but `key` is called exactly once for every element, like with
+/// [slice::sort_by_cached_key]. Keys are computed in parallel like with [divide_work_ref], then the keys are sorted
+/// along with the indices of their elements on the calling thread, and the elements are moved into place. This is
+/// the better choice when `key` is expensive, even though the sort itself is sequential.
+///
+/// ```
+/// use and_conquer::divide_sort_by_cached_key;
+///
+/// let words: Vec<String> = vec!["b".into(), "C".into(), "a".into()];
+/// let sorted = divide_sort_by_cached_key(words, |word| word.to_lowercase());
+/// assert_eq!(sorted, ["a", "b", "C"]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `key` panics, regardless of [WorkConfig::panic_policy], since leaving elements out would change the
+/// length of the sorted output. The elements are dropped along with the panic, same as with [divide_sort].
+pub fn divide_sort_by_cached_key<F, T, K>(input: Vec<T>, key: F) -> Vec<T>
+where
+    T: Sync,
+    K: Ord + Send,
+    F: Fn(&T) -> K + Send + Sync,
+{
+    WorkConfig::default().divide_sort_by_cached_key(input, key)
+}
+
+/// Merges `shards` that are each sorted already into a single sorted vector. Adjacent shards are merged pairwise, with
+/// the pairs of each round merged in parallel, like the last step of [divide_sort]. Equal elements of different
+/// shards keep the order of the shards, and equal elements of the same shard keep their order within it. Below the
+/// threshold, shards are concatenated and sorted with [slice::sort], which merges existing runs on its own. Order of
+/// the output is unspecified if some of the shards aren't sorted, but it has every element either way.
+///
+/// ```
+/// use and_conquer::divide_merge_sorted;
+///
+/// let merged = divide_merge_sorted(vec![vec![1, 4, 7], vec![], vec![2, 3, 8, 9], vec![5]]);
+/// assert_eq!(merged, [1, 2, 3, 4, 5, 7, 8, 9]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_sort].
+pub fn divide_merge_sorted<T>(shards: Vec<Vec<T>>) -> Vec<T>
+where
+    T: Ord + Send + 'static,
+{
+    WorkConfig::default().divide_merge_sorted(shards)
+}
+
+/// Same as [divide_merge_sorted], but with a comparator function that shards are sorted by, like [slice::sort_by]
+///
+/// # Panics
+///
+/// Same as [divide_sort_by].
+pub fn divide_merge_sorted_by<F, T>(shards: Vec<Vec<T>>, compare: F) -> Vec<T>
+where
+    T: Send + 'static,
+    F: Fn(&T, &T) -> std::cmp::Ordering + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_merge_sorted_by(shards, compare)
+}
+
+/// Removes consecutive repeated elements of `input`, same as [Vec::dedup]. Input is split into contiguous chunks like
+/// with [divide_equal_work], every worker deduplicates its chunk, and the chunks are joined in order, dropping the
+/// first element of a chunk if it repeats the last element of the previous one. Elements are moved into a new vector
+/// along the way.
+///
+/// ```
+/// use and_conquer::divide_dedup;
+///
+/// let deduped = divide_dedup((0..1000).map(|x| x / 10).collect());
+/// assert_eq!(deduped, (0..100).collect::<Vec<_>>());
+/// ```
+///
+/// # Panics
+///
+/// Panics if [PartialEq] implementation of `T` panics, regardless of [WorkConfig::panic_policy], since the elements
+/// that the chunk kept so far are lost along with the panic. Also panics if the OS fails to create a worker thread,
+/// same as [divide_equal_work].
+pub fn divide_dedup<T>(input: Vec<T>) -> Vec<T>
+where
+    T: PartialEq + Send + 'static,
+{
+    WorkConfig::default().divide_dedup(input)
+}
+
+/// Same as [divide_dedup], but elements repeat if their keys are equal, like with [Vec::dedup_by_key]
+///
+/// # Panics
+///
+/// Same as [divide_dedup], but for `key` and [PartialEq] implementation of `K`.
+pub fn divide_dedup_by_key<F, T, K>(input: Vec<T>, key: F) -> Vec<T>
+where
+    T: Send + 'static,
+    K: PartialEq,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_dedup_by_key(input, key)
+}
+
+/// Inclusive scan of `input`, like the one [Iterator::scan] can do: element `i` of the output is `identity()` combined
+/// with elements `0..=i` of `input` in order with `op`. Input is split into contiguous chunks like with
+/// [divide_equal_work], every worker scans its chunk, totals of the chunks are scanned on the calling thread, and
+/// workers then apply the total of all previous chunks to every element of their chunk. For the result to match a
+/// sequential scan, `identity()` must be neutral for `op`, and `op` must be associative, but it doesn't have to be
+/// commutative.
+///
+/// ```
+/// use and_conquer::divide_scan;
+///
+/// let maxima = divide_scan(vec![3, 1, 4, 1, 5, 9, 2, 6], || i32::MIN, |a, b| *a.max(b));
+/// assert_eq!(maxima, [3, 3, 4, 4, 5, 9, 9, 9]);
+/// ```
+///
+/// # Panics
+///
+/// If `identity` or `op` panics for any element, the panic is propagated to the caller with its original payload once
+/// all workers have finished, regardless of [WorkConfig::panic_policy]. [PanicPolicy::SkipItem] doesn't apply, since
+/// every element of the output depends on all elements before it, so leaving one out would change the rest of the
+/// prefix. Also panics if the OS fails to create a worker thread, same as [divide_equal_work].
+pub fn divide_scan<I, F, A>(input: Vec<A>, identity: I, op: F) -> Vec<A>
+where
+    A: Send + 'static,
+    I: Fn() -> A + Send + Sync + 'static,
+    F: Fn(&A, &A) -> A + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_scan(input, identity, op)
+}
+
+/// Running totals of `input`, same as [divide_scan] with addition
+///
+/// ```
+/// use and_conquer::divide_prefix_sum;
+///
+/// let totals = divide_prefix_sum((1..=1000u64).collect());
+/// assert_eq!(totals[..4], [1, 3, 6, 10]);
+/// assert_eq!(totals[999], 500_500);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_scan], including on overflow in debug builds.
+pub fn divide_prefix_sum<T>(input: Vec<T>) -> Vec<T>
+where
+    T: std::ops::Add<Output = T> + Copy + Default + Send + 'static,
+{
+    WorkConfig::default().divide_prefix_sum(input)
+}
+
+/// Indices of the elements of `input` for which `pred` returns `true`, in ascending order. Input is split into
+/// contiguous chunks like with [divide_equal_work_ref], every worker collects indices of its chunk, and the indices
+/// are concatenated in order of the chunks, so elements are neither cloned nor sorted.
+///
+/// ```
+/// use and_conquer::divide_positions;
+///
+/// let input: Vec<u32> = (0..1000).collect();
+/// assert_eq!(divide_positions(&input, |&x| x % 400 == 0), [0, 400, 800]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_positions<P, T>(input: &[T], pred: P) -> Vec<usize>
+where
+    T: Sync,
+    P: Fn(&T) -> bool + Send + Sync,
+{
+    WorkConfig::default().divide_positions(input, pred)
+}
+
+/// Same as [Vec::retain], but `pred` is evaluated for all elements in parallel like with [divide_work_ref]. Elements
+/// are then compacted in place on the calling thread, keeping their order, so nothing is reallocated.
+///
+/// ```
+/// use and_conquer::divide_retain;
+///
+/// let mut vec: Vec<u32> = (0..1000).collect();
+/// divide_retain(&mut vec, |&x| x % 300 == 0);
+/// assert_eq!(vec, [0, 300, 600, 900]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work]. With [PanicPolicy::SkipItem], elements for which `pred` panicked are removed.
+pub fn divide_retain<P, T>(vec: &mut Vec<T>, pred: P)
+where
+    T: Sync,
+    P: Fn(&T) -> bool + Send + Sync,
+{
+    WorkConfig::default().divide_retain(vec, pred)
+}
+
+/// Returns any element of `input` for which `pred` returns `true`, or `None` if there is no such element. Workers stop
+/// as soon as one of them finds a match, same as with [divide_any], and the elements that are left are dropped. Which
+/// of the matching elements is returned is unspecified.
+///
+/// ```
+/// use and_conquer::divide_find_any;
+///
+/// let found = divide_find_any((0..1000).collect(), |&x| x % 100 == 42);
+/// assert_eq!(found.map(|x| x % 100), Some(42));
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_find_any<P, T>(input: Vec<T>, pred: P) -> Option<T>
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_find_any(input, pred)
+}
+
+/// Processes elements of `input` with `f` until it returns `None`, and returns results of the elements before it, same
+/// as [Iterator::map_while] would. Input is split into contiguous chunks like with [divide_equal_work], and every
+/// worker goes through its chunk from the start. Workers share the lowest index for which `f` returned `None` so far,
+/// and stop once they get past it, since results past it are discarded anyway. Elements are processed in no
+/// particular order, so `f` may still get some of them past the first `None`.
+///
+/// ```
+/// use and_conquer::divide_map_while;
+///
+/// let records: Vec<u32> = (0..1000).collect();
+/// let before_cutoff = divide_map_while(records, |time| (time < 600).then(|| time * 2));
+/// assert_eq!(before_cutoff.len(), 600);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work]. With [PanicPolicy::SkipItem], elements for which `f` panicked are left out of the
+/// output, and don't end it.
+pub fn divide_map_while<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Option<R> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_map_while(input, f)
+}
+
+/// Returns the first element of `input` for which `pred` returns `true` along with its index, same as
+/// [Iterator::position] would find. Input is split into contiguous chunks like with [divide_equal_work], and every
+/// worker checks its chunk from the start. Workers share the lowest index of a match found so far, and stop once they
+/// get past it, so later chunks are abandoned as soon as an earlier one has a match.
+///
+/// ```
+/// use and_conquer::divide_find_first;
+///
+/// assert_eq!(divide_find_first((0..1000).collect(), |&x| x % 100 == 42), Some((42, 42)));
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_find_first<P, T>(input: Vec<T>, pred: P) -> Option<(usize, T)>
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_find_first(input, pred)
+}
+
+/// Same as [divide_find_any], but borrows `input`, returning a reference to the match. Workers run on scoped threads
+/// like with [divide_work_ref], so neither `input` nor `pred` need to be `'static`.
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_find_any_ref<P, T>(input: &[T], pred: P) -> Option<&T>
+where
+    T: Sync,
+    P: Fn(&T) -> bool + Send + Sync,
+{
+    WorkConfig::default().divide_find_any_ref(input, pred)
+}
+
+/// Checks whether `pred` returns `true` for every element of `input`. Same as [divide_any], all workers stop at the
+/// first counterexample.
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_all<P, T>(input: Vec<T>, pred: P) -> bool
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_all(input, pred)
+}
+
+/// Counts elements of `input` for which `pred` returns `true`. Every worker counts matches of the elements it takes
+/// from the queue, and the counts are added up once workers are done, so no output is collected. Use
+/// [divide_equal_count] if cost of `pred` is the same for every element.
+///
+/// ```
+/// use and_conquer::divide_count;
+///
+/// assert_eq!(divide_count((0..1000).collect(), |x| x % 3 == 0), 334);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_count<P, T>(input: Vec<T>, pred: P) -> usize
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_count(input, pred)
+}
+
+/// Same as [divide_count], but splits work evenly like [divide_equal_work]
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_count<P, T>(input: Vec<T>, pred: P) -> usize
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_count(input, pred)
+}
+
+/// Returns the element of `input` with the maximum key, or `None` if `input` is empty. Keys are computed once for
+/// every element in parallel, and each worker keeps only the best element of its chunk, so no keys are collected. If
+/// several elements are equally maximum, the last one is returned, same as with [Iterator::max_by_key].
+///
+/// ```
+/// use and_conquer::divide_max_by_key;
+///
+/// let words: Vec<String> = vec!["a".into(), "ccc".into(), "bb".into(), "ddd".into()];
+/// assert_eq!(divide_max_by_key(words, |word| word.len()).unwrap(), "ddd");
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_max_by_key<F, T, K>(input: Vec<T>, key: F) -> Option<T>
+where
+    T: Send + 'static,
+    K: Ord + Send + 'static,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_max_by_key(input, key)
+}
+
+/// Same as [divide_max_by_key], but returns the element with the minimum key. If several elements are equally
+/// minimum, the first one is returned, same as with [Iterator::min_by_key].
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_min_by_key<F, T, K>(input: Vec<T>, key: F) -> Option<T>
+where
+    T: Send + 'static,
+    K: Ord + Send + 'static,
+    F: Fn(&T) -> K + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_min_by_key(input, key)
+}
+
+/// Same as [divide_max_by_key], but compares elements with `compare`, same as [Iterator::max_by]
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_max_by<F, T>(input: Vec<T>, compare: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: Fn(&T, &T) -> std::cmp::Ordering + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_max_by(input, compare)
+}
+
+/// Same as [divide_min_by_key], but compares elements with `compare`, same as [Iterator::min_by]
+///
+/// # Panics
+///
+/// Same as [divide_fold].
+pub fn divide_min_by<F, T>(input: Vec<T>, compare: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: Fn(&T, &T) -> std::cmp::Ordering + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_min_by(input, compare)
+}
+
+/// Same as [divide_work], but `f` produces any amount of results for an element, which are flattened into a single
+/// output: all results of the first element, then all results of the second one, and so on. Workers flatten results
+/// of their elements as they go, so nothing is allocated per element.
+///
+/// ```
+/// use and_conquer::divide_flat_map;
+///
+/// let docs: Vec<String> = vec!["a b".into(), "".into(), "c".into()];
+/// let words = divide_flat_map(docs, |doc| doc.split_whitespace().map(String::from).collect::<Vec<_>>());
+/// assert_eq!(words, ["a", "b", "c"]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_flat_map<F, T, I>(input: Vec<T>, f: F) -> Vec<I::Item>
+where
+    T: Send + 'static,
+    I: IntoIterator + 'static,
+    I::Item: Send + 'static,
+    F: Fn(T) -> I + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_flat_map(input, f)
+}
+
+/// Same as [divide_work], but keeps only the results of `f` that aren't `None`, in the same relative order as their
+/// elements in `input`. Unlike filtering the output of [divide_work], there is no intermediate vector of `Option`s.
+///
+/// ```
+/// use and_conquer::divide_filter_map;
+///
+/// let lines: Vec<String> = vec!["1".into(), "x".into(), "3".into()];
+/// assert_eq!(divide_filter_map(lines, |line| line.parse::<u32>().ok()), [1, 3]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_filter_map<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Option<R> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_filter_map(input, f)
+}
+
+/// Same as [divide_work], but keeps the elements of `input` for which `pred` returns `true` instead of producing new
+/// values. Kept elements are in the same relative order as in `input`.
+///
+/// ```
+/// use and_conquer::divide_filter;
+///
+/// let primes = divide_filter((2..100u32).collect(), |&n| (2..n).all(|d| n % d != 0));
+/// assert_eq!(primes[..5], [2, 3, 5, 7, 11]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_filter<P, T>(input: Vec<T>, pred: P) -> Vec<T>
+where
+    T: Send + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_filter(input, pred)
+}
+
+/// Same as [divide_equal_work], but mutates elements of `data` in place instead of producing new values. `data` is
+/// split into contiguous chunks of equal size, which are processed on scoped threads, so neither `data` nor `f` need
+/// to be `'static`, and nothing is allocated for the output. [WorkConfig::partition] doesn't apply.
+///
+/// ```
+/// use and_conquer::divide_for_each_mut;
+///
+/// let mut data: Vec<f64> = (0..1000).map(f64::from).collect();
+/// let max = 999.0;
+/// divide_for_each_mut(&mut data, |x| *x /= max);
+/// assert_eq!(data[999], 1.0);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_equal_work]. Elements that were processed before the panic stay mutated.
+pub fn divide_for_each_mut<F, T>(data: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Send + Sync,
+{
+    WorkConfig::default().divide_for_each_mut(data, f)
+}
+
+/// Same as [divide_for_each_mut], but with dynamic scheduling of [divide_work], which works better when cost of `f`
+/// varies greatly between elements. Workers take references to the elements from a shared queue, so a vector of them
+/// is allocated, but nothing is allocated for the output.
+///
+/// # Panics
+///
+/// Same as [divide_work]. Elements that were processed before the panic stay mutated.
+pub fn divide_for_each_mut_dynamic<F, T>(data: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Send + Sync,
+{
+    WorkConfig::default().divide_for_each_mut_dynamic(data, f)
+}
+
+/// Splits work between threads if amount of elements in `input` is greater than or equal to [parallel_threshold].
+/// This function schedules work evenly between each thread, but scheduling comes with extra overhead. If work required
+/// to complete `f` is expected to be equal regardless of input value, it is best to use [divide_equal_work].
+///
+/// # Panics
+///
+/// If `f` panics, other workers stop picking up new elements, and the panic is propagated to the caller with its
+/// original payload once all workers have finished. See [WorkConfig::panic_policy] for alternatives. Also panics if
+/// the OS fails to create a worker thread, use [divide_work_checked] to handle this case.
+#[inline]
+pub fn divide_work<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work(input, f)
+}
+
+/// Same as [divide_work], but takes any input with an exact amount of elements, like a `VecDeque`, a boxed slice or
+/// `(0..n).map(...)`. Elements are processed and returned in iteration order. Input is collected into a `Vec` of
+/// exact capacity before work is split, reusing the buffer if `input` already is a `Vec`.
+///
+/// ```
+/// use and_conquer::divide_work_iter;
+/// use std::collections::VecDeque;
+///
+/// let input: VecDeque<u64> = (0..1000).collect();
+/// assert_eq!(divide_work_iter(input, |x| x * 2)[999], 1998);
+/// assert_eq!(divide_work_iter((0..10).map(|x| x + 1), |x| x * 2)[0], 2);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+#[inline]
+pub fn divide_work_iter<I, F, T, R>(input: I, f: F) -> Vec<R>
+where
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_iter(input, f)
+}
+
+/// Same as [divide_work], but also reports how many elements each thread processed and how long it was busy. Results
+/// are the same, collecting statistics costs a couple of clock readings per element.
+///
+/// ```
+/// use and_conquer::divide_work_with_stats;
+///
+/// let (output, stats) = divide_work_with_stats((0..1000).collect(), |x| x * 2);
+/// assert_eq!(output.len(), 1000);
+/// assert_eq!(stats.items(), 1000);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_with_stats<F, T, R>(input: Vec<T>, f: F) -> (Vec<R>, WorkStats)
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_with_stats(input, f)
+}
+
+/// Same as [divide_work], but elements are processed starting from the most expensive ones according to `cost`,
+/// which is an estimate of how long `f` takes for an element. Cheap elements are left for the end, so workers finish at
+/// roughly the same time instead of waiting for a single worker that picked up an expensive element last. `cost` is
+/// computed for every element on the calling thread before work is split.
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_by_cost<C, F, T, R>(input: Vec<T>, cost: C, f: F) -> Vec<R>
+where
+    C: Fn(&T) -> u64,
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_by_cost(input, cost, f)
+}
+
+/// Same as [divide_work], but results are returned in unspecified order, which may change from call to call. Each
+/// worker collects results of the elements it processed, and these are concatenated in whatever order workers finish,
+/// so there is no index tracking or shared output buffer. Use it when order doesn't matter, for example when results
+/// are summed or collected into a map.
+///
+/// ```
+/// use and_conquer::divide_work_unordered;
+///
+/// let output = divide_work_unordered((1..=100).collect(), |x: u64| x * 2);
+/// assert_eq!(output.iter().sum::<u64>(), 10_100);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_unordered<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_unordered(input, f)
+}
+
+/// Same as [divide_work], but with work stealing instead of a single shared queue. Each worker starts with a
+/// contiguous range of `input`, as with [divide_equal_work], and processes it from its end. Once its own range is
+/// exhausted, a worker steals the back half of the remaining range of another worker. Uniform work is scheduled nearly
+/// as cheaply as with [divide_equal_work], while skewed work is still balanced between workers.
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_stealing<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_stealing(input, f)
+}
+
+/// Same as [divide_work], but workers share `input` instead of taking elements out of it, and `f` gets elements by
+/// reference. Workers claim elements with a single atomic increment instead of locking a queue, which makes
+/// scheduling much cheaper when `f` does little work. `input` is anything that converts into `Arc<[T]>`, such as a
+/// `Vec<T>`. Passing an `Arc<[T]>` lets the caller keep using the input afterwards.
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_shared<F, T, R>(input: impl Into<Arc<[T]>>, f: F) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_shared(input, f)
+}
+
+/// Same as [divide_work], but every worker creates state with `init` once, which `f` gets by mutable reference along
+/// with each element that the worker processes. Useful for scratch buffers and contexts that are expensive to create
+/// per element and can't be shared between threads. State doesn't need to be [Send], since it never leaves the worker.
+/// If work isn't split, the calling thread creates state of its own once.
+///
+/// ```
+/// use and_conquer::divide_work_init;
+///
+/// let lines: Vec<String> = (0..1000).map(|x| format!("line {}", x)).collect();
+/// let lengths = divide_work_init(lines, String::new, |scratch, line| {
+///     scratch.clear();
+///     scratch.push_str(&line.to_uppercase());
+///     scratch.len()
+/// });
+/// assert_eq!(lengths[999], 8);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work]. Panics of `init` are propagated the same way.
+pub fn divide_work_init<I, F, T, R, St>(input: Vec<T>, init: I, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    St: 'static,
+    I: Fn() -> St + Send + Sync + 'static,
+    F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_init(input, init, f)
+}
+
+/// Same as [divide_work_init], but splits `input` into equal chunks, same as [divide_equal_work]. Every chunk is
+/// processed by a worker of its own, so `init` runs once per chunk.
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_work_init<I, F, T, R, St>(input: Vec<T>, init: I, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    St: 'static,
+    I: Fn() -> St + Send + Sync + 'static,
+    F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work_init(input, init, f)
+}
+
+/// Same as [divide_work], but `f` also gets the index of every element in `input`. Indices are the original
+/// positions of the elements regardless of the order in which they are processed, so there is no need to pair them
+/// up with their indices before the call.
+///
+/// ```
+/// use and_conquer::divide_work_enumerate;
+///
+/// let words = vec!["a", "b", "c"];
+/// let output = divide_work_enumerate(words, |idx, word| format!("{}{}", word, idx));
+/// assert_eq!(output, ["a0", "b1", "c2"]);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_enumerate<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(usize, T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_enumerate(input, f)
+}
+
+/// Same as [divide_work_enumerate], but splits `input` into equal chunks, same as [divide_equal_work]
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_work_enumerate<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(usize, T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work_enumerate(input, f)
+}
+
+/// Same as [divide_work_init], but states are handed back to the caller along with the results once workers are done,
+/// which lets them accumulate per-thread data, such as statistics or filters, without synchronization. Order of the
+/// states is unspecified. There is a state for every worker that was started, and for the calling thread if it
+/// processed some elements on its own, so every element is covered by exactly one of them.
+///
+/// ```
+/// use and_conquer::divide_work_with_state;
+///
+/// let (output, counts) = divide_work_with_state((0..1000).collect(), || 0, |count, x: u32| {
+///     *count += 1;
+///     x * 2
+/// });
+/// assert_eq!(output[999], 1998);
+/// assert_eq!(counts.iter().sum::<usize>(), 1000);
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work_init].
+pub fn divide_work_with_state<I, F, T, R, St>(input: Vec<T>, init: I, f: F) -> (Vec<R>, Vec<St>)
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    St: Send + 'static,
+    I: Fn() -> St + Send + Sync + 'static,
+    F: Fn(&mut St, T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_with_state(input, init, f)
+}
+
+/// Same as [divide_work], but work is done on scoped threads, so elements, results and `f` don't need to be `'static`,
+/// and may borrow locals of the caller. Threads are started for every call instead of being taken from the pool,
+/// which costs some time on small inputs.
+///
+/// ```
+/// use and_conquer::divide_work_scoped;
+///
+/// let buffer = String::from("alpha beta gamma");
+/// let prefix = String::from("word: ");
+/// let words: Vec<&str> = buffer.split(' ').collect();
+/// let output = divide_work_scoped(words, |word| format!("{}{}", prefix, word));
+/// assert_eq!(output[1], "word: beta");
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_scoped<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_work_scoped(input, f)
+}
+
+/// Same as [divide_equal_work], but work is done on scoped threads, same as with [divide_work_scoped]
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_work_scoped<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_equal_work_scoped(input, f)
+}
+
+/// Same as [divide_work_shared], but borrows `input`, which the caller keeps using afterwards. Work is done on scoped
+/// threads, so neither `input` nor `f` need to be `'static`, and `f` may capture locals by reference. Results are in
+/// the order of `input`. With [Schedule::Static] the input is split into equal chunks, same as with
+/// [divide_equal_work_ref].
+///
+/// ```
+/// use and_conquer::divide_work_ref;
+///
+/// let records: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+/// let suffix = String::from("!");
+/// let marked = divide_work_ref(&records, |record| format!("{}{}", record, suffix));
+/// assert_eq!(marked[999], "999!");
+/// assert_eq!(records[999], "999");
+/// ```
+///
+/// # Panics
+///
+/// Same as [divide_work].
+pub fn divide_work_ref<F, T, R>(input: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_work_ref(input, f)
+}
+
+/// Same as [divide_work_ref], but splits `input` into contiguous chunks of equal size, same as [divide_equal_work].
+/// [WorkConfig::partition] doesn't apply.
+///
+/// # Panics
+///
+/// Same as [divide_equal_work].
+pub fn divide_equal_work_ref<F, T, R>(input: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Send + Sync,
+{
+    WorkConfig::default().divide_equal_work_ref(input, f)
+}
+
+/// Same as [divide_work], but splits work between threads only if amount of elements in `input` is greater than or
+/// equal to `threshold`. Threshold of `0` or `1` means that work is split whenever there is more than one element.
+pub fn divide_work_with_threshold<F, T, R>(input: Vec<T>, threshold: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::new().threshold(threshold).divide_work(input, f)
+}
+
+/// Same as [divide_work], but spawns `n_threads` worker threads instead of [default_parallelism] threads. `n_threads`
+/// of `0` or `1` means that work is done sequentially on the calling thread.
+pub fn divide_work_threads<F, T, R>(input: Vec<T>, n_threads: usize, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::new().threads(n_threads).divide_work(input, f)
+}
+
+/// Same as [divide_work], but catches panics of `f` for every element separately instead of propagating them. Result
+/// of each element is placed at its index in the output, with panic payload in place of results of elements for which
+/// `f` panicked.
+///
+/// Panics are caught with [std::panic::catch_unwind] as if `f` was [std::panic::UnwindSafe]. If `f` panics, state it
+/// shares between elements, like data behind a captured `Mutex` or `RefCell`, may be left inconsistent and observed by
+/// following elements. Panic hook is still called for each panic, so messages are printed as usual, and panics that
+/// abort the process can't be caught.
+pub fn divide_work_catch<F, T, R>(input: Vec<T>, f: F) -> Vec<std::thread::Result<R>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_catch(input, f)
+}
+
+/// Same as [divide_work], but catches the panic of `f` instead of propagating it, and reports index of the element
+/// that caused it. Workers stop picking up new elements as soon as `f` panics. If `f` panics for several elements
+/// concurrently, only the first caught panic is returned, which isn't necessarily the one with the lowest index.
+///
+/// Same unwind safety caveats as for [divide_work_catch] apply.
+///
+/// # Panics
+///
+/// Panics if the OS fails to create a worker thread, same as [divide_work].
+pub fn divide_work_catching<F, T, R>(input: Vec<T>, f: F) -> Result<Vec<R>, ItemPanic>
 where
     T: Send + 'static,
     R: Send + 'static,
-    F: Fn(T) -> R + Send + Sync + Clone + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
 {
-    if input.len() < PARALLEL_WORK_THRESHOLD {
-        input.into_iter().map(f).collect()
-    } else {
-        let cores = num_cpus::get();
-        let length = input.len();
-        let tasks_per_worker = length as f32 / cores as f32;
+    WorkConfig::default().divide_work_catching(input, f)
+}
 
-        let workers = (0..cores)
-            .rev()
-            .map(|core| input.split_off((tasks_per_worker * core as f32).round() as usize))
-            .map(|tasks| {
-                let f = f.clone();
-                std::thread::spawn(move || tasks.into_iter().map(f).collect::<Vec<_>>())
-            })
-            // Collect is required to actually spawn threads
-            .collect::<Vec<_>>();
+/// Same as [divide_work], but keeps the completed work if processing is interrupted. Results are placed at their
+/// indices in the output. If `f` panics, workers stop picking up new elements and the first caught panic is returned
+/// along with the output, where results of the panicked element and of the elements that weren't processed are
+/// `None`. Failures to spawn worker threads are handled as with [SpawnFailurePolicy::Degrade], so
+/// [WorkError::SpawnFailed] is returned only if no work could be done at all.
+///
+/// Same unwind safety caveats as for [divide_work_catch] apply.
+pub fn divide_work_partial<F, T, R>(input: Vec<T>, f: F) -> (Vec<Option<R>>, Option<WorkError>)
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_partial(input, f)
+}
 
-        // Workers are grouped in reversed order, but the value they return preserves original vector order. Joining their
-        // produced output is enough if order of workers is reversed back.
-        workers
-            .into_iter()
-            .rev()
-            .map(|w| {
-                w.join()
-                    // If function `f` panics, we should panic too, so that output vector has results for all input
-                    // values.
-                    .unwrap_or_else(|_| panic!("Worker thread panicked"))
-                    .into_iter()
-            })
-            .flatten()
-            .collect()
-    }
+/// Same as [divide_work], but stops picking up new elements once `timeout` has passed, and returns what was finished
+/// by then. Elements that are already being processed when the deadline passes are allowed to finish, so the call
+/// may take longer than `timeout`. Unprocessed elements are returned in [TimeoutOutcome::TimedOut], so that they can
+/// be retried later.
+pub fn divide_work_timeout<F, T, R>(input: Vec<T>, timeout: Duration, f: F) -> TimeoutOutcome<T, R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_timeout(input, timeout, f)
 }
 
-/// Splits work between threads if amount of elements in `input` is greater than or equal to
-/// `PARALLEL_WORK_THRESHOLD = 10`. This function schedules work evenly between each thread, but scheduling comes
-/// with extra overhead. If work required to complete `f` is expected to be equal regardless of input value, it is
-/// best to use [divide_equal_work].
-pub fn divide_work<F, T, R>(input: Vec<T>, f: F) -> Vec<R>
+/// Same as [divide_work], but stops picking up new elements once `token` is cancelled, for example from another
+/// thread. Elements that are already being processed when the token is cancelled are finished. If the token was
+/// cancelled before the whole input was processed, processed and unprocessed elements are returned in [Cancelled].
+pub fn divide_work_cancellable<F, T, R>(
+    input: Vec<T>,
+    token: &CancelToken,
+    f: F,
+) -> Result<Vec<R>, Cancelled<T, R>>
 where
     T: Send + 'static,
     R: Send + 'static,
-    F: Fn(T) -> R + Send + Sync + Clone + 'static,
-{
-    if input.len() < PARALLEL_WORK_THRESHOLD {
-        input.into_iter().map(f).collect()
-    } else {
-        let cores = num_cpus::get();
-        let length = input.len();
-
-        let workers = {
-            let queue = Arc::new(Mutex::new(input));
-
-            let mut workers = Vec::with_capacity(cores);
-            for _ in 0..cores {
-                let queue = queue.clone();
-                let f = f.clone();
-
-                workers.push(std::thread::spawn(move || {
-                    let mut res = Vec::new();
-                    loop {
-                        let value = {
-                            let mut q = queue.lock().unwrap();
-                            let val = q.pop();
-                            // At this point len already has element index, because it was decrememted with pop.
-                            // `idx` will be unused if `pop` returns `None`
-                            (q.len(), val)
-                        };
-                        if let (idx, Some(val)) = value {
-                            // SAFETY: `idx` must remain within `length` to prevent writing data out of array bounds
-                            res.push((idx, f(val)));
-                        } else {
-                            break;
-                        }
-                    }
-                    res
-                }));
-            }
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_cancellable(input, token, f)
+}
 
-            workers
-        };
+/// Same as [divide_work], but limits time spent on each element to `limit`. Elements that take longer are reported as
+/// [ItemTimedOut], while the rest of the input proceeds as usual. Running `f` can't be interrupted, so it is given
+/// [ItemCtx] to check whether it is out of time and give up early. Overruns are reported even if `f` doesn't check the
+/// deadline and eventually finishes, its result is discarded in this case.
+pub fn divide_work_with_deadline<F, T, R>(
+    input: Vec<T>,
+    limit: Duration,
+    f: F,
+) -> Vec<Result<R, ItemTimedOut>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T, &ItemCtx) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_with_deadline(input, limit, f)
+}
 
-        // SAFETY: capacity must be >= than used in `set_len`
-        let mut res = Vec::with_capacity(length);
-        let res_mut_ptr: *mut R = res.as_mut_ptr();
-
-        for w in workers {
-            match w.join() {
-                Ok(worker_res) => {
-                    for (idx, r) in worker_res {
-                        // SAFETY: 1) idx is obtained from input Vec enumeration, input and output vectors have
-                        //         the same length, so idx remains within bounds.
-                        //         2) ptr is a valid location to write, because it is obtained from preallocated
-                        //         vector with required capacity.
-                        unsafe {
-                            std::ptr::write(res_mut_ptr.add(idx), r);
-                        }
-                    }
-                }
-                // SAFETY: Necessary to prevent vector having uninitialized elements
-                Err(_) => panic!("Worker thread panicked"),
-            }
-        }
+/// Same as [divide_equal_work], but limits time spent on each element the same way as [divide_work_with_deadline]
+pub fn divide_equal_work_with_deadline<F, T, R>(
+    input: Vec<T>,
+    limit: Duration,
+    f: F,
+) -> Vec<Result<R, ItemTimedOut>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T, &ItemCtx) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work_with_deadline(input, limit, f)
+}
 
-        // SAFETY: 1) Allocated with capacity `length`
-        //         2) Workers produce value for each element, initialization occurs after joining worker threads.
-        //         In case function `f` panics, this statement is unreachable.
-        unsafe { res.set_len(length) }
+/// Same as [divide_work], but for fallible `f`, and substitutes results of the elements for which `f` failed or
+/// panicked with the value produced by `fallback`. Output has results for the whole input in input order, so failures
+/// don't change its shape. Same unwind safety caveats as for [divide_work_catch] apply.
+pub fn divide_work_or<F, G, T, R, E>(input: Vec<T>, f: F, fallback: G) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    E: 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+    G: Fn(&PanicOrError<E>) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_or(input, f, fallback)
+}
 
-        res
-    }
+/// Same as [divide_work_or], but substitutes results of the elements for which `f` failed or panicked with
+/// `R::default()`
+pub fn divide_work_or_default<F, T, R, E>(input: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Default + Send + 'static,
+    E: 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_or_default(input, f)
+}
+
+/// Same as [divide_work], but for fallible `f`. Returns results in input order if `f` succeeds for every element.
+/// Otherwise workers stop picking up new elements as soon as `f` fails, and the first encountered error is returned
+/// as [WorkError::ItemError]. If the OS fails to create a worker thread, [WorkError::SpawnFailed] is returned. Panics
+/// of `f` are propagated the same way as in [divide_work].
+pub fn try_divide_work<F, T, R, E>(input: Vec<T>, f: F) -> Result<Vec<R>, WorkError<E>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    E: Send + 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+{
+    WorkConfig::default().try_divide_work(input, f)
+}
+
+/// Same as [divide_equal_work], but for fallible `f`. Returns results in input order if `f` succeeds for every
+/// element. Otherwise each worker stops processing its chunk as soon as `f` fails on any of them, and the first
+/// encountered error is returned as [WorkError::ItemError]. If the OS fails to create a worker thread,
+/// [WorkError::SpawnFailed] is returned. Panics of `f` are propagated the same way as in [divide_equal_work].
+pub fn try_divide_equal_work<F, T, R, E>(input: Vec<T>, f: F) -> Result<Vec<R>, WorkError<E>>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    E: Send + 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+{
+    WorkConfig::default().try_divide_equal_work(input, f)
+}
+
+/// Same as [try_divide_work], but calls `f` for an element up to `retries` extra times if it fails, before treating
+/// the error as final. This is useful when `f` fails transiently, like on a flaky network. Retries happen on the same
+/// worker right away, and error of the last attempt is reported, see [WorkConfig::report_error] to change that.
+pub fn try_divide_work_with_retries<F, T, R, E>(
+    input: Vec<T>,
+    retries: usize,
+    f: F,
+) -> Result<Vec<R>, WorkError<E>>
+where
+    T: Clone + Send + 'static,
+    R: Send + 'static,
+    E: Send + 'static,
+    F: Fn(T) -> Result<R, E> + Send + Sync + 'static,
+{
+    WorkConfig::new()
+        .retries(retries)
+        .try_divide_work_retrying(input, f)
+}
+
+/// Same as [divide_work], but never unwinds into the caller, which makes it suitable for use behind FFI boundaries.
+/// Panics of `f` are caught for every element and reported as [WorkError::ItemPanicked] with the index of the element,
+/// failures to create worker threads are reported as [WorkError::SpawnFailed]. In both cases workers stop picking up
+/// new elements, and the error is returned once all of them have finished. Since panics never escape `f`, locks used
+/// by the scheduler can't be poisoned.
+///
+/// Panics are caught with [std::panic::catch_unwind], so the panic hook is still called and prints the message as
+/// usual. Replace it with [std::panic::set_hook] to suppress the messages. Panics that abort the process, for example
+/// with `panic = "abort"` profile setting, can't be caught. Same unwind safety caveats as for [divide_work_catch]
+/// apply.
+pub fn divide_work_checked<F, T, R>(input: Vec<T>, f: F) -> Result<Vec<R>, WorkError>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_work_checked(input, f)
+}
+
+/// Same as [divide_equal_work], but never unwinds into the caller. Errors are reported the same way as in
+/// [divide_work_checked], workers stop processing their chunks as soon as an error occurs.
+pub fn divide_equal_work_checked<F, T, R>(input: Vec<T>, f: F) -> Result<Vec<R>, WorkError>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    WorkConfig::default().divide_equal_work_checked(input, f)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     fn divide_numbers() {
@@ -151,6 +1855,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn divide_strings() {
         let input = vec![
             "walk", "show", "code", "enter", "etc", "etc", "etc", "etc", "etc", "etc", "etc", "etc",
@@ -170,6 +1875,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn divide_equal_strings() {
         let input = vec![
             "walk", "show", "code", "enter", "etc", "etc", "etc", "etc", "etc", "etc", "etc", "etc",
@@ -189,6 +1895,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn divide_undivisible() {
         let input = vec!["walk", "show", "code", "enter"];
 
@@ -207,6 +1914,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_closure)]
     fn divide_equal_undivisible() {
         let input = vec!["walk", "show", "code", "enter"];
 
@@ -223,4 +1931,638 @@ mod tests {
 
         assert_eq!(output, expected);
     }
+
+    /// Produces a closure that reports whether element was processed on the thread that created the closure
+    fn on_caller_thread() -> impl Fn(usize) -> bool + Send + Sync + 'static {
+        let caller = std::thread::current().id();
+        move |_| std::thread::current().id() == caller
+    }
+
+    // Thread count is fixed, so that work is split even on a single core machine. Calling thread takes part in the
+    // work, so only `divide_equal_work` reliably processes some elements on other threads: it always sends the first
+    // chunk to a spawned worker.
+
+    #[test]
+    fn threshold_below_len() {
+        let config = WorkConfig::new().threads(2).threshold(3);
+        assert!(config.decide(4).is_parallel());
+
+        let output = config.divide_equal_work((0..4).collect(), on_caller_thread());
+        assert_eq!(output, vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn threshold_equal_to_len() {
+        let config = WorkConfig::new().threads(2).threshold(4);
+        assert!(config.decide(4).is_parallel());
+
+        let output = config.divide_equal_work((0..4).collect(), on_caller_thread());
+        assert_eq!(output, vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn threshold_above_len() {
+        let config = WorkConfig::new().threads(2).threshold(5);
+        assert!(!config.decide(4).is_parallel());
+
+        let output = config.divide_work((0..4).collect(), on_caller_thread());
+        assert_eq!(output, vec![true; 4]);
+
+        let output = config.divide_equal_work((0..4).collect(), on_caller_thread());
+        assert_eq!(output, vec![true; 4]);
+    }
+
+    #[test]
+    fn threshold_zero_and_one() {
+        for threshold in 0..2 {
+            let config = WorkConfig::new().threads(2).threshold(threshold);
+            assert!(config.decide(2).is_parallel());
+            assert!(!config.decide(1).is_parallel());
+
+            let output = config.divide_equal_work((0..2).collect(), on_caller_thread());
+            assert_eq!(output, vec![false, true]);
+
+            let output = config.divide_work((0..1).collect(), on_caller_thread());
+            assert_eq!(output, vec![true]);
+            let output = config.divide_equal_work((0..1).collect(), on_caller_thread());
+            assert_eq!(output, vec![true]);
+        }
+    }
+
+    #[test]
+    fn global_threshold() {
+        // Other tests running in parallel rely on the default threshold to both split and not split work, so the
+        // global is only lowered for inputs shorter than the default and restored afterwards.
+        let config = WorkConfig::new().threads(2);
+        assert_eq!(parallel_threshold(), PARALLEL_WORK_THRESHOLD);
+        assert!(!config.decide(4).is_parallel());
+
+        set_parallel_threshold(4);
+        assert_eq!(parallel_threshold(), 4);
+        assert!(!config.decide(3).is_parallel());
+        assert!(config.decide(4).is_parallel());
+        // Per-call override still takes precedence
+        assert!(!config.clone().threshold(5).decide(4).is_parallel());
+
+        set_parallel_threshold(0);
+        assert!(config.decide(2).is_parallel());
+
+        set_parallel_threshold(PARALLEL_WORK_THRESHOLD);
+        assert!(!config.decide(4).is_parallel());
+    }
+
+    #[test]
+    fn threshold_preserves_order() {
+        let input: Vec<usize> = (0..100).collect();
+        let expected: Vec<usize> = (1..101).collect();
+
+        assert_eq!(
+            divide_work_with_threshold(input.clone(), 2, |x| x + 1),
+            expected
+        );
+        assert_eq!(
+            divide_equal_work_with_threshold(input, 2, |x| x + 1),
+            expected
+        );
+    }
+
+    /// Produces a closure that returns id of the thread it was called on
+    fn thread_id() -> impl Fn(usize) -> std::thread::ThreadId + Send + Sync + 'static {
+        |_| std::thread::current().id()
+    }
+
+    fn distinct<T: PartialEq>(mut values: Vec<T>) -> Vec<T> {
+        values.dedup();
+        values
+    }
+
+    #[test]
+    fn threads_sequential() {
+        for n_threads in 0..2 {
+            let output = divide_work_threads((0..12).collect(), n_threads, on_caller_thread());
+            assert_eq!(output, vec![true; 12]);
+
+            let output =
+                divide_equal_work_threads((0..12).collect(), n_threads, on_caller_thread());
+            assert_eq!(output, vec![true; 12]);
+        }
+    }
+
+    #[test]
+    fn threads_exceed_len() {
+        let input: Vec<usize> = (0..12).collect();
+        let expected: Vec<usize> = (1..13).collect();
+
+        assert_eq!(divide_work_threads(input.clone(), 64, |x| x + 1), expected);
+        assert_eq!(divide_equal_work_threads(input, 64, |x| x + 1), expected);
+    }
+
+    #[test]
+    fn equal_threads_chunks() {
+        let output = divide_equal_work_threads((0..12).collect(), 3, thread_id());
+        assert_eq!(distinct(output).len(), 3);
+
+        // One chunk per element if there are not enough elements
+        let output = divide_equal_work_threads((0..12).collect(), 64, thread_id());
+        assert_eq!(distinct(output).len(), 12);
+    }
+
+    /// Produces a closure that fails for `bad` element and counts how many times it was called
+    fn fail_at(
+        bad: usize,
+        calls: &Arc<AtomicUsize>,
+    ) -> impl Fn(usize) -> Result<usize, String> + Send + Sync {
+        let calls = calls.clone();
+        move |x| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            if x == bad {
+                Err(format!("bad {}", x))
+            } else {
+                Ok(x * 2)
+            }
+        }
+    }
+
+    #[test]
+    fn try_all_ok() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let expected: Vec<usize> = (0..100).map(|x| x * 2).collect();
+
+        let output = try_divide_work((0..100).collect(), fail_at(100, &calls));
+        assert_eq!(output.unwrap(), expected.clone());
+        let output = WorkConfig::new()
+            .threads(4)
+            .try_divide_work((0..100).collect(), fail_at(100, &calls));
+        assert_eq!(output.unwrap(), expected);
+        assert_eq!(calls.load(Ordering::Relaxed), 200);
+    }
+
+    #[test]
+    fn try_error_early() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let output = WorkConfig::new()
+            .threads(4)
+            .try_divide_work((0..100).collect(), fail_at(3, &calls));
+        assert!(matches!(output, Err(WorkError::ItemError(err)) if err == "bad 3"));
+    }
+
+    #[test]
+    fn try_error_late() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let output = WorkConfig::new()
+            .threads(4)
+            .try_divide_work((0..100).collect(), fail_at(97, &calls));
+        assert!(matches!(output, Err(WorkError::ItemError(err)) if err == "bad 97"));
+    }
+
+    #[test]
+    fn try_sequential_short_circuit() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let output = WorkConfig::new()
+            .threshold(1000)
+            .try_divide_work((0..100).collect(), fail_at(3, &calls));
+        assert!(matches!(output, Err(WorkError::ItemError(err)) if err == "bad 3"));
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct CustomPayload(usize);
+
+    /// Produces a closure that panics with `panic` on element `7`
+    fn panics_at_7(panic: fn(usize)) -> impl Fn(usize) -> usize + Send + Sync + 'static {
+        move |x| {
+            if x == 7 {
+                panic(x);
+            }
+            x
+        }
+    }
+
+    #[test]
+    fn panic_payload_propagated() {
+        let config = WorkConfig::new().threads(4);
+        let caught_payloads = |panic: fn(usize)| {
+            let dynamic = std::panic::catch_unwind(|| {
+                config.divide_work((0..20).collect(), panics_at_7(panic))
+            });
+            let equal = std::panic::catch_unwind(|| {
+                config.divide_equal_work((0..20).collect(), panics_at_7(panic))
+            });
+            vec![dynamic.unwrap_err(), equal.unwrap_err()]
+        };
+
+        for payload in caught_payloads(|_| panic!("static message")) {
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&"static message"));
+        }
+        for payload in caught_payloads(|x| panic!("formatted message {}", x)) {
+            assert_eq!(
+                payload.downcast_ref::<String>().map(String::as_str),
+                Some("formatted message 7")
+            );
+        }
+        for payload in caught_payloads(|x| std::panic::panic_any(CustomPayload(x))) {
+            assert_eq!(
+                payload.downcast_ref::<CustomPayload>(),
+                Some(&CustomPayload(7))
+            );
+        }
+    }
+
+    #[test]
+    fn catch_item_panics() {
+        let f = |x: usize| {
+            if x == 3 || x == 7 {
+                panic!("bad item {}", x);
+            }
+            x * 2
+        };
+        let outputs = vec![
+            divide_work_catch((0..20).collect(), f),
+            WorkConfig::new()
+                .threads(4)
+                .divide_work_catch((0..20).collect(), f),
+        ];
+
+        for output in outputs {
+            assert_eq!(output.len(), 20);
+            for (idx, res) in output.into_iter().enumerate() {
+                match res {
+                    Ok(r) => {
+                        assert!(idx != 3 && idx != 7);
+                        assert_eq!(r, idx * 2);
+                    }
+                    Err(payload) => {
+                        let msg = payload.downcast_ref::<String>().unwrap();
+                        assert_eq!(*msg, format!("bad item {}", idx));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn checked_spawns() {
+        let expected: Vec<usize> = (1..101).collect();
+        assert_eq!(
+            divide_work_checked((0..100).collect(), |x| x + 1).unwrap(),
+            expected
+        );
+        assert_eq!(
+            divide_equal_work_checked((0..100).collect(), |x| x + 1).unwrap(),
+            expected
+        );
+
+        let config = WorkConfig::new().threads(4);
+        assert_eq!(
+            config
+                .divide_work_checked((0..100).collect(), |x| x + 1)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            config
+                .divide_equal_work_checked((0..100).collect(), |x| x + 1)
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn catching_reports_index() {
+        let f = |x: usize| {
+            assert_ne!(x, 1234, "bad item");
+            x
+        };
+        let errors = vec![
+            divide_work_catching((0..2000).collect(), f).unwrap_err(),
+            WorkConfig::new()
+                .threads(4)
+                .divide_work_catching((0..2000).collect(), f)
+                .unwrap_err(),
+        ];
+        for err in errors {
+            assert_eq!(err.index, 1234);
+            assert!(err.message().unwrap().contains("bad item"));
+            assert!(err.to_string().starts_with("element 1234 panicked: "));
+        }
+
+        let output = WorkConfig::new()
+            .threads(4)
+            .divide_work_catching((0..2000).collect(), |x: usize| x + 1);
+        assert_eq!(output.unwrap(), (1..2001).collect::<Vec<_>>());
+    }
+
+    /// Handle that can't be cloned, like a connection
+    struct Unique(std::sync::Mutex<usize>);
+
+    /// Closure that owns a [Unique], so it isn't [Clone] either
+    fn uncloneable() -> impl Fn(usize) -> usize + Send + Sync {
+        let handle = Unique(std::sync::Mutex::new(0));
+        move |x| {
+            *handle.0.lock().unwrap() += 1;
+            x * 2
+        }
+    }
+
+    #[test]
+    fn closure_without_clone() {
+        let expected: Vec<usize> = (0..1000).map(|x| x * 2).collect();
+        assert_eq!(divide_work((0..1000).collect(), uncloneable()), expected);
+        assert_eq!(
+            divide_equal_work((0..1000).collect(), uncloneable()),
+            expected
+        );
+        let config = WorkConfig::new().threads(4);
+        assert_eq!(
+            config.divide_work((0..1000).collect(), uncloneable()),
+            expected
+        );
+        assert_eq!(
+            config.divide_work_stealing((0..1000).collect(), uncloneable()),
+            expected
+        );
+        let f = uncloneable();
+        let output = config.try_divide_work((0..1000).collect(), move |x| Ok::<_, ()>(f(x)));
+        assert_eq!(output.unwrap(), expected);
+    }
+
+    /// Fails the first `failures` attempts for every multiple of 10, reporting the attempt number
+    fn flaky(
+        failures: usize,
+        attempts: &Arc<Vec<AtomicUsize>>,
+    ) -> impl Fn(usize) -> Result<usize, usize> + Clone {
+        let attempts = attempts.clone();
+        move |x| {
+            let attempt = attempts[x].fetch_add(1, Ordering::Relaxed);
+            if x % 10 == 0 && attempt < failures {
+                Err(attempt)
+            } else {
+                Ok(x * 2)
+            }
+        }
+    }
+
+    #[test]
+    fn retries() {
+        let expected: Vec<usize> = (0..100).map(|x| x * 2).collect();
+        let attempts = || Arc::new((0..100).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>());
+
+        let output = try_divide_work_with_retries((0..100).collect(), 1, flaky(1, &attempts()));
+        assert_eq!(output.unwrap(), expected.clone());
+
+        let counts = attempts();
+        let config = WorkConfig::new().threads(4).retries(2);
+        let output = config.try_divide_work_retrying((0..100).collect(), flaky(2, &counts));
+        assert_eq!(output.unwrap(), expected);
+        for (x, count) in counts.iter().enumerate() {
+            let expected = if x % 10 == 0 { 3 } else { 1 };
+            assert_eq!(count.load(Ordering::Relaxed), expected);
+        }
+
+        let output = config.try_divide_work_retrying((0..100).collect(), flaky(3, &attempts()));
+        assert!(matches!(output, Err(WorkError::ItemError(2))));
+        let output = config
+            .clone()
+            .report_error(ReportedError::First)
+            .try_divide_work_retrying((0..100).collect(), flaky(3, &attempts()));
+        assert!(matches!(output, Err(WorkError::ItemError(0))));
+    }
+
+    #[test]
+    fn panic_policy_skip_item() {
+        let f = |x: usize| {
+            if x == 3 || x == 7 {
+                panic!("bad item {}", x);
+            }
+            x * 2
+        };
+        let expected: Vec<usize> = (0..20)
+            .filter(|x| *x != 3 && *x != 7)
+            .map(|x| x * 2)
+            .collect();
+        let expected_skipping: Vec<Option<usize>> = (0..20)
+            .map(|x| Some(x * 2).filter(|_| x != 3 && x != 7))
+            .collect();
+
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threshold(1000),
+        ] {
+            let skipping = config.clone().panic_policy(PanicPolicy::SkipItem);
+            assert_eq!(skipping.divide_work((0..20).collect(), f), expected);
+            assert_eq!(skipping.divide_equal_work((0..20).collect(), f), expected);
+
+            assert_eq!(
+                config.divide_work_skipping((0..20).collect(), f),
+                expected_skipping
+            );
+            assert_eq!(
+                config.divide_equal_work_skipping((0..20).collect(), f),
+                expected_skipping
+            );
+            assert_eq!(
+                skipping.divide_work_skipping((0..20).collect(), f),
+                expected_skipping
+            );
+        }
+    }
+
+    #[test]
+    fn panic_policy_propagate() {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let counter = processed.clone();
+        let config = WorkConfig::new()
+            .threads(2)
+            .panic_policy(PanicPolicy::Propagate);
+        // The last element is one of the first to be processed
+        let res = std::panic::catch_unwind(|| {
+            config.divide_work((0..1000).collect(), move |x| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                assert_ne!(x, 999, "bad item");
+                x
+            })
+        });
+
+        let payload = res.unwrap_err().downcast::<String>().unwrap();
+        assert!(payload.contains("bad item"));
+        assert_eq!(processed.load(Ordering::Relaxed), 1000);
+    }
+
+    #[test]
+    fn partial_results() {
+        let processed: Arc<Vec<AtomicBool>> =
+            Arc::new((0..1000).map(|_| AtomicBool::new(false)).collect());
+        let flags = processed.clone();
+        // The last element is one of the first to be processed, so most of the input is left unprocessed
+        let (output, failure) = WorkConfig::new().threads(4).divide_work_partial(
+            (0..1000).collect(),
+            move |x: usize| {
+                assert_ne!(x, 990, "bad item");
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                flags[x].store(true, Ordering::Relaxed);
+                x * 2
+            },
+        );
+
+        match failure {
+            Some(WorkError::ItemPanicked { index, .. }) => assert_eq!(index, 990),
+            other => panic!("unexpected failure: {:?}", other),
+        }
+        assert_eq!(output.len(), 1000);
+        assert!(output.iter().filter(|r| r.is_none()).count() > 500);
+        for (idx, res) in output.into_iter().enumerate() {
+            let expected = Some(idx * 2).filter(|_| processed[idx].load(Ordering::Relaxed));
+            assert_eq!(res, expected);
+        }
+
+        let (output, failure) = divide_work_partial((0..100).collect(), |x| x * 2);
+        assert!(failure.is_none());
+        assert_eq!(output, (0..100).map(|x| Some(x * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn try_spawn_failure_is_error() {
+        // Stack size that is too large to be allocated
+        let output = WorkConfig::new()
+            .threads(4)
+            .stack_size(usize::MAX)
+            .try_divide_work((0..100).collect(), |x: usize| Ok::<_, ()>(x));
+        assert!(matches!(output, Err(WorkError::SpawnFailed(_))));
+    }
+
+    #[test]
+    fn checked_panics_are_errors() {
+        let config = WorkConfig::new().threads(4);
+        let errors = vec![
+            divide_work_checked((0..100).collect(), panics_at_7(|_| panic!("bad item"))),
+            config.divide_work_checked((0..100).collect(), panics_at_7(|_| panic!("bad item"))),
+            divide_equal_work_checked((0..100).collect(), panics_at_7(|_| panic!("bad item"))),
+            config
+                .divide_equal_work_checked((0..100).collect(), panics_at_7(|_| panic!("bad item"))),
+        ];
+        for err in errors {
+            let err = err.unwrap_err();
+            assert!(matches!(err, WorkError::ItemPanicked { index: 7, .. }));
+            assert_eq!(err.panic_message(), Some("bad item"));
+        }
+    }
+
+    fn sleepy(x: usize) -> usize {
+        std::thread::sleep(Duration::from_millis(10));
+        x * 2
+    }
+
+    #[test]
+    fn timeout_completed() {
+        let expected: Vec<usize> = (0..20).map(|x| x * 2).collect();
+        let config = WorkConfig::new().threads(4);
+        let output = config.divide_work_timeout((0..20).collect(), Duration::from_secs(60), sleepy);
+        assert_eq!(output, TimeoutOutcome::Completed(expected.clone()));
+        let output = divide_work_timeout((0..20).collect(), Duration::from_secs(60), sleepy);
+        assert_eq!(output, TimeoutOutcome::Completed(expected));
+    }
+
+    #[test]
+    fn timeout_timed_out() {
+        for config in [WorkConfig::new().threads(2), WorkConfig::new().threads(1)] {
+            // Takes at least 250 ms with 2 threads, but only 50 ms are given
+            let output =
+                config.divide_work_timeout((0..50).collect(), Duration::from_millis(50), sleepy);
+            match output {
+                TimeoutOutcome::TimedOut {
+                    completed,
+                    remaining,
+                } => {
+                    assert_eq!(completed.len(), 50);
+                    assert!(!remaining.is_empty());
+                    let unprocessed: Vec<usize> =
+                        (0..50).filter(|idx| completed[*idx].is_none()).collect();
+                    assert_eq!(remaining, unprocessed);
+                    for (idx, res) in completed.into_iter().enumerate() {
+                        assert!(res.map_or(true, |r| r == idx * 2));
+                    }
+                }
+                TimeoutOutcome::Completed(_) => panic!("finished unexpectedly fast"),
+            }
+        }
+    }
+
+    #[test]
+    fn timeout_work_error() {
+        let config = WorkConfig::new().threads(2);
+        let output =
+            config.divide_work_timeout((0..50).collect(), Duration::from_millis(50), sleepy);
+        let output: Result<_, WorkError> = output.into_result();
+        assert!(matches!(output, Err(WorkError::TimedOut)));
+        let output = config.divide_work_timeout((0..4).collect(), Duration::from_secs(60), sleepy);
+        let output: Result<_, WorkError> = output.into_result();
+        assert_eq!(output.unwrap(), [0, 2, 4, 6]);
+    }
+
+    fn fails_for_odd(x: i64) -> Result<i64, String> {
+        match x % 4 {
+            1 => panic!("bad item {}", x),
+            3 => Err(format!("bad item {}", x)),
+            _ => Ok(x),
+        }
+    }
+
+    #[test]
+    fn fallback_values() {
+        let expected: Vec<i64> = (0..100).map(|x| if x % 2 == 1 { -1 } else { x }).collect();
+        let fallback = |err: &PanicOrError<String>| {
+            match err {
+                PanicOrError::Panicked(_) => {
+                    assert!(err.panic_message().unwrap().starts_with("bad item"))
+                }
+                PanicOrError::Failed(err) => assert!(err.starts_with("bad item")),
+            }
+            -1
+        };
+
+        assert_eq!(
+            divide_work_or((0..100).collect(), fails_for_odd, fallback),
+            expected
+        );
+        let output = WorkConfig::new().threads(4).divide_work_or(
+            (0..100).collect(),
+            fails_for_odd,
+            fallback,
+        );
+        assert_eq!(output, expected);
+
+        let expected: Vec<i64> = (0..100).map(|x| if x % 2 == 1 { 0 } else { x }).collect();
+        assert_eq!(
+            divide_work_or_default((0..100).collect(), fails_for_odd),
+            expected
+        );
+    }
+
+    #[test]
+    fn for_each_visits_every_element() {
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let visited = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let push = {
+                let visited = visited.clone();
+                move |x: usize| visited.lock().unwrap().push(x)
+            };
+            config.divide_for_each((0..1000).collect(), push.clone());
+            config.divide_equal_for_each((1000..2000).collect(), push);
+            let mut visited = visited.lock().unwrap().clone();
+            visited.sort_unstable();
+            assert_eq!(visited, (0..2000).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn for_each_panic() {
+        WorkConfig::new()
+            .threads(4)
+            .divide_for_each((0..1000).collect(), |x| assert_ne!(x, 500, "bad item"));
+    }
 }
@@ -0,0 +1,282 @@
+//! NUMA awareness, enabled with [WorkConfig::numa](crate::WorkConfig::numa). Nodes are detected from sysfs, so only
+//! Linux is supported. Workers are spread over the nodes in contiguous groups and pinned to the cores of their node,
+//! and each group processes a contiguous region of the input. Linux allocates memory on the node of the thread that
+//! first touches it, so whatever `f` allocates stays local to the node as well.
+
+use crate::equal::chunk_start;
+use crate::output::{Output, Sink};
+use crate::queue::{Batching, Queue};
+use crate::worker::{self, CancelOnPanic, Spawner, StopFlag};
+use crate::{affinity, equal, SpawnError, WorkConfig};
+use std::fs;
+use std::sync::{Arc, OnceLock};
+
+/// Cores of every NUMA node that are available to the process, in order of the nodes. `None` if there is a single
+/// node or topology couldn't be detected, in which case there is nothing to gain from NUMA awareness.
+pub(crate) fn nodes() -> Option<&'static [Vec<usize>]> {
+    static NODES: OnceLock<Option<Vec<Vec<usize>>>> = OnceLock::new();
+    NODES.get_or_init(detect).as_deref()
+}
+
+fn detect() -> Option<Vec<Vec<usize>>> {
+    let available = affinity::available_cores()?;
+    let mut nodes = Vec::new();
+    for entry in fs::read_dir("/sys/devices/system/node").ok()?.flatten() {
+        let name = entry.file_name();
+        let node = name.to_str().and_then(|name| name.strip_prefix("node"));
+        let Some(Ok(node)) = node.map(str::parse::<usize>) else {
+            continue;
+        };
+        let list = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+        let mut cores = parse_cpulist(&list)?;
+        cores.retain(|core| available.contains(core));
+        if !cores.is_empty() {
+            nodes.push((node, cores));
+        }
+    }
+    nodes.sort_unstable();
+    (nodes.len() > 1).then(|| nodes.into_iter().map(|(_, cores)| cores).collect())
+}
+
+/// Parses list of cores in sysfs format, like `0-3,8,10-11`
+fn parse_cpulist(list: &str) -> Option<Vec<usize>> {
+    let mut cores = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cores.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cores.push(range.parse().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+/// Node and core of each of `workers` workers. Workers are spread evenly over the cores of all nodes, so that
+/// consecutive workers share a node, and every node gets amount of workers proportional to amount of its cores.
+fn assign(nodes: &[Vec<usize>], workers: usize) -> Vec<(usize, usize)> {
+    let cores: Vec<(usize, usize)> = nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(node, cores)| cores.iter().map(move |&core| (node, core)))
+        .collect();
+    (0..workers)
+        .map(|worker| cores[worker * cores.len() / workers])
+        .collect()
+}
+
+/// Same as `config`, but pins each worker to the core it is assigned
+fn pinned(config: &WorkConfig, assignment: &[(usize, usize)]) -> WorkConfig {
+    let cores: Vec<usize> = assignment.iter().map(|&(_, core)| core).collect();
+    config.clone().pin_cores(cores)
+}
+
+/// Parallel part of [divide_equal_work](crate::divide_equal_work) with NUMA awareness. Chunks are contiguous and
+/// processed in order of workers, so pinning the workers is enough to give each node a contiguous region of the input.
+pub(crate) fn divide_equal_work<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    f: F,
+    nodes: &[Vec<usize>],
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let assignment = assign(nodes, equal::chunk_count(config, input.len()));
+    equal::divide_equal_work(&pinned(config, &assignment), spawner, input, f)
+}
+
+/// Queue of the region of the input that belongs to a node
+struct NodeQueue<T> {
+    /// Index of the first element of the region in the original input
+    start: usize,
+    queue: Queue<T>,
+    batching: Batching,
+}
+
+/// Processes elements of the queue of node `own`, then helps other nodes with their queues once it is empty, until
+/// every queue is empty or work is cancelled. Results are written to `output` at indices of their elements in the
+/// original input. If `cancel_on_panic` is set, work is cancelled when `f` panics on any of the workers.
+fn process_queues<F, T, R>(
+    queues: &[NodeQueue<T>],
+    own: usize,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    f: &F,
+    output: &Sink<R>,
+) where
+    F: Fn(T) -> R,
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    let mut writer = output.writer();
+    for node in (0..queues.len()).map(|offset| (own + offset) % queues.len()) {
+        let NodeQueue {
+            start,
+            queue,
+            batching,
+        } = &queues[node];
+        while !stop.is_stopped() {
+            let Some(batch) = queue.pop(*batching) else {
+                break;
+            };
+            for (idx, val) in batch {
+                if stop.is_stopped() {
+                    break;
+                }
+                let r = f(val);
+                // SAFETY: Regions of the queues don't overlap, and every element is popped from its queue exactly once
+                unsafe { writer.write(start + idx, r) }
+            }
+        }
+    }
+}
+
+/// Parallel part of [divide_work](crate::divide_work) with NUMA awareness. Each node gets a queue of a contiguous
+/// region of the input proportional to amount of its workers, and its workers take elements from other queues only
+/// once their own one is empty. `input` is expected to be large enough to be split.
+pub(crate) fn divide_work<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    mut input: Vec<T>,
+    f: F,
+    nodes: &[Vec<usize>],
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+    let assignment = assign(nodes, threads);
+
+    // Workers of a node are consecutive, so each group of them is a range of workers
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    let mut queue_of = Vec::with_capacity(threads);
+    for (worker, &(node, _)) in assignment.iter().enumerate() {
+        match groups.last_mut() {
+            Some((first, end)) if assignment[*first].0 == node => *end += 1,
+            _ => groups.push((worker, worker + 1)),
+        }
+        queue_of.push(groups.len() - 1);
+    }
+    let mut queues: Vec<_> = groups
+        .iter()
+        .rev()
+        .map(|&(first, end)| {
+            let start = chunk_start(length, threads, first);
+            NodeQueue {
+                start,
+                queue: Queue::new(input.split_off(start)),
+                batching: Batching::new(config, end - first),
+            }
+        })
+        .collect();
+    queues.reverse();
+
+    let queues = Arc::new(queues);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+    let mut output = Output::new(length);
+    let sink = output.sink();
+
+    let f = Arc::new(f);
+    let work = |own: usize| {
+        let queues = queues.clone();
+        let stop = stop.clone();
+        let f = f.clone();
+        let sink = sink.clone();
+        move || process_queues(&queues, own, &stop, cancel_on_panic, &*f, &sink)
+    };
+    let jobs = (0..threads - 1).map(|worker| (worker, work(queue_of[worker])));
+    let inline = work(queue_of[threads - 1]);
+    let results = worker::run(&pinned(config, &assignment), spawner, &stop, jobs, inline)?;
+    Ok(output.finish(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::OsSpawner;
+
+    #[test]
+    fn cpulist() {
+        assert_eq!(
+            parse_cpulist("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_cpulist("5"), Some(vec![5]));
+        assert_eq!(parse_cpulist("\n"), Some(vec![]));
+        assert_eq!(parse_cpulist("0-x"), None);
+    }
+
+    #[test]
+    fn workers_grouped_by_node() {
+        let nodes = [vec![0, 1, 2, 3], vec![4, 5, 6, 7]];
+        assert_eq!(assign(&nodes, 4), [(0, 0), (0, 2), (1, 4), (1, 6)]);
+        assert_eq!(
+            assign(&nodes, 8),
+            (0..8).map(|core| (core / 4, core)).collect::<Vec<_>>()
+        );
+        // Larger node gets more workers
+        let nodes = [vec![0], vec![1, 2, 3]];
+        assert_eq!(assign(&nodes, 4), [(0, 0), (1, 1), (1, 2), (1, 3)]);
+    }
+
+    /// Two nodes that share the first core, so that pinning succeeds on any machine
+    fn nodes() -> Vec<Vec<usize>> {
+        vec![vec![0], vec![0]]
+    }
+
+    #[test]
+    fn ordered_output() {
+        let config = WorkConfig::new().threads(4);
+        for len in [2, 3, 100, 1001] {
+            let output = divide_work(&config, &OsSpawner, (0..len).collect(), |x| x * 2, &nodes());
+            assert_eq!(output.unwrap(), (0..len).map(|x| x * 2).collect::<Vec<_>>());
+            let output =
+                divide_equal_work(&config, &OsSpawner, (0..len).collect(), |x| x * 2, &nodes());
+            assert_eq!(output.unwrap(), (0..len).map(|x| x * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn nodes_help_each_other() {
+        // All expensive elements are in the region of the first node
+        let config = WorkConfig::new().threads(2);
+        let output = divide_work(
+            &config,
+            &OsSpawner,
+            (0..100).collect(),
+            |x| {
+                if x < 50 {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                std::thread::current().id()
+            },
+            &nodes(),
+        );
+        let output = output.unwrap();
+        let threads: std::collections::HashSet<_> = output[..50].iter().collect();
+        assert_eq!(threads.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_any_worker() {
+        let config = WorkConfig::new().threads(4);
+        let _ = divide_work(
+            &config,
+            &OsSpawner,
+            (0..100).collect(),
+            |x| {
+                assert_ne!(x, 50, "bad item");
+                x
+            },
+            &nodes(),
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::worker;
+use std::mem::MaybeUninit;
+use std::ops::Range;
+use std::panic;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Pointer to the output buffer shared between workers. Buffer is never accessed through a reference while workers
+/// run, every access goes through this pointer.
+struct SyncPtr<R>(*mut MaybeUninit<R>);
+
+impl<R> Clone for SyncPtr<R> {
+    fn clone(&self) -> Self {
+        SyncPtr(self.0)
+    }
+}
+
+// SAFETY: Workers write disjoint slots of the buffer, so sharing the pointer between them is the same as sending
+//         results to the thread that owns the buffer
+unsafe impl<R: Send> Send for SyncPtr<R> {}
+unsafe impl<R: Send> Sync for SyncPtr<R> {}
+
+/// Output buffer of schedulers that produce results out of order. Workers write results directly at their indices
+/// with [Writer]s, so nothing is buffered on the way. Written slots are tracked, so that results are dropped instead
+/// of leaked if the output is never completed, for example when a worker panics. Buffer is converted into results only
+/// once every slot is known to be initialized.
+pub(crate) struct Output<R> {
+    buf: Box<[MaybeUninit<R>]>,
+    /// Ranges of initialized slots reported by writers
+    written: Arc<Mutex<Vec<Range<usize>>>>,
+}
+
+impl<R> Output<R> {
+    /// Allocates output for `len` results
+    pub(crate) fn new(len: usize) -> Self {
+        Output {
+            buf: (0..len).map(|_| MaybeUninit::uninit()).collect(),
+            written: Arc::default(),
+        }
+    }
+
+    /// Handle that workers use to create their [Writer]s
+    pub(crate) fn sink(&mut self) -> Sink<R> {
+        Sink {
+            ptr: SyncPtr(self.buf.as_mut_ptr()),
+            len: self.buf.len(),
+            written: self.written.clone(),
+        }
+    }
+
+    /// Converts output into the results once all workers are joined. The panic of any worker is propagated with its
+    /// original payload, in which case results that were written are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Also panics if some of the slots weren't written, which means that a scheduler lost an element.
+    pub(crate) fn finish(mut self, workers: Vec<thread::Result<()>>) -> Vec<R> {
+        for res in workers {
+            if let Err(payload) = res {
+                panic::resume_unwind(payload);
+            }
+        }
+
+        let len = self.buf.len();
+        let count: usize = worker::lock(&self.written).iter().map(Range::len).sum();
+        assert_eq!(count, len, "every element must have a result");
+
+        // Slots are owned by the results from now on, so the drop guard must not drop them
+        worker::lock(&self.written).clear();
+        let buf = std::mem::take(&mut self.buf);
+        // SAFETY: 1) Every slot is written at most once, and `len` slots were written, so all of them are initialized.
+        //         Writers are dropped by now, since the workers that owned them have finished.
+        //         2) `MaybeUninit<R>` has the same layout as `R`, and the allocation was made for `len` of them.
+        unsafe { Vec::from_raw_parts(Box::into_raw(buf) as *mut R, len, len) }
+    }
+}
+
+impl<R> Drop for Output<R> {
+    fn drop(&mut self) {
+        let written = std::mem::take(&mut *worker::lock(&self.written));
+        for range in written {
+            for slot in &mut self.buf[range] {
+                // SAFETY: Slots in reported ranges were initialized by writers, and are dropped exactly once, since
+                //         the ranges are taken out of the list
+                unsafe { ptr::drop_in_place(slot.as_mut_ptr()) }
+            }
+        }
+    }
+}
+
+/// Handle to [Output] that is cloned into every worker
+pub(crate) struct Sink<R> {
+    ptr: SyncPtr<R>,
+    len: usize,
+    written: Arc<Mutex<Vec<Range<usize>>>>,
+}
+
+impl<R> Clone for Sink<R> {
+    fn clone(&self) -> Self {
+        Sink {
+            ptr: self.ptr.clone(),
+            len: self.len,
+            written: self.written.clone(),
+        }
+    }
+}
+
+impl<R> Sink<R> {
+    pub(crate) fn writer(&self) -> Writer<'_, R> {
+        Writer {
+            sink: self,
+            written: Vec::new(),
+        }
+    }
+}
+
+/// Writes results of a single worker. Written slots are reported to the [Output] once the writer is dropped, which
+/// happens when the worker is finished, including when it panics.
+pub(crate) struct Writer<'a, R> {
+    sink: &'a Sink<R>,
+    /// Workers mostly process contiguous elements, so ranges keep the list short
+    written: Vec<Range<usize>>,
+}
+
+impl<R> Writer<'_, R> {
+    /// Writes result of element `idx`
+    ///
+    /// # Safety
+    ///
+    /// Every index must be written at most once by all writers of the same output combined.
+    pub(crate) unsafe fn write(&mut self, idx: usize, r: R) {
+        assert!(idx < self.sink.len, "index {} is out of bounds", idx);
+        // SAFETY: Slot is within the allocation, and nothing else writes it or reads it until workers are joined
+        (*self.sink.ptr.0.add(idx)).write(r);
+        match self.written.last_mut() {
+            Some(range) if range.end == idx => range.end += 1,
+            _ => self.written.push(idx..idx + 1),
+        }
+    }
+}
+
+impl<R> Drop for Writer<'_, R> {
+    fn drop(&mut self) {
+        worker::lock(&self.sink.written).append(&mut self.written);
+    }
+}
+
+/// Concatenates results of workers, allocating exactly as much as they take together
+pub(crate) fn concat<R>(parts: Vec<Vec<R>>) -> Vec<R> {
+    let mut output = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+    for part in parts {
+        output.extend(part);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn results_at_their_indices() {
+        let mut output = Output::new(5);
+        let sink = output.sink();
+        let workers = [[4, 0, 1], [2, 3, 5]]
+            .iter()
+            .map(|indices| {
+                let mut writer = sink.writer();
+                for &idx in indices.iter().filter(|&&idx| idx < 5) {
+                    // SAFETY: indices are unique
+                    unsafe { writer.write(idx, idx.to_string()) };
+                }
+                Ok(())
+            })
+            .collect();
+        assert_eq!(output.finish(workers), ["0", "1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn incomplete_output_drops_results() {
+        let counter = Arc::new(());
+        let mut output = Output::new(10);
+        let sink = output.sink();
+        let mut writer = sink.writer();
+        for idx in [0, 1, 2, 7, 8] {
+            // SAFETY: indices are unique
+            unsafe { writer.write(idx, counter.clone()) };
+        }
+        drop(writer);
+        assert_eq!(Arc::strong_count(&counter), 6);
+        drop(output);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "every element must have a result")]
+    fn missing_results() {
+        let mut output = Output::<usize>::new(2);
+        let sink = output.sink();
+        // SAFETY: single write
+        unsafe { sink.writer().write(0, 0) };
+        output.finish(vec![Ok(())]);
+    }
+
+    /// Counts drops of the results
+    struct DropCounter<'a>(&'a AtomicUsize);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn write_all<'a>(output: &mut Output<DropCounter<'a>>, dropped: &'a AtomicUsize) {
+        let sink = output.sink();
+        let mut writer = sink.writer();
+        for idx in (0..sink.len).rev() {
+            // SAFETY: indices are unique
+            unsafe { writer.write(idx, DropCounter(dropped)) };
+        }
+    }
+
+    #[test]
+    fn results_dropped_once() {
+        let dropped = AtomicUsize::new(0);
+        let mut output = Output::new(10);
+        write_all(&mut output, &dropped);
+        let results = output.finish(vec![Ok(()), Ok(())]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        drop(results);
+        assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn panic_drops_results_once() {
+        let dropped = AtomicUsize::new(0);
+        let mut output = Output::new(10);
+        write_all(&mut output, &dropped);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            output.finish(vec![Ok(()), Err(Box::new("worker panicked"))])
+        }));
+        assert!(res.is_err());
+        assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    }
+}
@@ -0,0 +1,995 @@
+//! Pools of worker threads. By default workers run on a process-wide pool, created on first use. Spawning and joining
+//! threads on every call dominates the runtime of medium-sized inputs, so workers are kept parked between jobs instead
+//! of exiting. A job is handed to an idle worker that was created with the same name and stack size it would get from a
+//! freshly spawned thread, and a new worker is spawned only if there is none, so jobs never wait for each other, even
+//! when calls are nested.
+//!
+//! [Pool] is a fixed set of threads owned by the user instead. Jobs wait in its queue until a thread is free, and a
+//! caller that waits for a job that is still queued runs it on its own thread, so calls can still be nested. Jobs of a
+//! [Scope] borrow data of the caller, their lifetime is erased once they are queued, and the scope waits for all of
+//! them before returning.
+
+use crate::config::expect_spawned;
+use crate::worker::{self, lock, Handle, Spawner};
+use crate::WorkConfig;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Name and stack size of a worker thread. Workers are reused only for jobs that would create the same thread.
+type Key = (String, Option<usize>);
+
+static IDLE: OnceLock<Mutex<HashMap<Key, Vec<Arc<Worker>>>>> = OnceLock::new();
+
+/// Parked workers, grouped by the threads they were created as
+fn idle() -> MutexGuard<'static, HashMap<Key, Vec<Arc<Worker>>>> {
+    lock(IDLE.get_or_init(Mutex::default))
+}
+
+fn wait<'a, T>(condvar: &Condvar, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+    condvar.wait(guard).unwrap_or_else(PoisonError::into_inner)
+}
+
+enum Message {
+    Run(Job, Arc<Done>),
+    Exit,
+}
+
+/// Signals that a job has finished
+#[derive(Default)]
+struct Done {
+    done: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Done {
+    fn set(&self) {
+        *lock(&self.done) = true;
+        self.condvar.notify_all();
+    }
+
+    fn wait(&self) {
+        let mut done = lock(&self.done);
+        while !*done {
+            done = wait(&self.condvar, done);
+        }
+    }
+}
+
+struct Worker {
+    key: Key,
+    mailbox: Mutex<Option<Message>>,
+    condvar: Condvar,
+    /// Set right after the thread is spawned, before the worker gets its first job
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Worker {
+    fn start(key: Key) -> io::Result<Arc<Self>> {
+        let worker = Arc::new(Worker {
+            key: key.clone(),
+            mailbox: Mutex::new(None),
+            condvar: Condvar::new(),
+            handle: Mutex::new(None),
+        });
+        let (name, stack_size) = key;
+        let mut builder = thread::Builder::new().name(name);
+        if let Some(size) = stack_size {
+            builder = builder.stack_size(size);
+        }
+        let handle = builder.spawn({
+            let worker = worker.clone();
+            move || worker.serve()
+        })?;
+        *lock(&worker.handle) = Some(handle);
+        Ok(worker)
+    }
+
+    fn send(&self, message: Message) {
+        *lock(&self.mailbox) = Some(message);
+        self.condvar.notify_one();
+    }
+
+    /// Waits for the next message. Returns `None` if the worker was idle for longer than [set_pool_idle_timeout] and
+    /// took itself out of the pool.
+    fn receive(self: &Arc<Self>) -> Option<Message> {
+        let mut mailbox = lock(&self.mailbox);
+        loop {
+            if let Some(message) = mailbox.take() {
+                return Some(message);
+            }
+            let timeout = *lock(&IDLE_TIMEOUT);
+            match timeout {
+                None => mailbox = wait(&self.condvar, mailbox),
+                Some(timeout) => {
+                    let res = self.condvar.wait_timeout(mailbox, timeout);
+                    let (guard, res) = res.unwrap_or_else(PoisonError::into_inner);
+                    mailbox = guard;
+                    if res.timed_out() && mailbox.is_none() {
+                        drop(mailbox);
+                        if self.retire() {
+                            return None;
+                        }
+                        mailbox = lock(&self.mailbox);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the worker out of the idle list, unless a job is already on its way to it, which is the case if someone
+    /// took it out of the list first
+    fn retire(self: &Arc<Self>) -> bool {
+        let mut idle = idle();
+        let Some(workers) = idle.get_mut(&self.key) else {
+            return false;
+        };
+        let Some(position) = workers.iter().position(|worker| Arc::ptr_eq(worker, self)) else {
+            return false;
+        };
+        workers.swap_remove(position);
+        true
+    }
+
+    fn serve(self: Arc<Self>) {
+        while let Some(message) = self.receive() {
+            match message {
+                Message::Run(job, done) => {
+                    job();
+                    // Worker is parked before the caller learns that the job is finished, so that a caller that
+                    // immediately submits more work finds it idle instead of spawning another thread
+                    idle()
+                        .entry(self.key.clone())
+                        .or_default()
+                        .push(self.clone());
+                    done.set();
+                }
+                Message::Exit => return,
+            }
+        }
+    }
+}
+
+/// Job that is waiting in the queue of a [Pool]. Taken either by a thread of the pool, or by the caller that joins it.
+type Pending = Arc<Mutex<Option<Job>>>;
+
+/// Job submitted to a pool, counterpart of [JoinHandle]
+pub(crate) struct Task<R> {
+    result: Arc<Mutex<Option<thread::Result<R>>>>,
+    done: Arc<Done>,
+    pending: Option<Pending>,
+}
+
+impl<R> Task<R> {
+    /// Creates job that runs `f` and stores its result
+    fn new<F>(f: F) -> (Self, impl FnOnce() + Send)
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let job = {
+            let result = result.clone();
+            // Everything `f` captured is dropped before the job is reported as finished
+            move || {
+                let res = panic::catch_unwind(AssertUnwindSafe(f));
+                *lock(&result) = Some(res);
+            }
+        };
+        let task = Task {
+            result,
+            done: Arc::default(),
+            pending: None,
+        };
+        (task, job)
+    }
+
+    /// Waits for the job to finish, or runs it on the calling thread if no thread of the pool has taken it yet. Panic
+    /// of the job is returned with its original payload, same as with [JoinHandle::join].
+    pub(crate) fn join(self) -> thread::Result<R> {
+        let job = self.pending.and_then(|pending| lock(&pending).take());
+        match job {
+            Some(job) => job(),
+            None => self.done.wait(),
+        }
+        let res = lock(&self.result).take();
+        res.expect("finished job has a result")
+    }
+}
+
+/// Runs `f` on an idle worker named `name` with `stack_size`, spawning a new one if there is none
+pub(crate) fn spawn<F, R>(name: String, stack_size: Option<usize>, f: F) -> io::Result<Task<R>>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let key = (name, stack_size);
+    let parked = idle().get_mut(&key).and_then(Vec::pop);
+    let worker = match parked {
+        Some(worker) => worker,
+        None => Worker::start(key)?,
+    };
+
+    let (task, job) = Task::new(f);
+    worker.send(Message::Run(Box::new(job), task.done.clone()));
+    Ok(task)
+}
+
+static IDLE_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// Sets how long threads of the process-wide worker pool stay parked without work before they exit. Threads that exit
+/// are spawned again on demand. `None` keeps them parked until [shutdown_pool], which is the default.
+pub fn set_pool_idle_timeout(timeout: Option<Duration>) {
+    *lock(&IDLE_TIMEOUT) = timeout;
+    // Parked workers are woken up to pick up the new timeout. Taking the mailbox lock makes sure that a worker that
+    // read the old timeout is already waiting, so that it doesn't miss the notification.
+    for worker in idle().values().flatten() {
+        drop(lock(&worker.mailbox));
+        worker.condvar.notify_one();
+    }
+}
+
+/// Stops the idle threads of the worker pool and waits for them to exit, for example before checking for leaked
+/// threads. Threads that are busy with calls that are still running are not affected and are parked once they are
+/// done. The pool is refilled on demand by later calls.
+pub fn shutdown_pool() {
+    let workers: Vec<_> = idle().drain().flat_map(|(_, workers)| workers).collect();
+    for worker in &workers {
+        worker.send(Message::Exit);
+    }
+    for worker in workers {
+        let handle = lock(&worker.handle).take();
+        if let Some(handle) = handle {
+            // Jobs can't panic the worker, since their panics are caught
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Queue of a [Pool] shared with its threads
+struct Shared {
+    queue: Mutex<PoolQueue>,
+    condvar: Condvar,
+    idle_timeout: Option<Duration>,
+    stats: PoolCounters,
+}
+
+/// Cumulative counters of [PoolStats]
+#[derive(Default)]
+struct PoolCounters {
+    jobs_run: AtomicU64,
+    items: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+/// Cumulative statistics of a [Pool] since it was created, returned by [Pool::stats]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    /// Jobs run by the threads of the pool. Every call that splits work submits one job per worker except the calling
+    /// thread, jobs that the calling thread ran itself because no thread of the pool was free are not counted.
+    pub jobs_run: u64,
+    /// Elements of the input of all calls
+    pub items: u64,
+    /// Time the threads of the pool spent running jobs
+    pub busy: Duration,
+}
+
+#[derive(Default)]
+struct PoolQueue {
+    jobs: VecDeque<(Pending, Arc<Done>)>,
+    /// Threads that haven't exited
+    live: usize,
+    /// Maximal amount of threads, set by [Pool::resize]
+    target: usize,
+    /// Threads that are waiting for jobs
+    idle: usize,
+    /// Set once the pool is dropped, threads exit once the queue is empty
+    closed: bool,
+}
+
+impl Shared {
+    fn serve(&self) {
+        let mut queue = lock(&self.queue);
+        loop {
+            if let Some((pending, done)) = queue.jobs.pop_front() {
+                drop(queue);
+                // The job is gone if its caller has already run it
+                let job = lock(&pending).take();
+                if let Some(job) = job {
+                    let start = Instant::now();
+                    job();
+                    let busy = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+                    self.stats.jobs_run.fetch_add(1, Ordering::Relaxed);
+                    self.stats.busy_nanos.fetch_add(busy, Ordering::Relaxed);
+                }
+                queue = lock(&self.queue);
+                // Surplus threads exit once their current job is done, before the caller learns that it is
+                let surplus = queue.live > queue.target;
+                if surplus {
+                    queue.live -= 1;
+                }
+                done.set();
+                if surplus {
+                    return;
+                }
+                continue;
+            }
+            if queue.closed || queue.live > queue.target {
+                queue.live -= 1;
+                return;
+            }
+
+            queue.idle += 1;
+            queue = match self.idle_timeout {
+                None => wait(&self.condvar, queue),
+                Some(timeout) => {
+                    let res = self.condvar.wait_timeout(queue, timeout);
+                    let (mut queue, res) = res.unwrap_or_else(PoisonError::into_inner);
+                    if res.timed_out() && queue.jobs.is_empty() {
+                        queue.idle -= 1;
+                        queue.live -= 1;
+                        return;
+                    }
+                    queue
+                }
+            };
+            queue.idle -= 1;
+        }
+    }
+}
+
+/// Configuration of a [Pool]. Created from the [WorkConfig] that the pool uses for every call.
+///
+/// ```
+/// use and_conquer::{Pool, PoolConfig};
+/// use std::time::Duration;
+///
+/// let pool = Pool::with_config(PoolConfig::new(4).idle_timeout(Duration::from_secs(30)));
+/// assert_eq!(pool.divide_work((0..100).collect(), |x| x + 1)[99], 100);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    work: WorkConfig,
+    idle_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    /// Configuration of a pool of `n_threads` workers, same as [Pool::new]
+    pub fn new(n_threads: usize) -> Self {
+        WorkConfig::new().threads(n_threads).into()
+    }
+
+    /// Sets how long threads of the pool stay without work before they exit. Threads are spawned again on demand, up
+    /// to the configured amount, once jobs arrive and there are no idle threads to take them. By default threads are
+    /// kept until the pool is dropped.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+impl From<WorkConfig> for PoolConfig {
+    fn from(work: WorkConfig) -> Self {
+        PoolConfig {
+            work,
+            idle_timeout: None,
+        }
+    }
+}
+
+/// Set of worker threads owned by the caller, with the same methods as the free functions of this crate. Threads are
+/// created by [Pool::new] and joined when the pool is dropped, unless they exit earlier after
+/// [idle timeout](PoolConfig::idle_timeout). A pool can be shared between threads and used by
+/// any number of concurrent calls, in which case their jobs wait in the queue of the pool until one of its threads is
+/// free. Results are in input order and panics are propagated the same way as with the free functions.
+///
+/// ```
+/// use and_conquer::Pool;
+///
+/// let pool = Pool::new(4);
+/// let output = pool.divide_work((0..1000).collect(), |x| x * 2);
+/// assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+/// ```
+pub struct Pool {
+    shared: Arc<Shared>,
+    /// Threads that weren't joined yet, including the ones that exited after idle timeout
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    /// Configuration of every call, except for the amount of workers
+    config: WorkConfig,
+}
+
+impl Pool {
+    /// Creates pool that splits work between `n_threads` workers. Calling thread takes part in the work, so
+    /// `n_threads - 1` threads are spawned. `n_threads` of `0` behaves the same as `1`.
+    ///
+    /// # Panics
+    ///
+    /// If the OS fails to create a thread.
+    pub fn new(n_threads: usize) -> Self {
+        Pool::with_config(PoolConfig::new(n_threads))
+    }
+
+    /// Creates pool according to `config`. Either [PoolConfig] or [WorkConfig], which is used for every call. Threads
+    /// are created according to its thread count, name prefix and stack size.
+    ///
+    /// # Panics
+    ///
+    /// If the OS fails to create a thread.
+    pub fn with_config(config: impl Into<PoolConfig>) -> Self {
+        let PoolConfig { work, idle_timeout } = config.into();
+        let pool = Pool {
+            shared: Arc::new(Shared {
+                queue: Mutex::default(),
+                condvar: Condvar::new(),
+                idle_timeout,
+                stats: PoolCounters::default(),
+            }),
+            threads: Mutex::default(),
+            config: work,
+        };
+        pool.resize(pool.config.worker_count());
+        pool
+    }
+
+    /// Changes amount of workers to `n_threads`, same as with [Pool::new]. Missing threads are spawned right away,
+    /// surplus ones exit once they finish their current job, so calls that are already running are not disturbed.
+    /// With `n_threads` of `0` or `1` no threads are left, and later calls run on the calling thread.
+    ///
+    /// # Panics
+    ///
+    /// If the OS fails to create a thread.
+    pub fn resize(&self, n_threads: usize) {
+        let mut queue = lock(&self.shared.queue);
+        queue.target = n_threads.max(1) - 1;
+        while queue.live < queue.target {
+            let thread = self.start_thread(queue.live);
+            thread.expect("failed to spawn thread of the pool");
+            queue.live += 1;
+        }
+        // Idle surplus threads exit once they wake up
+        self.shared.condvar.notify_all();
+    }
+
+    /// Statistics of all calls since the pool was created
+    pub fn stats(&self) -> PoolStats {
+        let stats = &self.shared.stats;
+        PoolStats {
+            jobs_run: stats.jobs_run.load(Ordering::Relaxed),
+            items: stats.items.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(stats.busy_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn count_items(&self, len: usize) {
+        self.shared
+            .stats
+            .items
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Configuration of a call, with the current amount of workers
+    fn call_config(&self) -> WorkConfig {
+        let target = lock(&self.shared.queue).target;
+        self.config.clone().threads(target + 1)
+    }
+
+    /// Same as [divide_work](crate::divide_work), but runs on the threads of this pool
+    pub fn divide_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.count_items(input.len());
+        expect_spawned(self.call_config().dynamic(self, input, f)).0
+    }
+
+    /// Same as [divide_equal_work](crate::divide_equal_work), but runs on the threads of this pool
+    pub fn divide_equal_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        self.count_items(input.len());
+        expect_spawned(self.call_config().equal(self, input, f)).0
+    }
+
+    /// Runs `f` with a [Scope], which processes input on the threads of this pool the same way, but lets elements,
+    /// results and `f` of its calls borrow data that outlives the scope. Waits for every job of the scope to finish
+    /// before returning, even if `f` panics, in which case the panic is propagated afterwards.
+    ///
+    /// ```
+    /// use and_conquer::Pool;
+    ///
+    /// let pool = Pool::new(4);
+    /// let records: Vec<String> = (0..1000).map(|x| x.to_string()).collect();
+    /// let lengths = pool.scope(|s| s.divide_work(records.iter().collect(), |record| record.len()));
+    /// assert_eq!(lengths[999], 3);
+    /// ```
+    pub fn scope<'scope, F, R>(&'scope self, f: F) -> R
+    where
+        F: FnOnce(&Scope<'scope>) -> R,
+    {
+        let scope = Scope {
+            pool: self,
+            running: Arc::default(),
+            _marker: PhantomData,
+        };
+        let res = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.running.wait();
+        res.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    /// Puts `job` of `task` in the queue, starting a new thread for it if there are no idle ones. Fails only if no
+    /// thread of the pool is running and a new one couldn't be started.
+    fn submit<'env, R>(&self, mut task: Task<R>, job: Job) -> io::Result<Handle<'env, R>> {
+        let mut queue = lock(&self.shared.queue);
+        if queue.jobs.len() >= queue.idle && queue.live < queue.target {
+            match self.start_thread(queue.live) {
+                Ok(()) => queue.live += 1,
+                Err(err) if queue.live == 0 => return Err(err),
+                // Job waits for one of the running threads
+                Err(_) => {}
+            }
+        }
+        let pending = Arc::new(Mutex::new(Some(job)));
+        task.pending = Some(pending.clone());
+        queue.jobs.push_back((pending, task.done.clone()));
+        self.shared.condvar.notify_one();
+        Ok(Handle::Pooled(task))
+    }
+
+    /// Spawns thread number `index`. Threads that have exited are joined along the way.
+    fn start_thread(&self, index: usize) -> io::Result<()> {
+        let shared = self.shared.clone();
+        let thread = worker::builder(&self.config, index).spawn(move || shared.serve())?;
+        let mut threads = lock(&self.threads);
+        threads.retain(|thread| !thread.is_finished());
+        threads.push(thread);
+        Ok(())
+    }
+}
+
+impl Spawner<'static> for Pool {
+    fn spawn<F, R>(&self, _: &WorkConfig, _: usize, f: F) -> io::Result<Handle<'static, R>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (task, job) = Task::new(f);
+        self.submit(task, Box::new(job))
+    }
+}
+
+/// Scope of [Pool::scope]. Has the same methods as [Pool], but lets them borrow data that lives for `'scope`.
+pub struct Scope<'scope> {
+    pool: &'scope Pool,
+    running: Arc<Running>,
+    /// Invariant, so that the scope is never coerced to a different lifetime
+    _marker: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> Scope<'scope> {
+    /// Same as [Pool::divide_work], but elements, results and `f` only need to outlive the scope
+    pub fn divide_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'scope,
+        R: Send + 'scope,
+        F: Fn(T) -> R + Send + Sync + 'scope,
+    {
+        self.pool.count_items(input.len());
+        expect_spawned(self.pool.call_config().dynamic(self, input, f)).0
+    }
+
+    /// Same as [Pool::divide_equal_work], but elements, results and `f` only need to outlive the scope
+    pub fn divide_equal_work<F, T, R>(&self, input: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send + 'scope,
+        R: Send + 'scope,
+        F: Fn(T) -> R + Send + Sync + 'scope,
+    {
+        self.pool.count_items(input.len());
+        expect_spawned(self.pool.call_config().equal(self, input, f)).0
+    }
+}
+
+impl<'scope> Spawner<'scope> for Scope<'scope> {
+    fn spawn<F, R>(&self, _: &WorkConfig, _: usize, f: F) -> io::Result<Handle<'scope, R>>
+    where
+        F: FnOnce() -> R + Send + 'scope,
+        R: Send + 'scope,
+    {
+        let (task, job) = Task::new(f);
+        let job = ScopedJob {
+            job,
+            _running: Running::start(&self.running),
+        };
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || job.run());
+        // SAFETY: The job only borrows data that lives for `'scope`, and [Pool::scope] doesn't return until every
+        //         job of the scope is dropped, whether it ran or not. Results are dropped either by their job or by
+        //         the task, which is joined or dropped before the scope returns too.
+        let job: Job = unsafe { std::mem::transmute(job) };
+        self.pool.submit(task, job)
+    }
+}
+
+/// Count of jobs of a [Scope] that aren't dropped yet
+#[derive(Default)]
+struct Running {
+    count: Mutex<usize>,
+    condvar: Condvar,
+}
+
+impl Running {
+    fn start(running: &Arc<Running>) -> RunningGuard {
+        *lock(&running.count) += 1;
+        RunningGuard(running.clone())
+    }
+
+    fn wait(&self) {
+        let mut count = lock(&self.count);
+        while *count > 0 {
+            count = wait(&self.condvar, count);
+        }
+    }
+}
+
+/// Counts the job as finished once dropped
+struct RunningGuard(Arc<Running>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        *lock(&self.0.count) -= 1;
+        self.0.condvar.notify_all();
+    }
+}
+
+/// Job of a [Scope]. Fields are dropped in order, so the job is counted as finished only after everything it
+/// captured is gone.
+struct ScopedJob<J> {
+    job: J,
+    _running: RunningGuard,
+}
+
+impl<J: FnOnce()> ScopedJob<J> {
+    fn run(self) {
+        let ScopedJob { job, _running } = self;
+        job();
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        lock(&self.shared.queue).closed = true;
+        self.shared.condvar.notify_all();
+        let threads = std::mem::take(&mut *lock(&self.threads));
+        for thread in threads {
+            // Jobs can't panic the threads, since their panics are caught
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorkConfig;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{mpsc, Barrier};
+    use std::time::Duration;
+
+    fn idle_count(name: &str) -> usize {
+        let key = (name.to_owned(), None);
+        idle().get(&key).map_or(0, Vec::len)
+    }
+
+    fn thread_id(_: usize) -> thread::ThreadId {
+        thread::current().id()
+    }
+
+    #[test]
+    fn workers_are_reused() {
+        let config = WorkConfig::new()
+            .threads(2)
+            .thread_name_prefix("pool-reuse");
+        let first = config.divide_equal_work((0..10).collect(), thread_id);
+        let second = config.divide_equal_work((0..10).collect(), thread_id);
+        assert_ne!(first[0], thread::current().id());
+        assert_eq!(first[0], second[0]);
+        assert_eq!(idle_count("pool-reuse-0"), 1);
+    }
+
+    #[test]
+    fn spawn_per_call() {
+        let config = WorkConfig::new()
+            .threads(2)
+            .thread_name_prefix("pool-bypass")
+            .spawn_per_call(true);
+        let first = config.divide_equal_work((0..10).collect(), thread_id);
+        let second = config.divide_equal_work((0..10).collect(), thread_id);
+        assert_ne!(first[0], second[0]);
+        assert_eq!(idle_count("pool-bypass-0"), 0);
+    }
+
+    fn pool_threads(prefix: &str, output: &[Option<String>]) -> usize {
+        let names: std::collections::HashSet<_> = output
+            .iter()
+            .flatten()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.len()
+    }
+
+    fn thread_name(_: usize) -> Option<String> {
+        thread::current().name().map(String::from)
+    }
+
+    #[test]
+    fn pool_sequential_reuse() {
+        let pool = Pool::with_config(WorkConfig::new().threads(3).thread_name_prefix("owned"));
+        assert_eq!(lock(&pool.shared.queue).live, 2);
+        for _ in 0..10 {
+            let output = pool.divide_work((0..1000).collect(), |x| x * 2);
+            assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+            let output = pool.divide_equal_work((0..1000).collect(), |x| x + 1);
+            assert_eq!(output, (1..1001).collect::<Vec<_>>());
+        }
+        let names = pool.divide_equal_work((0..30).collect(), thread_name);
+        assert!(pool_threads("owned-", &names) <= 2);
+    }
+
+    #[test]
+    fn pool_concurrent_submission() {
+        let pool = Pool::new(3);
+        thread::scope(|s| {
+            let callers: Vec<_> = (0..2)
+                .map(|caller| {
+                    let pool = &pool;
+                    s.spawn(move || {
+                        for _ in 0..20 {
+                            let output = pool.divide_work((0..500).collect(), move |x| x * caller);
+                            assert_eq!(output, (0..500).map(|x| x * caller).collect::<Vec<_>>());
+                            let output =
+                                pool.divide_equal_work((0..500).collect(), move |x| x + caller);
+                            assert_eq!(output, (caller..500 + caller).collect::<Vec<_>>());
+                        }
+                    })
+                })
+                .collect();
+            for caller in callers {
+                caller.join().unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn pool_nested_calls() {
+        // Nested calls would run sequentially otherwise
+        let pool = Arc::new(Pool::with_config(WorkConfig::new().threads(2).nested(true)));
+        let inner = pool.clone();
+        let output = pool.divide_equal_work((0..20).collect(), move |x| {
+            inner
+                .divide_equal_work((0..20).collect(), move |y| x * y)
+                .len()
+        });
+        assert_eq!(output, vec![20; 20]);
+    }
+
+    #[test]
+    fn pool_idle_timeout() {
+        let config = PoolConfig::new(3).idle_timeout(Duration::from_millis(10));
+        let pool = Pool::with_config(config);
+        let live = || lock(&pool.shared.queue).live;
+        assert_eq!(live(), 2);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(live(), 0);
+
+        for _ in 0..3 {
+            let output = pool.divide_equal_work((0..100).collect(), |x| x * 2);
+            assert_eq!(output, (0..100).map(|x| x * 2).collect::<Vec<_>>());
+            assert!((1..=2).contains(&live()));
+        }
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(live(), 0);
+        drop(pool);
+    }
+
+    /// Element processing that is blocked until the test lets it continue
+    struct Gate {
+        started: Mutex<mpsc::Sender<()>>,
+        release: Mutex<mpsc::Receiver<()>>,
+    }
+
+    fn gate() -> (Arc<Gate>, mpsc::Receiver<()>, mpsc::Sender<()>) {
+        let (started, started_rx) = mpsc::channel();
+        let (release_tx, release) = mpsc::channel();
+        let gate = Gate {
+            started: Mutex::new(started),
+            release: Mutex::new(release),
+        };
+        (Arc::new(gate), started_rx, release_tx)
+    }
+
+    /// Submits elements where each of the three workers blocks on its first element, then calls `resize` once all of
+    /// them are blocked
+    fn resize_mid_job(pool: Pool, resize: impl FnOnce(&Pool)) -> Pool {
+        let pool = Arc::new(pool);
+        let (gate, started, release) = gate();
+        let caller = {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                pool.divide_equal_work((0..30).collect(), move |x| {
+                    lock(&gate.started).send(()).unwrap();
+                    lock(&gate.release).recv().unwrap();
+                    x * 2
+                })
+            })
+        };
+        for _ in 0..3 {
+            started.recv().unwrap();
+        }
+        resize(&pool);
+        for _ in 0..30 {
+            release.send(()).unwrap();
+        }
+        let output = caller.join().unwrap();
+        assert_eq!(output, (0..30).map(|x| x * 2).collect::<Vec<_>>());
+        Arc::try_unwrap(pool).ok().unwrap()
+    }
+
+    #[test]
+    fn pool_shrinks_mid_job() {
+        let pool = Pool::with_config(WorkConfig::new().threads(3).threshold(1));
+        let pool = resize_mid_job(pool, |pool| pool.resize(2));
+        assert_eq!(lock(&pool.shared.queue).live, 1);
+        let output = pool.divide_work((0..100).collect(), |x| x + 1);
+        assert_eq!(output, (1..101).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pool_shrinks_to_zero() {
+        let pool = Pool::with_config(WorkConfig::new().threads(3).threshold(1));
+        let pool = resize_mid_job(pool, |pool| pool.resize(0));
+        assert_eq!(lock(&pool.shared.queue).live, 0);
+        let caller = thread::current().id();
+        let threads = pool.divide_equal_work((0..100).collect(), |_| thread::current().id());
+        assert!(threads.iter().all(|&id| id == caller));
+    }
+
+    #[test]
+    fn pool_grows_mid_job() {
+        let pool = Pool::with_config(WorkConfig::new().threads(3).threshold(1));
+        let pool = resize_mid_job(pool, |pool| {
+            pool.resize(5);
+            assert_eq!(lock(&pool.shared.queue).live, 4);
+        });
+        assert_eq!(lock(&pool.shared.queue).live, 4);
+        let names = pool.divide_equal_work((0..100).collect(), thread_name);
+        assert!(pool_threads("and-conquer-", &names) <= 4);
+        assert_eq!(names.len(), 100);
+    }
+
+    #[test]
+    fn pool_stats() {
+        let pool = Pool::new(3);
+        assert_eq!(pool.stats(), PoolStats::default());
+        let gate = Barrier::new(3);
+        // Every worker waits for the others, so each of them processes its own chunk
+        pool.scope(|s| {
+            s.divide_equal_work((0..30).collect(), |x| {
+                if x % 10 == 0 {
+                    gate.wait();
+                }
+                thread::sleep(Duration::from_millis(1));
+            })
+        });
+        pool.divide_work((0..5).collect(), |x| x);
+        let stats = pool.stats();
+        assert_eq!(stats.jobs_run, 2);
+        assert_eq!(stats.items, 35);
+        assert!(stats.busy >= Duration::from_millis(20), "{:?}", stats.busy);
+    }
+
+    #[test]
+    fn pool_propagates_panics() {
+        let pool = Pool::new(4);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.divide_work((0..100).collect(), |x| {
+                assert_ne!(x, 50, "bad item");
+                x
+            })
+        }));
+        let payload = res.unwrap_err();
+        assert!(payload
+            .downcast_ref::<String>()
+            .unwrap()
+            .contains("bad item"));
+        assert_eq!(pool.divide_work((0..100).collect(), |x| x).len(), 100);
+    }
+
+    #[test]
+    fn pool_drop_joins_threads() {
+        let pool = Pool::new(4);
+        assert_eq!(pool.divide_equal_work((0..100).collect(), |x| x).len(), 100);
+        let shared = Arc::downgrade(&pool.shared);
+        drop(pool);
+        // Every thread held the queue, so it is gone only if all of them exited
+        assert!(shared.upgrade().is_none());
+    }
+
+    #[test]
+    fn scope_borrows_local_string() {
+        let pool = Pool::new(4);
+        let text = (0..1000)
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let words: Vec<&str> = text.split(' ').collect();
+        let (lengths, numbers) = pool.scope(|s| {
+            let lengths = s.divide_work(words.clone(), str::len);
+            let numbers =
+                s.divide_equal_work(words.clone(), |word: &str| word.parse::<usize>().unwrap());
+            (lengths, numbers)
+        });
+        assert_eq!(lengths.iter().sum::<usize>() + 999, text.len());
+        assert_eq!(numbers, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scope_mutable_state() {
+        let pool = Pool::new(4);
+        let mut total = 0;
+        let mut seen = Vec::new();
+        {
+            let state = Mutex::new((&mut total, &mut seen));
+            let state = &state;
+            pool.scope(|s| {
+                s.divide_work((0..100).collect(), move |x: usize| {
+                    let mut state = lock(state);
+                    *state.0 += x;
+                    state.1.push(x);
+                })
+            });
+        }
+        assert_eq!(total, 4950);
+        seen.sort_unstable();
+        assert_eq!(seen, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scope_waits_for_jobs_on_panic() {
+        let pool = Pool::new(4);
+        let finished = AtomicUsize::new(0);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.divide_work((0..100).collect(), |x| {
+                    assert_ne!(x, 50, "bad item");
+                    finished.fetch_add(1, Ordering::Relaxed);
+                })
+            })
+        }));
+        assert!(res.is_err());
+        // Nothing is running once the scope returns
+        let count = finished.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(finished.load(Ordering::Relaxed), count);
+    }
+
+    #[test]
+    fn panics_are_joined() {
+        let task = spawn("pool-panic".to_owned(), None, || panic!("bad item")).unwrap();
+        let payload = task.join().unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"bad item"));
+        let task = spawn("pool-panic".to_owned(), None, || 1).unwrap();
+        assert_eq!(task.join().unwrap(), 1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Queue of elements shared by workers of the dynamic scheduler. Backed by `Mutex<Vec<T>>` by default, or by lock-free
+//! [Injector](crossbeam_deque::Injector) with `crossbeam` feature enabled. Either way elements are taken starting from
+//! the end of the input, so that elements left in the queue are always the first ones of the input.
+
+use crate::{Schedule, WorkConfig};
+
+/// How many elements workers take from the queue at once. Only the default backend takes elements in batches, since
+/// there is no lock to amortize with the lock-free one.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "crossbeam", allow(dead_code))]
+pub(crate) struct Batching {
+    size: Option<usize>,
+    /// Batches are `remaining / (workers * per_worker)`, but no smaller than `min`
+    per_worker: usize,
+    min: usize,
+    workers: usize,
+}
+
+impl Batching {
+    pub(crate) fn new(config: &WorkConfig, workers: usize) -> Self {
+        match config.schedule {
+            Schedule::Guided { min_chunk } => Batching {
+                size: None,
+                per_worker: 2,
+                min: min_chunk.max(1),
+                workers,
+            },
+            Schedule::Dynamic | Schedule::Static => Batching {
+                size: config.batch_size,
+                per_worker: 4,
+                min: 1,
+                workers,
+            },
+        }
+    }
+
+    /// Batches of a single element, so that elements are never taken out of the queue before they are processed
+    pub(crate) fn single() -> Self {
+        Batching {
+            size: Some(1),
+            per_worker: 1,
+            min: 1,
+            workers: 1,
+        }
+    }
+
+    /// Size of the next batch when `remaining` elements are left in the queue. Unless configured, batches get smaller
+    /// as the queue drains, so that workers finish at roughly the same time.
+    #[cfg_attr(feature = "crossbeam", allow(dead_code))]
+    pub(crate) fn size(&self, remaining: usize) -> usize {
+        self.size
+            .unwrap_or_else(|| (remaining / (self.workers * self.per_worker)).max(self.min))
+    }
+}
+
+#[cfg(not(feature = "crossbeam"))]
+mod imp {
+    use super::Batching;
+    use crate::worker;
+    use std::iter::Zip;
+    use std::ops::RangeFrom;
+    use std::sync::Mutex;
+    use std::vec;
+
+    /// Elements taken from the queue at once, along with their indices in the original input
+    pub(crate) type Batch<T> = Zip<RangeFrom<usize>, vec::IntoIter<T>>;
+
+    pub(crate) struct Queue<T>(Mutex<Vec<T>>);
+
+    impl<T> Queue<T> {
+        pub(crate) fn new(input: Vec<T>) -> Self {
+            Queue(Mutex::new(input))
+        }
+
+        /// Pops next batch of elements from the back of the queue. Elements of the batch are in input order.
+        pub(crate) fn pop(&self, batching: Batching) -> Option<Batch<T>> {
+            let mut q = worker::lock(&self.0);
+            if q.is_empty() {
+                return None;
+            }
+            // Elements that are left in the queue are the first ones of the input, so the batch starts at their count
+            let base = q.len().saturating_sub(batching.size(q.len()));
+            Some((base..).zip(q.split_off(base)))
+        }
+
+        /// Amount of elements left in the queue
+        pub(crate) fn len(&self) -> usize {
+            worker::lock(&self.0).len()
+        }
+
+        /// Takes all elements that are left in the queue, in input order
+        pub(crate) fn take_remaining(&self) -> Vec<T> {
+            std::mem::take(&mut *worker::lock(&self.0))
+        }
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+mod imp {
+    use super::Batching;
+    use crossbeam_deque::{Injector, Steal};
+    use std::iter::{self, Once};
+
+    /// Elements taken from the queue at once, along with their indices in the original input. Always a single element.
+    pub(crate) type Batch<T> = Once<(usize, T)>;
+
+    /// Elements are pushed in reverse, so that they are taken from the back of the input as with the default backend.
+    /// Each element carries its own index, since the queue has no notion of length.
+    pub(crate) struct Queue<T>(Injector<(usize, T)>);
+
+    impl<T> Queue<T> {
+        pub(crate) fn new(input: Vec<T>) -> Self {
+            let queue = Injector::new();
+            for item in input.into_iter().enumerate().rev() {
+                queue.push(item);
+            }
+            Queue(queue)
+        }
+
+        /// Pops next element from the back of the input
+        pub(crate) fn pop(&self, _: Batching) -> Option<Batch<T>> {
+            iter::repeat_with(|| self.0.steal())
+                .find(|steal| !steal.is_retry())
+                .and_then(Steal::success)
+                .map(iter::once)
+        }
+
+        /// Amount of elements left in the queue
+        pub(crate) fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        /// Takes all elements that are left in the queue, in input order
+        pub(crate) fn take_remaining(&self) -> Vec<T> {
+            let mut remaining: Vec<_> = iter::from_fn(|| self.pop(Batching::single()))
+                .flatten()
+                .collect();
+            remaining.reverse();
+            remaining.into_iter().map(|(_, val)| val).collect()
+        }
+    }
+}
+
+pub(crate) use imp::Queue;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_batch_size() {
+        let batching = Batching::new(&WorkConfig::new(), 4);
+        assert_eq!(batching.size(1000), 62);
+        assert_eq!(batching.size(16), 1);
+        assert_eq!(batching.size(1), 1);
+
+        let batching = Batching::new(&WorkConfig::new().batch_size(0), 4);
+        assert_eq!(batching.size(1000), 1);
+        let batching = Batching::new(&WorkConfig::new().dynamic_chunk(16), 4);
+        assert_eq!(batching.size(1000), 16);
+    }
+
+    #[test]
+    fn guided_batch_size() {
+        let config = WorkConfig::new()
+            .batch_size(3)
+            .schedule(Schedule::Guided { min_chunk: 4 });
+        let batching = Batching::new(&config, 4);
+        assert_eq!(batching.size(1000), 125);
+        assert_eq!(batching.size(40), 5);
+        assert_eq!(batching.size(10), 4);
+        assert_eq!(batching.size(1), 4);
+
+        let batching = Batching::new(
+            &WorkConfig::new().schedule(Schedule::Guided { min_chunk: 0 }),
+            4,
+        );
+        assert_eq!(batching.size(1), 1);
+    }
+
+    #[test]
+    fn pops_from_the_back() {
+        let queue = Queue::new((0..10).collect());
+        let batching = Batching::new(&WorkConfig::new().batch_size(3), 1);
+        let popped: Vec<_> = queue.pop(batching).unwrap().collect();
+        assert!(popped.contains(&(9, 9)));
+        assert!(popped.iter().all(|&(idx, val)| idx == val && idx >= 7));
+        assert!(queue.pop(Batching::single()).is_some());
+        assert_eq!(queue.take_remaining().len(), 10 - popped.len() - 1);
+        assert!(queue.pop(Batching::single()).is_none());
+    }
+
+    #[test]
+    fn remaining_in_input_order() {
+        let queue = Queue::new((0..10).collect());
+        queue.pop(Batching::single());
+        assert_eq!(queue.take_remaining(), (0..9).collect::<Vec<_>>());
+    }
+}
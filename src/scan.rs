@@ -0,0 +1,136 @@
+//! Parallel inclusive scan in two passes. Every chunk of the input is scanned on its own, then the totals of the chunks
+//! are scanned on the calling thread, which gives the offset of every chunk, and the offsets are applied to the chunks
+//! in parallel.
+
+use crate::equal;
+use crate::output::concat;
+use crate::worker::Spawner;
+use crate::{SpawnError, WorkConfig};
+use std::sync::Arc;
+
+/// Inclusive scan of `input` that starts from `init`
+pub(crate) fn scan<F, A>(input: Vec<A>, init: A, op: &F) -> Vec<A>
+where
+    F: Fn(&A, &A) -> A,
+{
+    let mut output: Vec<A> = Vec::with_capacity(input.len());
+    for val in input {
+        let next = op(output.last().unwrap_or(&init), &val);
+        output.push(next);
+    }
+    output
+}
+
+/// Parallel part of [divide_scan](crate::divide_scan). `input` is expected to be large enough to be split.
+pub(crate) fn divide_scan<'env, S, I, F, A>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<A>,
+    identity: I,
+    op: F,
+) -> Result<Vec<A>, SpawnError>
+where
+    S: Spawner<'env>,
+    A: Send + 'env,
+    I: Fn() -> A + Send + Sync + 'env,
+    F: Fn(&A, &A) -> A + Send + Sync + 'env,
+{
+    let shared = Arc::new((identity, op));
+    let chunks = equal::split_vecs(config, input);
+    let local = {
+        let shared = shared.clone();
+        move |chunk| {
+            let (identity, op) = &*shared;
+            scan(chunk, identity(), op)
+        }
+    };
+    let mut chunks = equal::divide_each(config, spawner, chunks, local)?.into_iter();
+
+    // Offset of a chunk is the total of all chunks before it, and the first chunk needs none. Chunks are never empty.
+    let (identity, op) = &*shared;
+    let first = chunks.next().expect("there is at least one chunk");
+    let mut offset = op(&identity(), first.last().expect("chunks are not empty"));
+    let mut tasks = Vec::with_capacity(chunks.len());
+    while let Some(chunk) = chunks.next() {
+        // Total of the last chunk isn't needed, so `op` isn't called for it
+        let total =
+            (chunks.len() > 0).then(|| op(&offset, chunk.last().expect("chunks are not empty")));
+        tasks.push((offset, chunk));
+        match total {
+            Some(total) => offset = total,
+            None => break,
+        }
+    }
+    let apply = {
+        let shared = shared.clone();
+        move |(offset, mut chunk): (A, Vec<A>)| {
+            let (_, op) = &*shared;
+            for val in &mut chunk {
+                *val = op(&offset, val);
+            }
+            chunk
+        }
+    };
+    let mut results = vec![first];
+    if !tasks.is_empty() {
+        results.extend(equal::divide_each(config, spawner, tasks, apply)?);
+    }
+    Ok(concat(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shuffle::shuffle;
+    use crate::WorkConfig;
+
+    /// 2x2 matrix, multiplication of which is associative but not commutative
+    type Matrix = [[u64; 2]; 2];
+
+    fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+        let cell = |i: usize, j: usize| {
+            a[i][0]
+                .wrapping_mul(b[0][j])
+                .wrapping_add(a[i][1].wrapping_mul(b[1][j]))
+        };
+        [[cell(0, 0), cell(0, 1)], [cell(1, 0), cell(1, 1)]]
+    }
+
+    #[test]
+    fn matches_sequential_scan() {
+        for config in [
+            WorkConfig::new().threads(2).threshold(1),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::new().threads(8).threshold(1),
+            WorkConfig::new().threads(4).min_chunk_size(10_000),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..10 {
+                let mut input: Vec<u64> = (0..seed * 123 + 1).collect();
+                shuffle(&mut input, seed);
+                let expected: Vec<u64> = input
+                    .iter()
+                    .scan(0, |acc, x| {
+                        *acc += x;
+                        Some(*acc)
+                    })
+                    .collect();
+                assert_eq!(config.divide_prefix_sum(input.clone()), expected);
+
+                let matrices: Vec<Matrix> = input.iter().map(|&x| [[x, 1], [x % 7, 2]]).collect();
+                let identity = [[1, 0], [0, 1]];
+                let expected: Vec<Matrix> = matrices
+                    .iter()
+                    .scan(identity, |acc, m| {
+                        *acc = mul(acc, m);
+                        Some(*acc)
+                    })
+                    .collect();
+                assert_eq!(
+                    config.divide_scan(matrices, move || identity, mul),
+                    expected
+                );
+            }
+            assert!(config.divide_prefix_sum(Vec::<u32>::new()).is_empty());
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/// Small deterministic generator, SplitMix64. Quality is more than enough to smear hot spots of the input between
+/// workers, and the same seed always gives the same sequence on any platform.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed number in `0..bound`
+    fn below(&mut self, bound: usize) -> usize {
+        ((u128::from(self.next()) * bound as u128) >> 64) as usize
+    }
+}
+
+/// Shuffles `input` in place with a permutation determined by `seed`. Returns original index of every element of the
+/// shuffled input, which is needed to [restore] the order.
+pub(crate) fn shuffle<T>(input: &mut [T], seed: u64) -> Vec<usize> {
+    let mut rng = SplitMix64(seed);
+    let mut order: Vec<usize> = (0..input.len()).collect();
+    // Fisher-Yates, applied to elements and their indices alike
+    for i in (1..input.len()).rev() {
+        let j = rng.below(i + 1);
+        input.swap(i, j);
+        order.swap(i, j);
+    }
+    order
+}
+
+/// Moves every element of `shuffled` to its original index in `order`, undoing [shuffle]
+pub(crate) fn restore<R>(shuffled: &mut [R], mut order: Vec<usize>) {
+    // Each swap puts one element at its place, so every cycle of the permutation is followed once
+    for i in 0..shuffled.len() {
+        while order[i] != i {
+            let j = order[i];
+            shuffled.swap(i, j);
+            order.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 2, 3, 10, 1000] {
+            for seed in [0, 1, 42, u64::MAX] {
+                let input: Vec<usize> = (0..len).collect();
+                let mut shuffled = input.clone();
+                let order = shuffle(&mut shuffled, seed);
+                assert!(shuffled.iter().zip(&order).all(|(val, idx)| val == idx));
+                restore(&mut shuffled, order);
+                assert_eq!(shuffled, input);
+            }
+        }
+    }
+
+    #[test]
+    fn seed_determines_permutation() {
+        let shuffled = |seed| {
+            let mut input: Vec<usize> = (0..100).collect();
+            shuffle(&mut input, seed);
+            input
+        };
+        assert_eq!(shuffled(7), shuffled(7));
+        assert_ne!(shuffled(7), shuffled(8));
+        assert_ne!(shuffled(7), (0..100).collect::<Vec<_>>());
+    }
+}
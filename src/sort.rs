@@ -0,0 +1,233 @@
+//! Parallel merge sort. Input is split into contiguous runs that are sorted on their own, and adjacent runs are then
+//! merged pairwise in rounds, with the pairs of a round merged in parallel. Ties are always resolved in favor of the
+//! earlier run, so the sort is stable.
+
+use crate::equal;
+use crate::worker::Spawner;
+use crate::{SpawnError, WorkConfig};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Merges sorted `left` and `right`. Elements of `left` go first on ties.
+fn merge<T, F>(left: Vec<T>, right: Vec<T>, compare: &F) -> Vec<T>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut output = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        let next = if compare(r, l) == Ordering::Less {
+            right.next()
+        } else {
+            left.next()
+        };
+        output.extend(next);
+    }
+    output.extend(left);
+    output.extend(right);
+    output
+}
+
+/// Merges sorted `runs` in order into a single sorted vector. Each round merges adjacent pairs of runs in parallel,
+/// so there are `log2(runs)` rounds, the last of which merges the two remaining runs on the calling thread.
+pub(crate) fn merge_runs<'env, S, F, T>(
+    config: &WorkConfig,
+    spawner: &S,
+    mut runs: Vec<Vec<T>>,
+    compare: Arc<F>,
+) -> Result<Vec<T>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    F: Fn(&T, &T) -> Ordering + Send + Sync + 'env,
+{
+    while runs.len() > 2 {
+        let odd = if runs.len() % 2 == 1 {
+            runs.pop()
+        } else {
+            None
+        };
+        let mut pairs = Vec::with_capacity(runs.len() / 2);
+        let mut runs_iter = runs.into_iter();
+        while let (Some(left), Some(right)) = (runs_iter.next(), runs_iter.next()) {
+            pairs.push((left, right));
+        }
+        let compare = compare.clone();
+        runs = equal::divide_each(config, spawner, pairs, move |(left, right)| {
+            merge(left, right, &*compare)
+        })?;
+        runs.extend(odd);
+    }
+    let mut runs = runs.into_iter();
+    Ok(match (runs.next(), runs.next()) {
+        (Some(left), Some(right)) => merge(left, right, &*compare),
+        (left, _) => left.unwrap_or_default(),
+    })
+}
+
+/// Parallel part of [divide_sort_by](crate::divide_sort_by). Input is split into a run per worker, each of which is
+/// sorted with [slice::sort_by]. `input` is expected to be large enough to be split.
+pub(crate) fn divide_sort_by<'env, S, F, T>(
+    config: &WorkConfig,
+    spawner: &S,
+    input: Vec<T>,
+    compare: F,
+) -> Result<Vec<T>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    F: Fn(&T, &T) -> Ordering + Send + Sync + 'env,
+{
+    let runs = equal::split_vecs(config, input);
+    let compare = Arc::new(compare);
+    let sort = {
+        let compare = compare.clone();
+        move |mut run: Vec<T>| {
+            run.sort_by(&*compare);
+            run
+        }
+    };
+    let runs = equal::divide_each(config, spawner, runs, sort)?;
+    merge_runs(config, spawner, runs, compare)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shuffle::shuffle;
+    use crate::{PanicPolicy, Schedule, WorkConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn sorted_permutation() {
+        for threads in [2, 3, 8] {
+            let config = WorkConfig::new().threads(threads).threshold(1);
+            for seed in 0..20 {
+                let len = seed as usize * 37;
+                // Few distinct keys, so that stability matters, tagged with their original positions
+                let mut input: Vec<(u64, usize)> = (0..len).map(|i| (i as u64 % 7, 0)).collect();
+                shuffle(&mut input, seed);
+                for (i, val) in input.iter_mut().enumerate() {
+                    val.1 = i;
+                }
+                let mut expected = input.clone();
+                expected.sort_by_key(|&(key, _)| key);
+                assert_eq!(
+                    config.divide_sort_by_key(input.clone(), |&(key, _)| key),
+                    expected
+                );
+                expected.sort();
+                assert_eq!(config.divide_sort(input.clone()), expected);
+                expected.reverse();
+                assert_eq!(config.divide_sort_by(input, |a, b| b.cmp(a)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn below_threshold() {
+        let config = WorkConfig::new().threads(4).threshold(100);
+        assert_eq!(config.divide_sort(vec![3, 1, 2]), [1, 2, 3]);
+        assert!(config.divide_sort(Vec::<u8>::new()).is_empty());
+        let config = WorkConfig::new().threads(4).min_chunk_size(1000);
+        assert_eq!(
+            config.divide_sort((0..500).rev().collect()),
+            (0..500).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "bad comparison")]
+    fn comparator_panic() {
+        let config = WorkConfig::new().threads(4).threshold(1);
+        config.divide_sort_by((0..100).collect(), |a: &i32, b| {
+            assert!(*a != 50 && *b != 50, "bad comparison");
+            a.cmp(b)
+        });
+    }
+
+    #[test]
+    fn cached_key_called_once() {
+        let calls = AtomicUsize::new(0);
+        let key = |word: &String| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            word.to_lowercase()
+        };
+        for config in [
+            WorkConfig::new().threads(4),
+            WorkConfig::new().threads(4).schedule(Schedule::Static),
+            WorkConfig::adaptive().threads(4),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            for seed in 0..10 {
+                let mut input: Vec<String> = (0..seed as usize * 50)
+                    .map(|i| {
+                        if i % 2 == 0 {
+                            format!("W{}", i % 13)
+                        } else {
+                            format!("w{}", i % 13)
+                        }
+                    })
+                    .collect();
+                shuffle(&mut input, seed);
+                let mut expected = input.clone();
+                expected.sort_by_cached_key(|word| word.to_lowercase());
+                assert_eq!(
+                    config.divide_sort_by_cached_key(input.clone(), key),
+                    expected
+                );
+                assert_eq!(calls.swap(0, Ordering::Relaxed), input.len());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "bad key")]
+    fn cached_key_panics_with_skip_item() {
+        let config = WorkConfig::new()
+            .threads(4)
+            .threshold(1)
+            .panic_policy(PanicPolicy::SkipItem);
+        config.divide_sort_by_cached_key((0..100).collect(), |&x: &i32| {
+            assert_ne!(x, 50, "bad key");
+            -x
+        });
+    }
+
+    #[test]
+    fn merge_sorted_shards() {
+        // Shards of very different lengths, some of them empty, with values that repeat across shards. Every element
+        // is tagged with its shard and position to check stability.
+        let lengths = [0, 1, 500, 0, 3, 2000, 7, 0, 64, 1];
+        let shards: Vec<Vec<(u32, usize, usize)>> = lengths
+            .iter()
+            .enumerate()
+            .map(|(shard, &len)| {
+                (0..len)
+                    .map(|i| (i as u32 * 5 / len as u32, shard, i))
+                    .collect()
+            })
+            .collect();
+        let mut expected: Vec<_> = shards.concat();
+        expected.sort_by_key(|&(val, _, _)| val);
+        for config in [
+            WorkConfig::new().threads(2).threshold(1),
+            WorkConfig::new().threads(3).threshold(1),
+            WorkConfig::new().threads(8).threshold(1),
+            WorkConfig::new().threshold(usize::MAX),
+        ] {
+            let merged = config.divide_merge_sorted_by(shards.clone(), |a, b| a.0.cmp(&b.0));
+            assert_eq!(merged, expected);
+            for count in 0..=lengths.len() {
+                let shards = shards[..count].to_vec();
+                let mut expected = shards.concat();
+                expected.sort();
+                assert_eq!(config.divide_merge_sorted(shards), expected);
+            }
+        }
+        let config = WorkConfig::new().threads(4).threshold(1);
+        assert!(config
+            .divide_merge_sorted(vec![Vec::<u8>::new(); 5])
+            .is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::worker::lock;
+use crate::{Decision, Schedule};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+/// Report of [divide_work_with_stats](crate::divide_work_with_stats)
+#[derive(Debug, Clone)]
+pub struct WorkStats {
+    /// Threads that processed at least one element, in order of their first element. Calling thread takes part in the
+    /// work, so it is one of them unless every element was processed elsewhere.
+    pub workers: Vec<WorkerStats>,
+    /// Time the whole call took
+    pub wall_time: Duration,
+    /// Schedule that was configured for the call
+    pub schedule: Schedule,
+    /// Whether work was split between threads
+    pub decision: Decision,
+}
+
+impl WorkStats {
+    /// Amount of processed elements, which is the length of the input unless some of the elements panicked
+    pub fn items(&self) -> usize {
+        self.workers.iter().map(|worker| worker.items).sum()
+    }
+}
+
+/// Work done by a single thread during a call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Thread that processed the elements
+    pub thread: ThreadId,
+    /// Amount of elements processed by the thread
+    pub items: usize,
+    /// Core the thread was pinned to with `WorkConfig::pin_threads`. `None` if it wasn't pinned, which is always the
+    /// case for the calling thread and without `affinity` feature.
+    pub core: Option<usize>,
+    /// Time the thread spent in `f`. The rest of [WorkStats::wall_time] it was idle, waiting for work or scheduling
+    /// it.
+    pub busy: Duration,
+}
+
+#[derive(Default)]
+struct Counters {
+    items: AtomicUsize,
+    busy_nanos: AtomicU64,
+}
+
+static NEXT_COLLECTOR: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Counters of this thread for every collector it has recorded to. Entries of finished calls are dead, and are
+    /// dropped once the thread records to another collector.
+    static LOCAL: RefCell<Vec<(u64, Weak<Counters>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Collects [WorkerStats] of a call. Every thread gets its own counters the first time it records an element, so
+/// recording is a couple of uncontended atomic additions.
+pub(crate) struct Collector {
+    id: u64,
+    workers: Mutex<Vec<Recorder>>,
+}
+
+/// Thread that recorded elements to a [Collector]
+struct Recorder {
+    thread: ThreadId,
+    core: Option<usize>,
+    counters: Arc<Counters>,
+}
+
+impl Collector {
+    pub(crate) fn new() -> Self {
+        Collector {
+            id: NEXT_COLLECTOR.fetch_add(1, Ordering::Relaxed),
+            workers: Mutex::default(),
+        }
+    }
+
+    /// Records that this thread processed an element for `busy` time
+    pub(crate) fn record(&self, busy: Duration) {
+        let counters = self.counters();
+        counters.items.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(busy.as_nanos()).unwrap_or(u64::MAX);
+        counters.busy_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn counters(&self) -> Arc<Counters> {
+        LOCAL.with(|local| {
+            let mut local = local.borrow_mut();
+            let own = local.iter().find(|(id, _)| *id == self.id);
+            if let Some(counters) = own.and_then(|(_, counters)| counters.upgrade()) {
+                return counters;
+            }
+            local.retain(|(_, counters)| counters.strong_count() > 0);
+            let counters = Arc::<Counters>::default();
+            local.push((self.id, Arc::downgrade(&counters)));
+            #[cfg(feature = "affinity")]
+            let core = crate::affinity::pinned_core();
+            #[cfg(not(feature = "affinity"))]
+            let core = None;
+            lock(&self.workers).push(Recorder {
+                thread: thread::current().id(),
+                core,
+                counters: counters.clone(),
+            });
+            counters
+        })
+    }
+
+    /// Report of workers that recorded elements
+    pub(crate) fn finish(
+        &self,
+        wall_time: Duration,
+        schedule: Schedule,
+        decision: Decision,
+    ) -> WorkStats {
+        let workers = lock(&self.workers)
+            .iter()
+            .map(|recorder| WorkerStats {
+                thread: recorder.thread,
+                items: recorder.counters.items.load(Ordering::Relaxed),
+                core: recorder.core,
+                busy: Duration::from_nanos(recorder.counters.busy_nanos.load(Ordering::Relaxed)),
+            })
+            .collect();
+        WorkStats {
+            workers,
+            wall_time,
+            schedule,
+            decision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decision, Schedule, WorkConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn item_counts_sum_to_input_length() {
+        for schedule in [
+            Schedule::Dynamic,
+            Schedule::Static,
+            Schedule::Guided { min_chunk: 4 },
+        ] {
+            let config = WorkConfig::new().threads(4).schedule(schedule);
+            let (output, stats) = config.divide_work_with_stats((0..1000).collect(), |x| x * 2);
+            assert_eq!(output, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+            assert_eq!(stats.items(), 1000);
+            assert!(stats.workers.len() <= 4);
+            assert_eq!(stats.schedule, schedule);
+            assert!(stats.decision.is_parallel());
+        }
+    }
+
+    #[test]
+    fn sequential_stats() {
+        let (output, stats) = WorkConfig::new()
+            .threads(4)
+            .divide_work_with_stats((0..5).collect(), |x| x + 1);
+        assert_eq!(output, (1..6).collect::<Vec<_>>());
+        assert!(matches!(
+            stats.decision,
+            Decision::BelowThreshold { len: 5, .. }
+        ));
+        assert_eq!(stats.workers.len(), 1);
+        assert_eq!(stats.workers[0].thread, std::thread::current().id());
+        assert_eq!(stats.workers[0].items, 5);
+    }
+
+    #[test]
+    fn busy_time() {
+        let (_, stats) = WorkConfig::new()
+            .threads(2)
+            .divide_work_with_stats((0..20).collect(), |_| {
+                std::thread::sleep(Duration::from_millis(1))
+            });
+        let busy: Duration = stats.workers.iter().map(|worker| worker.busy).sum();
+        assert!(busy >= Duration::from_millis(20), "{:?}", busy);
+        assert!(stats
+            .workers
+            .iter()
+            .all(|worker| worker.busy <= stats.wall_time));
+    }
+
+    #[test]
+    fn nested_calls_are_separate() {
+        let config = WorkConfig::new().threads(2);
+        let inner_config = config.clone();
+        let (output, stats) = config.divide_work_with_stats((0..20).collect(), move |x| {
+            let (inner, stats) = inner_config.divide_work_with_stats((0..20).collect(), |y| y);
+            assert_eq!(stats.items(), 20);
+            x + inner.len()
+        });
+        assert_eq!(output[0], 20);
+        assert_eq!(stats.items(), 20);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::equal::chunk_start;
+use crate::output::{Output, Sink};
+use crate::worker::{self, CancelOnPanic, Spawner, StopFlag};
+use crate::{SpawnError, WorkConfig};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Contiguous range of input elements owned by a worker. The owner takes elements from the back, last one first, and
+/// thieves take the back half, so both the owner and the thief keep a contiguous range.
+struct Deque<T> {
+    /// Index of the first element of `items` in the original input
+    start: usize,
+    items: VecDeque<T>,
+}
+
+impl<T> Deque<T> {
+    /// Takes the last element along with its index in the original input
+    fn pop(&mut self) -> Option<(usize, T)> {
+        let val = self.items.pop_back()?;
+        Some((self.start + self.items.len(), val))
+    }
+
+    /// Takes the back half of the elements, rounding up, so that both halves stay contiguous
+    fn steal_half(&mut self) -> Option<Deque<T>> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let keep = self.items.len() / 2;
+        Some(Deque {
+            start: self.start + keep,
+            items: self.items.split_off(keep),
+        })
+    }
+}
+
+/// Moves half of the elements of the first non-empty deque of other workers into the deque of worker `own`. Returns
+/// whether anything was stolen. Deques of the victims are locked one at a time, so workers never wait on each other
+/// while holding a lock.
+fn steal<T>(deques: &[Mutex<Deque<T>>], own: usize) -> bool {
+    let victims = (1..deques.len()).map(|offset| (own + offset) % deques.len());
+    for victim in victims {
+        let stolen = worker::lock(&deques[victim]).steal_half();
+        if let Some(stolen) = stolen {
+            *worker::lock(&deques[own]) = stolen;
+            return true;
+        }
+    }
+    false
+}
+
+/// Processes elements of the deque of worker `own` from its back, stealing from other workers once it is empty, until
+/// there is nothing left to steal or work is cancelled. Results are written to `output` at indices of their elements
+/// in the original input. If `cancel_on_panic` is set, work is cancelled when `f` panics on any of the workers.
+fn process_deques<F, T, R>(
+    deques: &[Mutex<Deque<T>>],
+    own: usize,
+    stop: &StopFlag,
+    cancel_on_panic: bool,
+    f: &F,
+    output: &Sink<R>,
+) where
+    F: Fn(T) -> R,
+{
+    let _guard = cancel_on_panic.then(|| CancelOnPanic(stop));
+    let mut writer = output.writer();
+    while !stop.is_stopped() {
+        // Stolen elements are in transit between deques for a moment, but they are owned by the thief, so a worker
+        // that sees every deque empty can finish without losing any work
+        let next = worker::lock(&deques[own]).pop();
+        match next {
+            Some((idx, val)) => {
+                let r = f(val);
+                // SAFETY: Elements are moved between deques, so each of them is popped exactly once
+                unsafe { writer.write(idx, r) }
+            }
+            None if steal(deques, own) => continue,
+            None => break,
+        }
+    }
+}
+
+/// Parallel part of [divide_work_stealing](crate::divide_work_stealing). `input` is expected to be large enough to be
+/// split.
+pub(crate) fn divide_work_stealing<'env, S, F, T, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    mut input: Vec<T>,
+    f: F,
+) -> Result<Vec<R>, SpawnError>
+where
+    S: Spawner<'env>,
+    T: Send + 'env,
+    R: Send + 'env,
+    F: Fn(T) -> R + Send + Sync + 'env,
+{
+    let length = input.len();
+    let threads = config.workers_for(length);
+
+    // Each worker starts with a contiguous range of the input, as with equal split
+    let mut deques: Vec<_> = (0..threads)
+        .rev()
+        .map(|worker| {
+            let start = chunk_start(length, threads, worker);
+            let items = input.split_off(start).into();
+            Mutex::new(Deque { start, items })
+        })
+        .collect();
+    deques.reverse();
+    let deques = Arc::new(deques);
+    let stop = Arc::new(StopFlag::new());
+    let cancel_on_panic = config.cancels_on_panic();
+    let mut output = Output::new(length);
+    let sink = output.sink();
+
+    let f = Arc::new(f);
+    let work = |own: usize| {
+        let deques = deques.clone();
+        let stop = stop.clone();
+        let f = f.clone();
+        let sink = sink.clone();
+        move || process_deques(&deques, own, &stop, cancel_on_panic, &*f, &sink)
+    };
+    // Last deque is processed on the calling thread
+    let jobs = (0..threads - 1).map(|index| (index, work(index)));
+    let results = worker::run(config, spawner, &stop, jobs, work(threads - 1))?;
+    Ok(output.finish(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::FailingSpawner;
+    use crate::SpawnFailurePolicy;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    #[test]
+    fn deque_halves_are_contiguous() {
+        let mut deque = Deque {
+            start: 10,
+            items: (10..16).collect(),
+        };
+        assert_eq!(deque.pop(), Some((15, 15)));
+
+        let mut stolen = deque.steal_half().unwrap();
+        assert_eq!(stolen.start, 12);
+        assert_eq!(stolen.pop(), Some((14, 14)));
+        assert_eq!(stolen.pop(), Some((13, 13)));
+        assert_eq!(stolen.pop(), Some((12, 12)));
+        assert_eq!(stolen.pop(), None);
+
+        assert_eq!(deque.pop(), Some((11, 11)));
+        let mut stolen = deque.steal_half().unwrap();
+        assert_eq!(stolen.pop(), Some((10, 10)));
+        assert!(deque.steal_half().is_none());
+    }
+
+    #[test]
+    fn ordered_output() {
+        for len in [2, 3, 100, 1001] {
+            let output = WorkConfig::new()
+                .threads(4)
+                .divide_work_stealing((0..len).collect(), |x| x * 2);
+            assert_eq!(output, (0..len).map(|x| x * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn idle_workers_steal() {
+        // All expensive elements are in the range of the first worker
+        let output = WorkConfig::new()
+            .threads(2)
+            .divide_work_stealing((0..100).collect(), |x| {
+                if x < 50 {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                std::thread::current().id()
+            });
+        let threads: HashSet<_> = output[..50].iter().collect();
+        assert_eq!(threads.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "bad item")]
+    fn panic_in_any_worker() {
+        WorkConfig::new()
+            .threads(4)
+            .divide_work_stealing((0..100).collect(), |x| {
+                assert_ne!(x, 50, "bad item");
+                x
+            });
+    }
+
+    #[test]
+    fn spawn_failure() {
+        let config = WorkConfig::new().threads(4);
+        let res =
+            divide_work_stealing(&config, &FailingSpawner::new(1), (0..1000).collect(), |x| x);
+        assert!(res.is_err());
+
+        let config = config.on_spawn_failure(SpawnFailurePolicy::Degrade);
+        let output =
+            divide_work_stealing(&config, &FailingSpawner::new(1), (0..1000).collect(), |x| x);
+        assert_eq!(output.unwrap(), (0..1000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::OnceLock;
+#[cfg(target_os = "linux")]
+use std::{collections::HashSet, fs};
+
+/// Environment variable overriding default amount of worker threads
+const THREADS_ENV_VAR: &str = "AND_CONQUER_THREADS";
+
+static DEFAULT_PARALLELISM: OnceLock<usize> = OnceLock::new();
+static PHYSICAL_PARALLELISM: OnceLock<usize> = OnceLock::new();
+
+/// Amount of worker threads used when it is not set explicitly with [WorkConfig::threads](crate::WorkConfig::threads).
+///
+/// This is the amount of parallelism available to the process as reported by
+/// [std::thread::available_parallelism], which respects CPU affinity masks and cgroup quotas on platforms that
+/// support them. It can be overridden with `AND_CONQUER_THREADS` environment variable. Both are queried once on
+/// first use, later changes have no effect.
+pub fn default_parallelism() -> usize {
+    *DEFAULT_PARALLELISM.get_or_init(|| {
+        let var = std::env::var(THREADS_ENV_VAR).ok();
+        parse_threads(var.as_deref()).unwrap_or_else(available_parallelism)
+    })
+}
+
+/// Same as [default_parallelism], but hardware threads that share a physical core count once. Used with
+/// [CoreSelection::Physical](crate::CoreSelection::Physical).
+///
+/// Parallelism available to the process is scaled by the share of physical cores among all hardware threads of the
+/// machine, so a process limited to 8 hardware threads on a machine with 2-way SMT gets 4. Topology is only detected
+/// on Linux, elsewhere this is the same as [default_parallelism]. `AND_CONQUER_THREADS` environment variable takes
+/// precedence, same as for [default_parallelism].
+pub fn physical_parallelism() -> usize {
+    *PHYSICAL_PARALLELISM.get_or_init(|| {
+        let var = std::env::var(THREADS_ENV_VAR).ok();
+        parse_threads(var.as_deref())
+            .unwrap_or_else(|| physical_share(available_parallelism(), topology()))
+    })
+}
+
+/// Amount of physical cores among `available` hardware threads, given `(hardware threads, physical cores)` of the
+/// whole machine
+fn physical_share(available: usize, topology: Option<(usize, usize)>) -> usize {
+    match topology {
+        Some((logical, physical)) if logical > 0 => (available * physical / logical).max(1),
+        _ => available,
+    }
+}
+
+/// Amounts of online hardware threads and physical cores of the machine. Hardware threads of the same core have the
+/// same list of siblings.
+#[cfg(target_os = "linux")]
+fn topology() -> Option<(usize, usize)> {
+    let mut logical = 0;
+    let mut cores = HashSet::new();
+    for entry in fs::read_dir("/sys/devices/system/cpu").ok()?.flatten() {
+        let name = entry.file_name();
+        let is_cpu = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu"))
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()));
+        // Offline hardware threads have no topology
+        let siblings = entry.path().join("topology/thread_siblings_list");
+        if let (true, Ok(siblings)) = (is_cpu, fs::read_to_string(siblings)) {
+            logical += 1;
+            cores.insert(siblings.trim().to_owned());
+        }
+    }
+    Some((logical, cores.len()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn topology() -> Option<(usize, usize)> {
+    None
+}
+
+/// Parallelism reported by the OS. If it can't be determined, work is done sequentially rather than guessing a
+/// thread count that may oversubscribe the machine.
+fn available_parallelism() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Parses value of `AND_CONQUER_THREADS`. Unset, unparseable and `0` values mean that thread count is not overridden.
+fn parse_threads(value: Option<&str>) -> Option<usize> {
+    value?.trim().parse().ok().filter(|&threads| threads > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorkConfig;
+
+    #[test]
+    fn parse_env_var() {
+        assert_eq!(parse_threads(None), None);
+        assert_eq!(parse_threads(Some("")), None);
+        assert_eq!(parse_threads(Some("many")), None);
+        assert_eq!(parse_threads(Some("-4")), None);
+        assert_eq!(parse_threads(Some("0")), None);
+        assert_eq!(parse_threads(Some("1")), Some(1));
+        assert_eq!(parse_threads(Some(" 64\n")), Some(64));
+    }
+
+    #[test]
+    fn explicit_threads_override_env_var() {
+        assert_eq!(WorkConfig::new().worker_count(), default_parallelism());
+        assert_eq!(WorkConfig::new().threads(3).worker_count(), 3);
+        assert_eq!(WorkConfig::new().threads(1).worker_count(), 1);
+    }
+
+    #[test]
+    fn physical_cores() {
+        // 2-way SMT
+        assert_eq!(physical_share(16, Some((16, 8))), 8);
+        assert_eq!(physical_share(8, Some((16, 8))), 4);
+        // Limited to a single hardware thread
+        assert_eq!(physical_share(1, Some((16, 8))), 1);
+        // No SMT
+        assert_eq!(physical_share(12, Some((12, 12))), 12);
+        // Unknown topology
+        assert_eq!(physical_share(6, None), 6);
+        assert_eq!(physical_share(6, Some((0, 0))), 6);
+        assert!(
+            physical_parallelism() <= default_parallelism()
+                || std::env::var_os(THREADS_ENV_VAR).is_some()
+        );
+    }
+
+    #[test]
+    fn core_selection() {
+        use crate::CoreSelection;
+        let config = WorkConfig::new().cores(CoreSelection::Exact(6));
+        assert_eq!(config.worker_count(), 6);
+        assert_eq!(config.clone().reserve_cores(2).worker_count(), 4);
+        assert_eq!(config.threads(3).worker_count(), 3);
+        assert_eq!(
+            WorkConfig::new()
+                .cores(CoreSelection::Exact(0))
+                .worker_count(),
+            1
+        );
+        let physical = WorkConfig::new().cores(CoreSelection::Physical);
+        assert_eq!(physical.worker_count(), physical_parallelism());
+    }
+
+    #[test]
+    fn default_parallelism_without_env_var() {
+        if std::env::var_os(THREADS_ENV_VAR).is_none() {
+            assert_eq!(default_parallelism(), available_parallelism());
+        }
+        assert!(default_parallelism() >= 1);
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{pool, SpawnError, SpawnFailurePolicy, WorkConfig};
+use std::cell::Cell;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle, ScopedJoinHandle};
+
+/// Name prefix of worker threads unless configured otherwise
+const DEFAULT_THREAD_NAME_PREFIX: &str = "and-conquer";
+
+/// Creates worker threads. Abstracted away, so that tests can simulate failures to create a thread. Workers may borrow
+/// data that lives for `'env`.
+pub(crate) trait Spawner<'env> {
+    /// Starts worker number `index` configured according to `config`
+    fn spawn<F, R>(&self, config: &WorkConfig, index: usize, f: F) -> io::Result<Handle<'env, R>>
+    where
+        F: FnOnce() -> R + Send + 'env,
+        R: Send + 'env;
+}
+
+/// Runs workers on the threads of the [pool], or on new OS threads if
+/// [spawn_per_call](WorkConfig::spawn_per_call) is set
+pub(crate) struct OsSpawner;
+
+impl Spawner<'static> for OsSpawner {
+    fn spawn<F, R>(&self, config: &WorkConfig, index: usize, f: F) -> io::Result<Handle<'static, R>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if config.spawn_per_call {
+            builder(config, index).spawn(f).map(Handle::Thread)
+        } else {
+            pool::spawn(thread_name(config, index), config.stack_size, f).map(Handle::Pooled)
+        }
+    }
+}
+
+/// Runs workers on threads of a [thread::scope], so that they can borrow data of the caller
+pub(crate) struct ScopedSpawner<'scope, 'env>(pub(crate) &'scope thread::Scope<'scope, 'env>);
+
+impl<'scope> Spawner<'scope> for ScopedSpawner<'scope, '_> {
+    fn spawn<F, R>(&self, config: &WorkConfig, index: usize, f: F) -> io::Result<Handle<'scope, R>>
+    where
+        F: FnOnce() -> R + Send + 'scope,
+        R: Send + 'scope,
+    {
+        builder(config, index)
+            .spawn_scoped(self.0, f)
+            .map(Handle::Scoped)
+    }
+}
+
+/// Running worker, which may borrow data that lives for `'env`
+pub(crate) enum Handle<'env, R> {
+    Thread(JoinHandle<R>),
+    Pooled(pool::Task<R>),
+    Scoped(ScopedJoinHandle<'env, R>),
+}
+
+impl<R> Handle<'_, R> {
+    fn join(self) -> thread::Result<R> {
+        match self {
+            Handle::Thread(handle) => handle.join(),
+            Handle::Pooled(task) => task.join(),
+            Handle::Scoped(handle) => handle.join(),
+        }
+    }
+}
+
+/// Name of worker thread number `index`, `<prefix>-<index>`
+fn thread_name(config: &WorkConfig, index: usize) -> String {
+    let prefix = config
+        .thread_name_prefix
+        .as_deref()
+        .unwrap_or(DEFAULT_THREAD_NAME_PREFIX);
+    format!("{}-{}", prefix, index)
+}
+
+/// Builder of worker thread number `index` configured according to `config`. Workers are named with [thread_name]
+/// and use configured stack size if there is one.
+pub(crate) fn builder(config: &WorkConfig, index: usize) -> thread::Builder {
+    let mut builder = thread::Builder::new().name(thread_name(config, index));
+    if let Some(size) = config.stack_size {
+        builder = builder.stack_size(size);
+    }
+    builder
+}
+
+thread_local! {
+    /// Whether this thread is running a job of a call of this crate, including jobs run on the calling thread
+    static IN_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is processing elements of a call of this crate, so that a call made from `f` is nested
+pub(crate) fn in_worker() -> bool {
+    IN_WORKER.with(Cell::get)
+}
+
+/// Runs `job` with the current thread marked as a worker. The mark is restored afterwards, including when `job`
+/// panics, since threads of the pool and the calling thread outlive the job.
+fn as_worker<R>(job: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            IN_WORKER.with(|in_worker| in_worker.set(self.0));
+        }
+    }
+
+    let _restore = Restore(IN_WORKER.with(|in_worker| in_worker.replace(true)));
+    job()
+}
+
+/// `f` behind dynamic dispatch. Schedulers that are given `|val| f.call(val)` are instantiated once per input and
+/// result types, instead of once per closure type of the caller, which keeps code size and compile times in check
+/// when the crate is called with many different closures.
+pub(crate) struct DynFn<'env, T, R>(Arc<dyn Fn(T) -> R + Send + Sync + 'env>);
+
+impl<'env, T, R> DynFn<'env, T, R> {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(T) -> R + Send + Sync + 'env,
+    {
+        DynFn(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, val: T) -> R {
+        (self.0)(val)
+    }
+}
+
+impl<T, R> Clone for DynFn<'_, T, R> {
+    fn clone(&self) -> Self {
+        DynFn(self.0.clone())
+    }
+}
+
+/// Shared flag that tells workers to stop picking up new elements. Workers check it before each element, which is a
+/// single relaxed load, and finish elements that are already being processed. Used to cancel the rest of the work on
+/// errors, panics and timeouts.
+#[derive(Debug, Default)]
+pub(crate) struct StopFlag(AtomicBool);
+
+impl StopFlag {
+    pub(crate) fn new() -> Self {
+        StopFlag(AtomicBool::new(false))
+    }
+
+    /// Tells workers to stop
+    pub(crate) fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether workers were told to stop
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Sets the flag if dropped while the thread is panicking. Workers hold it while processing elements, so that other
+/// workers stop picking up new elements as soon as one of them panics, instead of finishing the whole input only for
+/// the result to be discarded.
+pub(crate) struct CancelOnPanic<'a>(pub(crate) &'a StopFlag);
+
+impl Drop for CancelOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.stop();
+        }
+    }
+}
+
+/// Locks `mutex`, recovering it if it is poisoned. Data guarded by the locks of this crate is consistent at any point,
+/// so a panic while holding one of them leaves nothing to repair. Recovering ensures that only the original panic is
+/// propagated to the caller, instead of panics of all the other workers on a poisoned lock.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Error shared between workers of fallible variants. Only the first reported error is kept.
+pub(crate) struct FirstError<E> {
+    error: Mutex<Option<E>>,
+}
+
+impl<E> FirstError<E> {
+    pub(crate) fn new() -> Self {
+        FirstError {
+            error: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn set(&self, err: E) {
+        let mut error = lock(&self.error);
+        if error.is_none() {
+            *error = Some(err);
+        }
+    }
+
+    pub(crate) fn take(&self) -> Option<E> {
+        lock(&self.error).take()
+    }
+}
+
+/// Starts `job` as worker number `index`. Gives `job` back along with the error if the worker couldn't be started, so
+/// that it can still be run elsewhere.
+fn spawn<'env, S, W, R>(
+    spawner: &S,
+    config: &WorkConfig,
+    index: usize,
+    job: W,
+) -> Result<Handle<'env, R>, (io::Error, W)>
+where
+    S: Spawner<'env>,
+    W: FnOnce() -> R + Send + 'env,
+    R: Send + 'env,
+{
+    let slot = Arc::new(Mutex::new(Some(job)));
+    let thread_slot = slot.clone();
+    #[cfg(feature = "affinity")]
+    let pin = config.pin(index);
+    let res = spawner.spawn(config, index, move || {
+        let job = lock(&thread_slot).take();
+        let job = job.expect("worker job is taken only once");
+        #[cfg(feature = "affinity")]
+        if let Some(pin) = pin {
+            return as_worker(|| pin.run(job));
+        }
+        as_worker(job)
+    });
+    res.map_err(|err| {
+        let job = lock(&slot).take();
+        (
+            err,
+            job.expect("failed to spawn worker, but its job was taken"),
+        )
+    })
+}
+
+/// Spawns a worker thread for each of `(index, job)` in `jobs`, then runs `inline` job on the calling thread. Returns
+/// results of all jobs in the same order, followed by result of `inline`, once every one of them has finished,
+/// including the ones that panicked, so that none of the workers is left running.
+///
+/// If a worker fails to spawn, [SpawnFailurePolicy] of `config` decides what happens. With
+/// [SpawnFailurePolicy::Degrade] jobs that weren't spawned are run on the calling thread before `inline`, if at least
+/// one worker was spawned. Otherwise `stop` is set so that already spawned workers stop early, and they are
+/// joined before returning the error. `inline` job is not run in this case. If some of the joined workers panicked,
+/// the panic is propagated instead of the error.
+pub(crate) fn run<'env, S, J, W, I, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    stop: &StopFlag,
+    jobs: J,
+    inline: I,
+) -> Result<Vec<thread::Result<R>>, SpawnError>
+where
+    S: Spawner<'env>,
+    J: IntoIterator<Item = (usize, W)>,
+    W: FnOnce() -> R + Send + 'env,
+    I: FnOnce() -> R,
+    R: Send + 'env,
+{
+    let mut jobs = jobs.into_iter();
+    let mut workers = Vec::new();
+    let mut unspawned = Vec::new();
+    for (index, job) in &mut jobs {
+        match spawn(spawner, config, index, job) {
+            Ok(worker) => workers.push(worker),
+            Err((err, job)) => {
+                let err = SpawnError::new(err);
+                if config.spawn_failure_policy == SpawnFailurePolicy::Degrade && !workers.is_empty()
+                {
+                    if let Some(hook) = config.spawn_failure_hook {
+                        hook(&err);
+                    }
+                    unspawned.push(job);
+                    break;
+                }
+
+                stop.stop();
+                for res in join_all(workers) {
+                    if let Err(payload) = res {
+                        panic::resume_unwind(payload);
+                    }
+                }
+                return Err(err);
+            }
+        }
+    }
+    // Failure to spawn is likely to repeat, so the rest of the jobs aren't even attempted
+    unspawned.extend(jobs.map(|(_, job)| job));
+
+    let unspawned_res = unspawned
+        .into_iter()
+        .map(|job| panic::catch_unwind(AssertUnwindSafe(|| as_worker(job))))
+        .collect::<Vec<_>>();
+    let inline_res = panic::catch_unwind(AssertUnwindSafe(|| as_worker(inline)));
+
+    let mut results = join_all(workers);
+    results.extend(unspawned_res);
+    results.push(inline_res);
+    Ok(results)
+}
+
+/// Same as [run], but none of the workers are started up front. `inline` job is given a function that starts the next
+/// worker of `jobs`, which it calls whenever it decides that there is enough work left for one more worker. The
+/// function returns `false` once there are no more jobs, or a worker failed to start, in which case the error is
+/// reported to the spawn failure hook, and no more workers are started. Jobs that weren't started are dropped without
+/// running, and only results of the started ones are returned, followed by result of `inline`.
+pub(crate) fn run_ramped<'env, S, J, W, I, R>(
+    config: &WorkConfig,
+    spawner: &S,
+    jobs: J,
+    inline: I,
+) -> Vec<thread::Result<R>>
+where
+    S: Spawner<'env>,
+    J: IntoIterator<Item = (usize, W)>,
+    W: FnOnce() -> R + Send + 'env,
+    I: FnOnce(&mut dyn FnMut() -> bool) -> R,
+    R: Send + 'env,
+{
+    let mut jobs = jobs.into_iter();
+    let mut workers = Vec::new();
+    let mut exhausted = false;
+    let mut start_next = || {
+        let Some((index, job)) = jobs.next().filter(|_| !exhausted) else {
+            return false;
+        };
+        match spawn(spawner, config, index, job) {
+            Ok(worker) => {
+                workers.push(worker);
+                true
+            }
+            Err((err, _)) => {
+                if let Some(hook) = config.spawn_failure_hook {
+                    hook(&SpawnError::new(err));
+                }
+                exhausted = true;
+                false
+            }
+        }
+    };
+    let inline_res =
+        panic::catch_unwind(AssertUnwindSafe(|| as_worker(|| inline(&mut start_next))));
+
+    let mut results = join_all(workers);
+    results.push(inline_res);
+    results
+}
+
+fn join_all<R>(workers: Vec<Handle<'_, R>>) -> Vec<thread::Result<R>> {
+    workers.into_iter().map(|w| w.join()).collect()
+}
+
+/// Spawns `successes` threads, then fails to spawn any more
+#[cfg(test)]
+pub(crate) struct FailingSpawner {
+    successes: usize,
+    spawned: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl FailingSpawner {
+    pub(crate) fn new(successes: usize) -> Self {
+        FailingSpawner {
+            successes,
+            spawned: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Spawner<'static> for FailingSpawner {
+    fn spawn<F, R>(&self, config: &WorkConfig, index: usize, f: F) -> io::Result<Handle<'static, R>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if self.spawned.fetch_add(1, Ordering::Relaxed) < self.successes {
+            OsSpawner.spawn(config, index, f)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "simulated spawn failure",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decision, WorkConfig};
+    use std::thread;
+
+    fn thread_name(_: usize) -> Option<String> {
+        std::thread::current().name().map(String::from)
+    }
+
+    #[test]
+    fn default_thread_names() {
+        let names = WorkConfig::new()
+            .threads(3)
+            .divide_equal_work((0..30).collect(), thread_name);
+        assert_eq!(names[0].as_deref(), Some("and-conquer-0"));
+        assert_eq!(names[10].as_deref(), Some("and-conquer-1"));
+    }
+
+    #[test]
+    fn prefixed_thread_names() {
+        let config = WorkConfig::new().threads(3).thread_name_prefix("resize");
+
+        let names = config.divide_equal_work((0..30).collect(), thread_name);
+        assert_eq!(names[0].as_deref(), Some("resize-0"));
+        assert_eq!(names[10].as_deref(), Some("resize-1"));
+
+        let names = config.divide_work((0..30).collect(), thread_name);
+        let caller = std::thread::current().name().map(String::from);
+        assert!(names
+            .iter()
+            .all(|name| *name == caller || name.as_deref().unwrap().starts_with("resize-")));
+    }
+
+    #[test]
+    fn nested_calls_are_sequential() {
+        let config = WorkConfig::new().threads(2).threshold(2);
+        let inner_config = config.clone();
+        let output = config.divide_work((0..4).collect(), move |_| {
+            let outer = thread::current().id();
+            let innermost_config = inner_config.clone();
+            let (inner, decision) =
+                inner_config.divide_equal_work_with_decision((0..4).collect(), move |_| {
+                    let (innermost, decision) = innermost_config
+                        .divide_work_with_decision((0..4).collect(), |_| thread::current().id());
+                    (thread::current().id(), innermost, decision)
+                });
+            assert_eq!(decision, Decision::Nested);
+            for (thread, innermost, decision) in inner {
+                assert_eq!(decision, Decision::Nested);
+                assert_eq!(thread, outer);
+                assert!(innermost.iter().all(|&thread| thread == outer));
+            }
+            outer
+        });
+        assert_eq!(output.len(), 4);
+        // Calling thread is marked only while it takes part in the work
+        assert!(!super::in_worker());
+        let (_, decision) = config.divide_work_with_decision((0..4).collect(), |x| x);
+        assert!(decision.is_parallel());
+    }
+
+    #[test]
+    fn nesting_allowed() {
+        let config = WorkConfig::new().threads(2).threshold(2).nested(true);
+        let inner_config = config.clone();
+        let output = config.divide_work((0..4).collect(), move |_| {
+            inner_config
+                .divide_work_with_decision((0..4).collect(), |x| x)
+                .1
+        });
+        assert!(output.iter().all(Decision::is_parallel));
+    }
+
+    /// Recursion that needs roughly `depth` KiB of stack
+    fn recurse(depth: usize) -> usize {
+        let frame = std::hint::black_box([depth as u8; 1024]);
+        if depth == 0 {
+            0
+        } else {
+            recurse(depth - 1) + frame[0] as usize % 2
+        }
+    }
+
+    #[test]
+    fn worker_stack_size() {
+        const MIB: usize = 1024 * 1024;
+        // Default stack of spawned threads is 2 MiB, so this is deep enough to overflow it
+        let depth = 8 * 1024;
+
+        // Calling thread takes part in the work, so it is given a large stack as well
+        let caller = std::thread::Builder::new()
+            .stack_size(64 * MIB)
+            .spawn(move || {
+                let output = WorkConfig::new()
+                    .threads(4)
+                    .threshold(2)
+                    .stack_size(64 * MIB)
+                    .divide_equal_work(vec![depth; 4], recurse);
+                (output, recurse(depth))
+            });
+        let (output, expected) = caller.unwrap().join().unwrap();
+        assert_eq!(output, vec![expected; 4]);
+    }
+}
@@ -0,0 +1,37 @@
+use and_conquer::{divide_work_checked, WorkConfig, WorkError};
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static HOOK_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn panics_at_7(x: usize) -> usize {
+    assert_ne!(x, 7, "bad item");
+    x
+}
+
+#[test]
+fn checked_calls_panic_hook() {
+    // Replacing the hook suppresses default panic messages
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {
+        HOOK_CALLS.fetch_add(1, Ordering::Relaxed);
+    }));
+
+    let output = divide_work_checked((0..100).collect(), panics_at_7);
+    assert!(matches!(
+        output,
+        Err(WorkError::ItemPanicked { index: 7, .. })
+    ));
+    assert_eq!(HOOK_CALLS.load(Ordering::Relaxed), 1);
+
+    let output = WorkConfig::new()
+        .threads(4)
+        .divide_equal_work_checked((0..100).collect(), panics_at_7);
+    assert!(matches!(
+        output,
+        Err(WorkError::ItemPanicked { index: 7, .. })
+    ));
+    assert_eq!(HOOK_CALLS.load(Ordering::Relaxed), 2);
+
+    panic::set_hook(default_hook);
+}
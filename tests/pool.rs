@@ -0,0 +1,46 @@
+use and_conquer::{shutdown_pool, WorkConfig};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static EXITED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts exits of the threads that touched it
+struct ExitCounter;
+
+impl Drop for ExitCounter {
+    fn drop(&mut self) {
+        EXITED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    static EXIT_COUNTER: ExitCounter = const { ExitCounter };
+}
+
+#[test]
+fn shutdown_stops_idle_workers() {
+    let config = WorkConfig::new().threads(4);
+    let work = |x: usize| {
+        EXIT_COUNTER.with(|_| ());
+        x
+    };
+
+    for _ in 0..3 {
+        assert_eq!(
+            config.divide_equal_work((0..100).collect(), work).len(),
+            100
+        );
+    }
+    // Workers are parked instead of exiting, and reused by the following calls
+    assert_eq!(EXITED.load(Ordering::Relaxed), 0);
+
+    shutdown_pool();
+    assert_eq!(EXITED.load(Ordering::Relaxed), 3);
+
+    // Pool is refilled on demand
+    assert_eq!(
+        config.divide_equal_work((0..100).collect(), work).len(),
+        100
+    );
+    shutdown_pool();
+    assert_eq!(EXITED.load(Ordering::Relaxed), 6);
+}
@@ -0,0 +1,49 @@
+use and_conquer::{set_pool_idle_timeout, WorkConfig};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static EXITED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts exits of the threads that touched it
+struct ExitCounter;
+
+impl Drop for ExitCounter {
+    fn drop(&mut self) {
+        EXITED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    static EXIT_COUNTER: ExitCounter = const { ExitCounter };
+}
+
+#[test]
+fn idle_workers_exit() {
+    let config = WorkConfig::new().threads(4);
+    let caller = std::thread::current().id();
+    let work = move |x: usize| {
+        if std::thread::current().id() != caller {
+            EXIT_COUNTER.with(|_| ());
+        }
+        x
+    };
+
+    assert_eq!(
+        config.divide_equal_work((0..100).collect(), work).len(),
+        100
+    );
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(EXITED.load(Ordering::Relaxed), 0);
+
+    // Already parked workers pick up the timeout
+    set_pool_idle_timeout(Some(Duration::from_millis(10)));
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(EXITED.load(Ordering::Relaxed), 3);
+
+    // Workers are spawned again on demand
+    let output = config.divide_equal_work((0..100).collect(), work);
+    assert_eq!(output, (0..100).collect::<Vec<_>>());
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(EXITED.load(Ordering::Relaxed), 6);
+    set_pool_idle_timeout(None);
+}
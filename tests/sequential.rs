@@ -0,0 +1,54 @@
+//! Forced sequential mode is process-wide, so it is tested separately from the rest of the tests
+
+use and_conquer::{divide_equal_work, divide_work, set_sequential, Decision, WorkConfig};
+use std::panic;
+
+#[test]
+fn forced_sequential() {
+    set_sequential(true);
+
+    let caller = std::thread::current().id();
+    let on_caller = move |_| std::thread::current().id() == caller;
+    let configs = [
+        WorkConfig::new().threads(4).threshold(2),
+        WorkConfig::adaptive().threads(4),
+        WorkConfig::io_bound(8.0),
+    ];
+
+    assert_eq!(divide_work((0..100).collect(), on_caller), vec![true; 100]);
+    assert_eq!(
+        divide_equal_work((0..100).collect(), on_caller),
+        vec![true; 100]
+    );
+    for config in configs.iter() {
+        let (output, decision) = config.divide_work_with_decision((0..100).collect(), on_caller);
+        assert_eq!(output, vec![true; 100]);
+        assert_eq!(decision, Decision::ForcedSequential);
+
+        let (output, decision) =
+            config.divide_equal_work_with_decision((0..100).collect(), on_caller);
+        assert_eq!(output, vec![true; 100]);
+        assert_eq!(decision, Decision::ForcedSequential);
+    }
+
+    // Panic of `f` is propagated as is
+    let payload = panic::catch_unwind(|| {
+        WorkConfig::new()
+            .threads(4)
+            .divide_work((0..100).collect(), |x| {
+                if x == 50 {
+                    panic!("bad item")
+                } else {
+                    x
+                }
+            })
+    })
+    .unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"bad item"));
+
+    set_sequential(false);
+    let output = WorkConfig::new()
+        .threads(2)
+        .divide_equal_work((0..100).collect(), on_caller);
+    assert!(output.contains(&false));
+}